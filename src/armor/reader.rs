@@ -83,6 +83,21 @@ impl Serialize for BlockType {
     }
 }
 
+impl BlockType {
+    /// Is this one of the PEM armor types, used for keys outside of OpenPGP?
+    fn is_pem(&self) -> bool {
+        matches!(
+            self,
+            BlockType::PublicKeyPKCS1(_)
+                | BlockType::PublicKeyPKCS8
+                | BlockType::PublicKeyOpenssh
+                | BlockType::PrivateKeyPKCS1(_)
+                | BlockType::PrivateKeyPKCS8
+                | BlockType::PrivateKeyOpenssh
+        )
+    }
+}
+
 /// OpenSSL PKCS#1 PEM armor types
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum PKCS1Type {
@@ -318,7 +333,13 @@ pub fn header_parser(i: &[u8]) -> IResult<&[u8], (BlockType, Headers, bool)> {
     let (i, (typ, headers)) = armor_header(i)?;
 
     // "A blank (zero length or containing only whitespace) line"
-    let (i, _) = pair(space0, line_ending).parse(i)?;
+    //
+    // PEM encoded keys (RFC 7468) have no headers, and usually no blank line either.
+    let (i, _) = if typ.is_pem() {
+        opt(pair(space0, line_ending)).parse(i)?
+    } else {
+        map(pair(space0, line_ending), Some).parse(i)?
+    };
 
     Ok((i, (typ, headers, has_leading_data)))
 }
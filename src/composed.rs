@@ -1,12 +1,16 @@
 mod any;
+mod autocrypt;
 mod cleartext;
+mod foreign;
 mod key;
 mod message;
 mod shared;
 mod signature;
 mod signed_key;
+mod store;
+mod wkd;
 
 pub use self::{
-    any::Any, cleartext::CleartextSignedMessage, key::*, message::*, shared::Deserializable,
-    signature::*, signed_key::*,
+    any::Any, autocrypt::*, cleartext::CleartextSignedMessage, foreign::*, key::*, message::*,
+    shared::Deserializable, signature::*, signed_key::*, store::*, wkd::*,
 };
@@ -1,12 +1,16 @@
 use std::io::{BufRead, BufReader, Read};
 
+use chrono::{DateTime, Utc};
+use zeroize::Zeroizing;
+
 use crate::{
     armor::{self, BlockType, Dearmor},
     composed::{
-        cleartext::CleartextSignedMessage, Deserializable, Message, SignedPublicKey,
-        SignedSecretKey, StandaloneSignature,
+        cleartext::CleartextSignedMessage, import_public_key, import_secret_key, Deserializable,
+        Message, SignedPublicKey, SignedSecretKey, StandaloneSignature,
     },
     errors::{ensure, unimplemented_err, Result},
+    packet,
 };
 
 /// A flexible representation of what can be represented in an armor file.
@@ -18,6 +22,14 @@ pub enum Any<'a> {
     SecretKey(SignedSecretKey),
     Message(Message<'a>),
     Signature(StandaloneSignature),
+    /// A public key in PKCS#1, PKCS#8 or OpenSSH format.
+    ///
+    /// Its creation time is set to the Unix epoch, see [`import_public_key`].
+    UnsignedPublicKey(packet::PublicKey),
+    /// A secret key in PKCS#1, SEC1, PKCS#8 or OpenSSH format.
+    ///
+    /// Its creation time is set to the Unix epoch, see [`import_secret_key`].
+    UnsignedSecretKey(packet::SecretKey),
 }
 
 impl<'a> Any<'a> {
@@ -71,6 +83,25 @@ impl<'a> Any<'a> {
                     CleartextSignedMessage::from_armor_after_header(rest, headers, limit)?;
                 Ok((Self::Cleartext(sig), headers))
             }
+            // Keys in other formats
+            BlockType::PublicKeyPKCS1(_)
+            | BlockType::PublicKeyPKCS8
+            | BlockType::PublicKeyOpenssh => {
+                let mut dearmor = Dearmor::after_header(typ, headers.clone(), rest, limit);
+                let mut data = Vec::new();
+                dearmor.read_to_end(&mut data)?;
+                let key = import_public_key(typ, &data, DateTime::<Utc>::UNIX_EPOCH)?;
+                Ok((Self::UnsignedPublicKey(key), headers))
+            }
+            BlockType::PrivateKeyPKCS1(_)
+            | BlockType::PrivateKeyPKCS8
+            | BlockType::PrivateKeyOpenssh => {
+                let mut dearmor = Dearmor::after_header(typ, headers.clone(), rest, limit);
+                let mut data = Zeroizing::new(Vec::new());
+                dearmor.read_to_end(&mut data)?;
+                let key = import_secret_key(typ, &data, DateTime::<Utc>::UNIX_EPOCH)?;
+                Ok((Self::UnsignedSecretKey(key), headers))
+            }
             _ => unimplemented_err!("unsupported block type: {}", typ),
         }
     }
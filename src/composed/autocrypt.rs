@@ -0,0 +1,557 @@
+//! Autocrypt headers and Autocrypt Setup Messages.
+//!
+//! Ref: <https://autocrypt.org/level1.html>
+
+use std::{fmt, str::FromStr};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Utc};
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    armor,
+    composed::{
+        ArmorOptions, Deserializable, ExportOptions, Message, MessageBuilder, SignedPublicKey,
+        SignedSecretKey,
+    },
+    crypto::sym::SymmetricKeyAlgorithm,
+    errors::{bail, ensure, ensure_eq, format_err, Result},
+    packet::KeyFlags,
+    ser::Serialize,
+    types::{KeyDetails, Password, PublicKeyTrait, StringToKey},
+};
+
+/// Name of the email header.
+const HEADER_NAME: &str = "Autocrypt";
+
+/// Maximum length of the lines of a folded header.
+const MAX_LINE_LEN: usize = 76;
+
+/// Armor header of the key in a setup message, carrying the [`PreferEncrypt`] setting.
+const PREFER_ENCRYPT_HEADER: &str = "Autocrypt-Prefer-Encrypt";
+
+/// The only passphrase format defined for setup messages: 9 blocks of 4 digits.
+const PASSPHRASE_FORMAT: &str = "numeric9x4";
+
+const SETUP_CODE_BLOCKS: usize = 9;
+const SETUP_CODE_BLOCK_LEN: usize = 4;
+
+/// The `prefer-encrypt` attribute of an Autocrypt header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PreferEncrypt {
+    #[default]
+    NoPreference,
+    /// The user wants encryption, if the other party agrees.
+    Mutual,
+}
+
+/// An `Autocrypt:` email header.
+///
+/// Use [`AutocryptHeader::from_str`] to parse the header value, and its [`fmt::Display`]
+/// implementation to produce it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutocryptHeader {
+    /// The email address of the sender.
+    pub addr: String,
+    pub prefer_encrypt: PreferEncrypt,
+    /// The key of the sender.
+    pub keydata: SignedPublicKey,
+}
+
+impl AutocryptHeader {
+    /// Creates a header for `addr`, with a minimized copy of `key`.
+    ///
+    /// See [`Self::new_at`].
+    pub fn new(
+        addr: impl Into<String>,
+        prefer_encrypt: PreferEncrypt,
+        key: &SignedPublicKey,
+    ) -> Result<Self> {
+        Self::new_at(addr, prefer_encrypt, key, Utc::now())
+    }
+
+    /// Creates a header for `addr`, with a minimized copy of `key`, evaluating the key at
+    /// `reference_time`.
+    ///
+    /// The key is reduced to the primary key, the user ID matching `addr` and the newest
+    /// encryption subkey, each with only its authoritative self-signature.
+    pub fn new_at(
+        addr: impl Into<String>,
+        prefer_encrypt: PreferEncrypt,
+        key: &SignedPublicKey,
+        reference_time: DateTime<Utc>,
+    ) -> Result<Self> {
+        let addr = addr.into();
+        let keydata = minimize(key, &addr, reference_time)?;
+
+        Ok(AutocryptHeader {
+            addr,
+            prefer_encrypt,
+            keydata,
+        })
+    }
+
+    /// Returns the complete header, including its name, folded into lines of at most
+    /// 76 characters.
+    ///
+    /// Only an address that is too long to fit on the first line by itself exceeds this.
+    pub fn to_header(&self) -> Result<String> {
+        let mut header = format!("{HEADER_NAME}: addr={};", self.addr);
+        let mut line_len = header.len();
+
+        let mut attributes = Vec::new();
+        if self.prefer_encrypt == PreferEncrypt::Mutual {
+            attributes.push(" prefer-encrypt=mutual;");
+        }
+        attributes.push(" keydata=");
+        for attribute in attributes {
+            // the leading space of the attribute starts the continuation line
+            if line_len + attribute.len() > MAX_LINE_LEN {
+                header.push_str("\r\n");
+                line_len = 0;
+            }
+            header.push_str(attribute);
+            line_len += attribute.len();
+        }
+
+        let keydata = STANDARD.encode(self.keydata.to_bytes()?);
+        let mut rest = keydata.as_str();
+        while !rest.is_empty() {
+            // continuation lines start with a space
+            let (line, tail) = rest.split_at(rest.len().min(MAX_LINE_LEN - 1));
+            header.push_str("\r\n ");
+            header.push_str(line);
+            rest = tail;
+        }
+
+        Ok(header)
+    }
+}
+
+impl fmt::Display for AutocryptHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "addr={};", self.addr)?;
+        if self.prefer_encrypt == PreferEncrypt::Mutual {
+            write!(f, " prefer-encrypt=mutual;")?;
+        }
+        let keydata = self.keydata.to_bytes().map_err(|_| fmt::Error)?;
+        write!(f, " keydata={}", STANDARD.encode(keydata))
+    }
+}
+
+impl FromStr for AutocryptHeader {
+    type Err = crate::errors::Error;
+
+    /// Parses the value of an `Autocrypt:` header.
+    ///
+    /// The header name may be included, folding whitespace is ignored.
+    fn from_str(s: &str) -> Result<Self> {
+        let value = match s.split_once(':') {
+            Some((name, value)) if name.trim().eq_ignore_ascii_case(HEADER_NAME) => value,
+            _ => s,
+        };
+
+        let mut addr = None;
+        let mut prefer_encrypt = None;
+        let mut keydata = None;
+
+        for attribute in value.split(';') {
+            let attribute = attribute.trim();
+            if attribute.is_empty() {
+                continue;
+            }
+            let Some((name, value)) = attribute.split_once('=') else {
+                bail!("invalid autocrypt attribute {:?}", attribute);
+            };
+            let name = name.trim();
+            let value = value.trim();
+
+            let slot = match name {
+                "addr" => &mut addr,
+                "prefer-encrypt" => &mut prefer_encrypt,
+                "keydata" => &mut keydata,
+                // Attributes starting with an underscore are optional.
+                _ if name.starts_with('_') => continue,
+                _ => bail!("unknown critical autocrypt attribute {:?}", name),
+            };
+            ensure!(slot.is_none(), "duplicate autocrypt attribute {:?}", name);
+            *slot = Some(value);
+        }
+
+        let addr = addr.ok_or_else(|| format_err!("missing autocrypt attribute addr"))?;
+        let keydata = keydata.ok_or_else(|| format_err!("missing autocrypt attribute keydata"))?;
+
+        let keydata: String = keydata
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect();
+        let keydata = STANDARD
+            .decode(keydata)
+            .map_err(|e| format_err!("invalid autocrypt keydata: {}", e))?;
+        let keydata = SignedPublicKey::from_bytes(&keydata[..])?;
+
+        let prefer_encrypt = match prefer_encrypt {
+            Some("mutual") => PreferEncrypt::Mutual,
+            _ => PreferEncrypt::NoPreference,
+        };
+
+        Ok(AutocryptHeader {
+            addr: addr.to_string(),
+            prefer_encrypt,
+            keydata,
+        })
+    }
+}
+
+/// The setup code protecting an Autocrypt Setup Message.
+///
+/// This consists of 36 random digits, displayed in 9 blocks of 4 digits separated by dashes.
+#[derive(Clone, PartialEq, Eq, derive_more::Debug)]
+#[debug("SetupCode(..)")]
+pub struct SetupCode(String);
+
+impl SetupCode {
+    /// Generates a new, random setup code.
+    pub fn generate<R: CryptoRng + Rng>(mut rng: R) -> Self {
+        let digits: String = (0..SETUP_CODE_BLOCKS * SETUP_CODE_BLOCK_LEN)
+            .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+            .collect();
+
+        Self::from_digits(&digits)
+    }
+
+    fn from_digits(digits: &str) -> Self {
+        let blocks: Vec<&str> = (0..SETUP_CODE_BLOCKS)
+            .map(|i| &digits[i * SETUP_CODE_BLOCK_LEN..(i + 1) * SETUP_CODE_BLOCK_LEN])
+            .collect();
+
+        SetupCode(blocks.join("-"))
+    }
+
+    /// The setup code, formatted with dashes. This is the passphrase of the setup message.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The first two digits, which are given as a hint in the setup message.
+    pub fn begin(&self) -> &str {
+        &self.0[..2]
+    }
+}
+
+impl FromStr for SetupCode {
+    type Err = crate::errors::Error;
+
+    /// Parses a setup code as entered by a user, ignoring whitespace and dashes.
+    fn from_str(s: &str) -> Result<Self> {
+        let digits: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect();
+        ensure_eq!(
+            digits.len(),
+            SETUP_CODE_BLOCKS * SETUP_CODE_BLOCK_LEN,
+            "invalid setup code length"
+        );
+        ensure!(
+            digits.chars().all(|c| c.is_ascii_digit()),
+            "setup code must only contain digits"
+        );
+
+        Ok(Self::from_digits(&digits))
+    }
+}
+
+impl fmt::Display for SetupCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl SignedSecretKey {
+    /// Creates an Autocrypt Setup Message, transferring this key to another device.
+    ///
+    /// Returns the armored message, encrypted with `setup_code`. Wrapping it into an email
+    /// is left to the caller.
+    pub fn to_autocrypt_setup_message<R: CryptoRng + Rng>(
+        &self,
+        mut rng: R,
+        prefer_encrypt: PreferEncrypt,
+        setup_code: &SetupCode,
+    ) -> Result<String> {
+        let mut key_headers = armor::Headers::new();
+        if prefer_encrypt == PreferEncrypt::Mutual {
+            key_headers.insert(PREFER_ENCRYPT_HEADER.to_string(), vec!["mutual".into()]);
+        }
+        let key = self.to_armored_string(ArmorOptions::from(Some(&key_headers)))?;
+
+        let mut builder =
+            MessageBuilder::from_bytes("", key).seipd_v1(&mut rng, SymmetricKeyAlgorithm::AES128);
+        builder.encrypt_with_password(
+            StringToKey::new_default(&mut rng),
+            &Password::from(setup_code.as_str()),
+        )?;
+
+        let mut headers = armor::Headers::new();
+        headers.insert(
+            "Passphrase-Format".to_string(),
+            vec![PASSPHRASE_FORMAT.into()],
+        );
+        headers.insert(
+            "Passphrase-Begin".to_string(),
+            vec![setup_code.begin().into()],
+        );
+
+        builder.to_armored_string(rng, ArmorOptions::from(Some(&headers)))
+    }
+
+    /// Decrypts an Autocrypt Setup Message, returning the contained key and its
+    /// [`PreferEncrypt`] setting.
+    ///
+    /// `message` may contain surrounding text, such as the HTML attachment of the setup email.
+    pub fn from_autocrypt_setup_message(
+        message: &str,
+        setup_code: &SetupCode,
+    ) -> Result<(Self, PreferEncrypt)> {
+        const BEGIN: &str = "-----BEGIN PGP MESSAGE-----";
+        const END: &str = "-----END PGP MESSAGE-----";
+
+        let start = message
+            .find(BEGIN)
+            .ok_or_else(|| format_err!("no armored message found"))?;
+        let end = message[start..]
+            .find(END)
+            .map(|end| start + end + END.len())
+            .ok_or_else(|| format_err!("armored message is not terminated"))?;
+
+        let (message, headers) = Message::from_string(&message[start..end])?;
+        if let Some(format) = headers.get("Passphrase-Format") {
+            ensure!(
+                format.iter().all(|f| f == PASSPHRASE_FORMAT),
+                "unsupported passphrase format {:?}",
+                format
+            );
+        }
+
+        let mut message = message.decrypt_with_password(&Password::from(setup_code.as_str()))?;
+        let data = message.as_data_vec()?;
+
+        let (key, headers) = SignedSecretKey::from_armor_single(&data[..])?;
+        let prefer_encrypt = match headers.get(PREFER_ENCRYPT_HEADER) {
+            Some(values) if values.iter().any(|v| v == "mutual") => PreferEncrypt::Mutual,
+            _ => PreferEncrypt::NoPreference,
+        };
+
+        Ok((key, prefer_encrypt))
+    }
+}
+
+/// Reduces `key` to what is needed to encrypt to `addr`.
+fn minimize(
+    key: &SignedPublicKey,
+    addr: &str,
+    reference_time: DateTime<Utc>,
+) -> Result<SignedPublicKey> {
+    let validity = key.validity_at(reference_time);
+    ensure!(
+        validity.is_valid(),
+        "key {} is not valid: {:?}",
+        key.fingerprint(),
+        validity.status()
+    );
+
+    let options = ExportOptions::minimal().with_user_ids([addr]);
+    let mut minimal = key.export_at(&options, reference_time);
+
+    let validity = minimal.validity_at(reference_time);
+    ensure!(
+        validity.valid_users().next().is_some(),
+        "key {} has no user ID for {}",
+        key.fingerprint(),
+        addr
+    );
+
+    let is_encryption = |flags: KeyFlags| flags.encrypt_comms() || flags.encrypt_storage();
+    let subkey = validity
+        .valid_subkeys()
+        .filter(|s| is_encryption(s.key_flags()) && s.key.is_encryption_key())
+        .max_by_key(|s| *s.key.created_at())
+        .map(|s| s.key.fingerprint());
+
+    if subkey.is_none()
+        && !(is_encryption(validity.key_flags()) && key.primary_key.is_encryption_key())
+    {
+        bail!("key {} has no valid encryption key", key.fingerprint());
+    }
+
+    minimal
+        .public_subkeys
+        .retain(|s| Some(s.key.fingerprint()) == subkey);
+
+    Ok(minimal)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    fn alice() -> (SignedPublicKey, SignedSecretKey) {
+        let (public, _) =
+            SignedPublicKey::from_armor_file("./tests/autocrypt/alice@autocrypt.example.pub.asc")
+                .unwrap();
+        let (secret, _) =
+            SignedSecretKey::from_armor_file("./tests/autocrypt/alice@autocrypt.example.sec.asc")
+                .unwrap();
+        (public, secret)
+    }
+
+    /// The example keys have expired, evaluate them when they were valid.
+    fn header_at_creation(
+        addr: &str,
+        prefer_encrypt: PreferEncrypt,
+        key: &SignedPublicKey,
+    ) -> Result<AutocryptHeader> {
+        let reference_time = *key.primary_key.created_at() + chrono::Duration::days(1);
+        AutocryptHeader::new_at(addr, prefer_encrypt, key, reference_time)
+    }
+
+    #[test]
+    fn header_roundtrip() {
+        let (public, _) = alice();
+
+        let header =
+            header_at_creation("Alice@Autocrypt.example", PreferEncrypt::Mutual, &public).unwrap();
+        // the example key is already minimal
+        assert_eq!(header.keydata, public);
+
+        let value = header.to_string();
+        assert!(value.starts_with("addr=Alice@Autocrypt.example; prefer-encrypt=mutual; keydata=mDMEXEcE6RYJKwYBBAHaRw8BAQdArjWwk3FAqyiFbFBKT4TzXcVBqPTB3gmzlC/U"));
+        assert_eq!(value.parse::<AutocryptHeader>().unwrap(), header);
+
+        let folded = header.to_header().unwrap();
+        assert!(folded.lines().all(|l| l.len() <= MAX_LINE_LEN));
+        assert_eq!(folded.parse::<AutocryptHeader>().unwrap(), header);
+
+        let header = header_at_creation(
+            "alice@autocrypt.example",
+            PreferEncrypt::NoPreference,
+            &public,
+        )
+        .unwrap();
+        assert!(!header.to_string().contains("prefer-encrypt"));
+
+        assert!(
+            header_at_creation("bob@autocrypt.example", PreferEncrypt::Mutual, &public).is_err()
+        );
+    }
+
+    #[test]
+    fn header_fold_long_addr() {
+        let (public, _) = alice();
+
+        // folded before prefer-encrypt, or only before keydata
+        for addr in [
+            format!("{}@autocrypt.example", "a".repeat(32)),
+            format!("{}@autocrypt.example", "a".repeat(10)),
+        ] {
+            let header = AutocryptHeader {
+                addr,
+                prefer_encrypt: PreferEncrypt::Mutual,
+                keydata: public.clone(),
+            };
+            let folded = header.to_header().unwrap();
+            assert!(folded.lines().all(|l| l.len() <= MAX_LINE_LEN), "{folded}");
+            assert!(folded.lines().skip(1).all(|l| l.starts_with(' ')));
+            assert_eq!(folded.parse::<AutocryptHeader>().unwrap(), header);
+        }
+    }
+
+    #[test]
+    fn header_minimize() {
+        let (public, _) = alice();
+        let mut key = public.clone();
+        // an additional user ID, and a duplicate of the self-signature
+        let mut other = key.details.users[0].clone();
+        other.id =
+            crate::packet::UserId::from_str(Default::default(), "other@example.org").unwrap();
+        key.details.users.push(other);
+        let sig = key.details.users[0].signatures[0].clone();
+        key.details.users[0].signatures.push(sig);
+
+        let header =
+            header_at_creation("alice@autocrypt.example", PreferEncrypt::Mutual, &key).unwrap();
+        assert_eq!(header.keydata, public);
+    }
+
+    #[test]
+    fn header_parse() {
+        let (public, _) = alice();
+        let keydata = STANDARD.encode(public.to_bytes().unwrap());
+
+        let header: AutocryptHeader =
+            format!("addr=alice@autocrypt.example; _extra=1; keydata={keydata}")
+                .parse()
+                .unwrap();
+        assert_eq!(header.prefer_encrypt, PreferEncrypt::NoPreference);
+        assert_eq!(header.keydata, public);
+
+        // unknown values of prefer-encrypt mean no preference
+        let header: AutocryptHeader =
+            format!("addr=alice@autocrypt.example; prefer-encrypt=other; keydata={keydata}")
+                .parse()
+                .unwrap();
+        assert_eq!(header.prefer_encrypt, PreferEncrypt::NoPreference);
+
+        for invalid in [
+            format!("addr=alice@autocrypt.example; extra=1; keydata={keydata}"),
+            format!("addr=alice@autocrypt.example; addr=a@b; keydata={keydata}"),
+            format!("keydata={keydata}"),
+            "addr=alice@autocrypt.example".to_string(),
+            "addr=alice@autocrypt.example; keydata=!!!".to_string(),
+        ] {
+            assert!(invalid.parse::<AutocryptHeader>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn setup_code() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let code = SetupCode::generate(&mut rng);
+        assert_eq!(code.as_str().len(), 44);
+        assert_eq!(code.as_str().split('-').count(), 9);
+        assert_eq!(code.begin(), &code.as_str()[..2]);
+
+        let entered = code.as_str().replace('-', " ");
+        assert_eq!(entered.parse::<SetupCode>().unwrap(), code);
+
+        assert!("1234-5678".parse::<SetupCode>().is_err());
+        assert!("a".repeat(36).parse::<SetupCode>().is_err());
+    }
+
+    #[test]
+    fn setup_message_roundtrip() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let (_, secret) = alice();
+        let code = SetupCode::generate(&mut rng);
+
+        let message = secret
+            .to_autocrypt_setup_message(&mut rng, PreferEncrypt::Mutual, &code)
+            .unwrap();
+        assert!(message.contains("Passphrase-Format: numeric9x4"));
+        assert!(message.contains(&format!("Passphrase-Begin: {}", code.begin())));
+
+        let html = format!("<html><body><pre>\n{message}</pre></body></html>");
+        let (key, prefer_encrypt) =
+            SignedSecretKey::from_autocrypt_setup_message(&html, &code).unwrap();
+        assert_eq!(key, secret);
+        assert_eq!(prefer_encrypt, PreferEncrypt::Mutual);
+
+        let other = SetupCode::generate(&mut rng);
+        assert!(SignedSecretKey::from_autocrypt_setup_message(&message, &other).is_err());
+    }
+}
@@ -11,12 +11,16 @@ use log::debug;
 
 use crate::{
     armor::{self, header_parser, read_from_buf, BlockType, Headers},
-    composed::{ArmorOptions, Deserializable, StandaloneSignature},
+    composed::{
+        ArmorOptions, Deserializable, SignedPublicKey, SignedSecretKey, StandaloneSignature,
+        VerificationReport,
+    },
     crypto::hash::HashAlgorithm,
     errors::{bail, ensure, ensure_eq, format_err, InvalidInputSnafu, Result},
     line_writer::LineBreak,
     normalize_lines::{normalize_lines, NormalizedReader},
     packet::{Signature, SignatureConfig, SignatureType, Subpacket, SubpacketData},
+    policy::Policy,
     types::{KeyVersion, Password, PublicKeyTrait, SecretKeyTrait},
     MAX_BUFFER_SIZE,
 };
@@ -86,6 +90,21 @@ where {
         Self::new(text, config, key, key_pw)
     }
 
+    /// Sign the given text with the currently valid signing key of `key`.
+    ///
+    /// See [`SignedSecretKey::signing_key`] for how the signing key is selected.
+    pub fn sign_with_key<R>(
+        rng: R,
+        text: &str,
+        key: &SignedSecretKey,
+        key_pw: &Password,
+    ) -> Result<Self>
+    where
+        R: rand::Rng + rand::CryptoRng,
+    {
+        Self::sign(rng, text, &key.signing_key()?, key_pw)
+    }
+
     /// Sign the same message with multiple keys.
     ///
     /// The signer function gets invoked with the normalized original text to be signed,
@@ -135,6 +154,25 @@ where {
         bail!("No matching signature found")
     }
 
+    /// Verify each signature against the normalized cleartext, looking up the signers in
+    /// `certs`, and report on each of them. Only signatures and keys that `policy` accepts
+    /// are valid.
+    pub fn verify_reports<'a>(
+        &'a self,
+        certs: &[&'a SignedPublicKey],
+        policy: &dyn Policy,
+    ) -> Vec<VerificationReport<'a>> {
+        let nt = self.signed_text();
+        self.signatures
+            .iter()
+            .map(|signature| {
+                VerificationReport::new(&signature.signature, certs, policy, |key| {
+                    signature.signature.verify(key, nt.as_bytes())
+                })
+            })
+            .collect()
+    }
+
     /// Verify each signature, potentially against a different key.
     pub fn verify_many<F>(&self, verifier: F) -> Result<()>
     where
@@ -391,7 +429,10 @@ mod tests {
     use rand_chacha::ChaCha8Rng;
 
     use super::*;
-    use crate::composed::{Any, SignedPublicKey, SignedSecretKey};
+    use crate::{
+        composed::{Any, SignedPublicKey, SignedSecretKey},
+        types::KeyDetails,
+    };
 
     #[test]
     fn test_cleartext_openpgp_1() {
@@ -651,6 +692,23 @@ mod tests {
         msg.verify(&*key.public_key()).unwrap();
     }
 
+    #[test]
+    fn test_sign_with_key() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        let key_data = std::fs::read_to_string("./tests/unit-tests/cleartext-key-01.asc").unwrap();
+        let (key, _) = SignedSecretKey::from_string(&key_data).unwrap();
+        let msg =
+            CleartextSignedMessage::sign_with_key(&mut rng, "hello\n", &key, &Password::empty())
+                .unwrap();
+
+        let signer = key.signing_key().unwrap();
+        let issuer = msg.signatures()[0].signature.issuer_fingerprint();
+        assert_eq!(issuer, vec![&signer.fingerprint()]);
+        assert!(signer.is_primary());
+        msg.verify(&*key.public_key()).unwrap();
+    }
+
     #[test]
     fn test_load_big_csf() {
         let msg_data = std::fs::read_to_string("./tests/unit-tests/csf-puppet/InRelease").unwrap();
@@ -0,0 +1,102 @@
+//! Conversion of keys from and to formats used outside of OpenPGP.
+//!
+//! Supported are PKCS#1 (and SEC1 for EC keys), PKCS#8 and the OpenSSH key formats, for RSA,
+//! ECDSA over the NIST curves and Ed25519.
+//!
+//! Imported keys are bare v4 key packets without any signatures, Ed25519 keys use the
+//! EdDSALegacy algorithm. As none of these formats records a creation time, it has to be
+//! provided, and is part of the fingerprint of the resulting key.
+//!
+//! # Exporting an authentication subkey for use with OpenSSH
+//!
+//! ```rust
+//! # use pgp::composed::{Deserializable, SignedSecretKey};
+//! use pgp::composed::export_openssh_secret_key;
+//! use pgp::types::Password;
+//!
+//! # let (key, _) = SignedSecretKey::from_armor_file("./tests/autocrypt/alice@autocrypt.example.sec.asc")?;
+//! for subkey in &key.secret_subkeys {
+//!     if subkey.signatures.iter().any(|sig| sig.key_flags().authentication()) {
+//!         let pem = subkey
+//!             .key
+//!             .unlock(&Password::empty(), |_, secret| {
+//!                 export_openssh_secret_key(secret, "alice@autocrypt.example")
+//!             })??;
+//!         std::fs::write("id_openpgp", pem.as_bytes())?;
+//!     }
+//! }
+//! # Ok::<(), pgp::errors::Error>(())
+//! ```
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    armor::{BlockType, PKCS1Type},
+    crypto::public_key::PublicKeyAlgorithm,
+    errors::{unsupported_err, Result},
+    packet::{PubKeyInner, PublicKey, SecretKey},
+    types::{KeyVersion, PlainSecretParams, PublicParams, SecretParams},
+};
+
+mod openssh;
+mod pkcs;
+
+pub use self::{openssh::*, pkcs::*};
+
+/// Parses a public key of a non-OpenPGP format, as indicated by the armor block type `typ`.
+///
+/// `data` is the content of the block, after removing the base64 encoding.
+pub fn import_public_key(
+    typ: BlockType,
+    data: &[u8],
+    created_at: DateTime<Utc>,
+) -> Result<PublicKey> {
+    let params = match typ {
+        BlockType::PublicKeyPKCS1(PKCS1Type::RSA) => pkcs::public_params_from_pkcs1(data)?,
+        BlockType::PublicKeyPKCS8 => pkcs::public_params_from_pkcs8(data)?,
+        BlockType::PublicKeyOpenssh => openssh::public_params_from_blob(data)?,
+        _ => unsupported_err!("public key import from {}", typ),
+    };
+
+    public_key(params, created_at)
+}
+
+/// Parses a secret key of a non-OpenPGP format, as indicated by the armor block type `typ`.
+///
+/// `data` is the content of the block, after removing the base64 encoding.
+/// Password protected keys are not supported.
+pub fn import_secret_key(
+    typ: BlockType,
+    data: &[u8],
+    created_at: DateTime<Utc>,
+) -> Result<SecretKey> {
+    let secret = match typ {
+        BlockType::PrivateKeyPKCS1(PKCS1Type::RSA) => pkcs::secret_params_from_pkcs1(data)?,
+        BlockType::PrivateKeyPKCS1(PKCS1Type::EC) => pkcs::secret_params_from_sec1(data)?,
+        BlockType::PrivateKeyPKCS8 => pkcs::secret_params_from_pkcs8(data)?,
+        BlockType::PrivateKeyOpenssh => openssh::secret_params_from_openssh(data)?,
+        _ => unsupported_err!("secret key import from {}", typ),
+    };
+
+    secret_key(secret, created_at)
+}
+
+/// Wraps imported public key material into a v4 key packet.
+fn public_key(params: PublicParams, created_at: DateTime<Utc>) -> Result<PublicKey> {
+    let algorithm = match params {
+        PublicParams::RSA(_) => PublicKeyAlgorithm::RSA,
+        PublicParams::ECDSA(_) => PublicKeyAlgorithm::ECDSA,
+        PublicParams::EdDSALegacy(_) => PublicKeyAlgorithm::EdDSALegacy,
+        _ => unsupported_err!("key import for {:?}", params),
+    };
+    let inner = PubKeyInner::new(KeyVersion::V4, algorithm, created_at, None, params)?;
+
+    PublicKey::from_inner(inner)
+}
+
+/// Wraps imported secret key material into a v4 key packet.
+fn secret_key(secret: PlainSecretParams, created_at: DateTime<Utc>) -> Result<SecretKey> {
+    let public = public_key(PublicParams::try_from(&secret)?, created_at)?;
+
+    SecretKey::new(public, SecretParams::Plain(secret))
+}
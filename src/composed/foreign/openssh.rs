@@ -0,0 +1,492 @@
+use base64::engine::{general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Utc};
+use elliptic_curve::sec1::ToEncodedPoint;
+use num_bigint::{BigUint, ModInverse};
+use rsa::{
+    pkcs8::{der::pem, LineEnding},
+    traits::{PrivateKeyParts, PublicKeyParts},
+    RsaPrivateKey, RsaPublicKey,
+};
+use zeroize::Zeroizing;
+
+use crate::{
+    composed::SignedPublicKey,
+    crypto::{ecdsa, ed25519},
+    errors::{bail, ensure, ensure_eq, format_err, unsupported_err, Result},
+    packet::PublicKey,
+    types::{
+        EcdsaPublicParams, EddsaLegacyPublicParams, KeyDetails, PlainSecretParams, PublicKeyTrait,
+        PublicParams, RsaPublicParams,
+    },
+};
+
+/// Magic at the start of an `OPENSSH PRIVATE KEY`.
+const AUTH_MAGIC: &[u8] = b"openssh-key-v1\0";
+
+/// Block size of the private section of an unencrypted `OPENSSH PRIVATE KEY`.
+const BLOCK_SIZE: usize = 8;
+
+/// Parses a public key in the format of an OpenSSH `authorized_keys` line:
+/// `<key type> <base64 encoded key> [comment]`.
+///
+/// Returns the key and the comment. Options in front of the key type are not supported.
+pub fn import_openssh_public_key(
+    line: &str,
+    created_at: DateTime<Utc>,
+) -> Result<(PublicKey, String)> {
+    let mut parts = line.trim().splitn(3, char::is_whitespace);
+    let typ = parts.next().unwrap_or_default();
+    let Some(blob) = parts.next() else {
+        bail!("invalid OpenSSH public key");
+    };
+    let comment = parts.next().unwrap_or_default().trim().to_string();
+
+    let blob = STANDARD.decode(blob)?;
+    let params = public_params_from_blob(&blob)?;
+    ensure_eq!(
+        typ,
+        key_type(&params)?,
+        "OpenSSH key type does not match the key"
+    );
+
+    Ok((super::public_key(params, created_at)?, comment))
+}
+
+/// Encodes the public key material of an RSA, ECDSA or Ed25519 key as a line for an
+/// OpenSSH `authorized_keys` file.
+pub fn export_openssh_public_key(params: &PublicParams, comment: &str) -> Result<String> {
+    let mut line = format!(
+        "{} {}",
+        key_type(params)?,
+        STANDARD.encode(public_blob(params)?)
+    );
+    if !comment.is_empty() {
+        line.push(' ');
+        line.push_str(comment);
+    }
+
+    Ok(line)
+}
+
+/// Encodes the secret key material of an RSA, ECDSA or Ed25519 key as an unencrypted
+/// `OPENSSH PRIVATE KEY`.
+pub fn export_openssh_secret_key(
+    secret: &PlainSecretParams,
+    comment: &str,
+) -> Result<Zeroizing<String>> {
+    let public = PublicParams::try_from(secret)?;
+    let public_blob = public_blob(&public)?;
+
+    // Unencrypted keys only use the check value to detect a wrong passphrase,
+    // so derive it from the public key instead of requiring an rng.
+    let check = public_blob
+        .iter()
+        .fold(0u32, |acc, b| acc.rotate_left(8) ^ u32::from(*b));
+
+    let mut private = Zeroizing::new(Vec::new());
+    private.extend_from_slice(&check.to_be_bytes());
+    private.extend_from_slice(&check.to_be_bytes());
+    put_string(&mut private, key_type(&public)?.as_bytes());
+    match secret {
+        PlainSecretParams::RSA(key) => {
+            let [p, q] = key.primes() else {
+                bail!("RSA keys with more than two primes are not supported");
+            };
+            let iqmp = q
+                .clone()
+                .mod_inverse(p)
+                .and_then(|iqmp| iqmp.to_biguint())
+                .ok_or_else(|| format_err!("invalid RSA key"))?;
+            put_mpint(&mut private, &key.n().to_bytes_be());
+            put_mpint(&mut private, &key.e().to_bytes_be());
+            put_mpint(&mut private, &Zeroizing::new(key.d().to_bytes_be()));
+            put_mpint(&mut private, &Zeroizing::new(iqmp.to_bytes_be()));
+            put_mpint(&mut private, &Zeroizing::new(p.to_bytes_be()));
+            put_mpint(&mut private, &Zeroizing::new(q.to_bytes_be()));
+        }
+        PlainSecretParams::ECDSA(key) => {
+            let (curve, point, scalar) = match key {
+                ecdsa::SecretKey::P256(key) => (
+                    "nistp256",
+                    key.public_key().to_encoded_point(false).as_bytes().to_vec(),
+                    Zeroizing::new(key.to_bytes().to_vec()),
+                ),
+                ecdsa::SecretKey::P384(key) => (
+                    "nistp384",
+                    key.public_key().to_encoded_point(false).as_bytes().to_vec(),
+                    Zeroizing::new(key.to_bytes().to_vec()),
+                ),
+                ecdsa::SecretKey::P521(key) => (
+                    "nistp521",
+                    key.public_key().to_encoded_point(false).as_bytes().to_vec(),
+                    Zeroizing::new(key.to_bytes().to_vec()),
+                ),
+                _ => unsupported_err!("OpenSSH export of {:?}", key),
+            };
+            put_string(&mut private, curve.as_bytes());
+            put_string(&mut private, &point);
+            put_mpint(&mut private, &scalar);
+        }
+        PlainSecretParams::Ed25519(key) | PlainSecretParams::Ed25519Legacy(key) => {
+            let public = key.verifying_key();
+            let mut keypair = Zeroizing::new(key.to_bytes().to_vec());
+            keypair.extend_from_slice(public.as_bytes());
+            put_string(&mut private, public.as_bytes());
+            put_string(&mut private, &keypair);
+        }
+        _ => unsupported_err!("OpenSSH export of {:?}", secret),
+    }
+    put_string(&mut private, comment.as_bytes());
+    let mut pad = 1;
+    while private.len() % BLOCK_SIZE != 0 {
+        private.push(pad);
+        pad += 1;
+    }
+
+    let mut out = Zeroizing::new(AUTH_MAGIC.to_vec());
+    put_string(&mut out, b"none"); // cipher
+    put_string(&mut out, b"none"); // kdf
+    put_string(&mut out, b""); // kdf options
+    out.extend_from_slice(&1u32.to_be_bytes());
+    put_string(&mut out, &public_blob);
+    put_string(&mut out, &private);
+
+    let pem = pem::encode_string("OPENSSH PRIVATE KEY", LineEnding::LF, &out)
+        .map_err(|err| format_err!("failed to encode OpenSSH secret key: {}", err))?;
+
+    Ok(Zeroizing::new(pem))
+}
+
+impl SignedPublicKey {
+    /// Exports the valid keys of this certificate that are marked for authentication, as
+    /// lines for an OpenSSH `authorized_keys` file.
+    ///
+    /// Like GnuPG, each line is commented with `openpgp:0x<key id>`.
+    pub fn to_openssh_authorized_keys(&self) -> Result<String> {
+        let validity = self.validity();
+        ensure!(validity.is_valid(), "certificate is not valid");
+
+        let mut keys = Vec::new();
+        if validity
+            .binding()
+            .is_some_and(|binding| binding.key_flags().authentication())
+        {
+            keys.push((self.primary_key.key_id(), self.primary_key.public_params()));
+        }
+        for (subkey, signed) in validity.subkeys().iter().zip(&self.public_subkeys) {
+            if subkey.status.is_valid() && subkey.key_flags().authentication() {
+                keys.push((signed.key.key_id(), signed.key.public_params()));
+            }
+        }
+
+        let mut lines = String::new();
+        for (key_id, params) in keys {
+            let comment = format!("openpgp:0x{}", hex::encode_upper(key_id));
+            lines.push_str(&export_openssh_public_key(params, &comment)?);
+            lines.push('\n');
+        }
+
+        Ok(lines)
+    }
+}
+
+/// Parses the binary encoding of an OpenSSH public key.
+pub(super) fn public_params_from_blob(blob: &[u8]) -> Result<PublicParams> {
+    let mut reader = Reader(blob);
+    let params = read_public(&mut reader)?;
+    ensure!(reader.0.is_empty(), "trailing data in OpenSSH public key");
+
+    Ok(params)
+}
+
+/// Parses an unencrypted `OPENSSH PRIVATE KEY`.
+pub(super) fn secret_params_from_openssh(data: &[u8]) -> Result<PlainSecretParams> {
+    let Some(data) = data.strip_prefix(AUTH_MAGIC) else {
+        bail!("invalid OpenSSH secret key");
+    };
+    let mut reader = Reader(data);
+    let cipher = reader.string()?;
+    let kdf = reader.string()?;
+    let _kdf_options = reader.string()?;
+    if cipher != b"none" || kdf != b"none" {
+        unsupported_err!("password protected OpenSSH secret keys");
+    }
+    ensure_eq!(
+        reader.u32()?,
+        1,
+        "only single OpenSSH secret keys are supported"
+    );
+    let public = public_params_from_blob(reader.string()?)?;
+
+    let mut reader = Reader(reader.string()?);
+    ensure_eq!(reader.u32()?, reader.u32()?, "invalid OpenSSH secret key");
+
+    let secret = match reader.string()? {
+        b"ssh-rsa" => {
+            let n = BigUint::from_bytes_be(reader.mpint()?);
+            let e = BigUint::from_bytes_be(reader.mpint()?);
+            let d = BigUint::from_bytes_be(reader.mpint()?);
+            let _iqmp = reader.mpint()?;
+            let p = BigUint::from_bytes_be(reader.mpint()?);
+            let q = BigUint::from_bytes_be(reader.mpint()?);
+            let key = RsaPrivateKey::from_components(n, e, d, vec![p, q])?;
+            key.validate()?;
+            PlainSecretParams::RSA(key.into())
+        }
+        b"ssh-ed25519" => {
+            let _public = reader.string()?;
+            let keypair = reader.string()?;
+            ensure_eq!(keypair.len(), 64, "invalid OpenSSH Ed25519 secret key");
+            let mut seed = Zeroizing::new([0u8; ed25519::KEY_LEN]);
+            seed.copy_from_slice(&keypair[..ed25519::KEY_LEN]);
+            let key = ed25519::SecretKey::try_from_bytes(*seed, ed25519::Mode::EdDSALegacy)?;
+            PlainSecretParams::Ed25519Legacy(key)
+        }
+        typ @ (b"ecdsa-sha2-nistp256" | b"ecdsa-sha2-nistp384" | b"ecdsa-sha2-nistp521") => {
+            let _curve = reader.string()?;
+            let _point = reader.string()?;
+            let scalar = reader.mpint()?;
+            let key: ecdsa::SecretKey = match typ {
+                b"ecdsa-sha2-nistp256" => p256::SecretKey::from_slice(scalar)?.into(),
+                b"ecdsa-sha2-nistp384" => p384::SecretKey::from_slice(scalar)?.into(),
+                _ => p521::SecretKey::from_slice(scalar)?.into(),
+            };
+            PlainSecretParams::ECDSA(key)
+        }
+        typ => unsupported_err!(
+            "OpenSSH secret key of type {}",
+            String::from_utf8_lossy(typ)
+        ),
+    };
+
+    ensure_eq!(
+        PublicParams::try_from(&secret)?,
+        public,
+        "OpenSSH secret key does not match its public key"
+    );
+
+    Ok(secret)
+}
+
+/// The OpenSSH name of the key type.
+fn key_type(params: &PublicParams) -> Result<&'static str> {
+    let typ = match params {
+        PublicParams::RSA(_) => "ssh-rsa",
+        PublicParams::ECDSA(EcdsaPublicParams::P256 { .. }) => "ecdsa-sha2-nistp256",
+        PublicParams::ECDSA(EcdsaPublicParams::P384 { .. }) => "ecdsa-sha2-nistp384",
+        PublicParams::ECDSA(EcdsaPublicParams::P521 { .. }) => "ecdsa-sha2-nistp521",
+        PublicParams::EdDSALegacy(EddsaLegacyPublicParams::Ed25519 { .. })
+        | PublicParams::Ed25519(_) => "ssh-ed25519",
+        _ => unsupported_err!("OpenSSH export of {:?}", params),
+    };
+
+    Ok(typ)
+}
+
+/// Binary encoding of an OpenSSH public key, as specified in RFC 4253, RFC 5656 and RFC 8709.
+fn public_blob(params: &PublicParams) -> Result<Vec<u8>> {
+    let mut blob = Vec::new();
+    put_string(&mut blob, key_type(params)?.as_bytes());
+    match params {
+        PublicParams::RSA(params) => {
+            put_mpint(&mut blob, &params.key.e().to_bytes_be());
+            put_mpint(&mut blob, &params.key.n().to_bytes_be());
+        }
+        PublicParams::ECDSA(EcdsaPublicParams::P256 { key }) => {
+            put_string(&mut blob, b"nistp256");
+            put_string(&mut blob, key.to_encoded_point(false).as_bytes());
+        }
+        PublicParams::ECDSA(EcdsaPublicParams::P384 { key }) => {
+            put_string(&mut blob, b"nistp384");
+            put_string(&mut blob, key.to_encoded_point(false).as_bytes());
+        }
+        PublicParams::ECDSA(EcdsaPublicParams::P521 { key }) => {
+            put_string(&mut blob, b"nistp521");
+            put_string(&mut blob, key.to_encoded_point(false).as_bytes());
+        }
+        PublicParams::EdDSALegacy(EddsaLegacyPublicParams::Ed25519 { key }) => {
+            put_string(&mut blob, key.as_bytes());
+        }
+        PublicParams::Ed25519(params) => {
+            put_string(&mut blob, params.key.as_bytes());
+        }
+        _ => unsupported_err!("OpenSSH export of {:?}", params),
+    }
+
+    Ok(blob)
+}
+
+fn read_public(reader: &mut Reader<'_>) -> Result<PublicParams> {
+    let typ = reader.string()?;
+    let params = match typ {
+        b"ssh-rsa" => {
+            let e = BigUint::from_bytes_be(reader.mpint()?);
+            let n = BigUint::from_bytes_be(reader.mpint()?);
+            PublicParams::RSA(RsaPublicParams::from(RsaPublicKey::new(n, e)?))
+        }
+        b"ssh-ed25519" => {
+            let key = reader
+                .string()?
+                .try_into()
+                .map_err(|_| format_err!("invalid OpenSSH Ed25519 public key"))?;
+            let key = ed25519_dalek::VerifyingKey::from_bytes(key)?;
+            PublicParams::EdDSALegacy(EddsaLegacyPublicParams::Ed25519 { key })
+        }
+        b"ecdsa-sha2-nistp256" | b"ecdsa-sha2-nistp384" | b"ecdsa-sha2-nistp521" => {
+            let curve = reader.string()?;
+            ensure_eq!(
+                &typ[b"ecdsa-sha2-".len()..],
+                curve,
+                "OpenSSH ECDSA key type does not match its curve"
+            );
+            let point = reader.string()?;
+            let params = match curve {
+                b"nistp256" => EcdsaPublicParams::P256 {
+                    key: p256::PublicKey::from_sec1_bytes(point)?,
+                },
+                b"nistp384" => EcdsaPublicParams::P384 {
+                    key: p384::PublicKey::from_sec1_bytes(point)?,
+                },
+                _ => EcdsaPublicParams::P521 {
+                    key: p521::PublicKey::from_sec1_bytes(point)?,
+                },
+            };
+            PublicParams::ECDSA(params)
+        }
+        _ => unsupported_err!(
+            "OpenSSH public key of type {}",
+            String::from_utf8_lossy(typ)
+        ),
+    };
+
+    Ok(params)
+}
+
+/// Reads the data types of the SSH wire encoding (RFC 4251, section 5).
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(self.0.len() >= len, "unexpected end of OpenSSH key");
+        let (data, rest) = self.0.split_at(len);
+        self.0 = rest;
+
+        Ok(data)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let data = self.take(4)?;
+
+        Ok(u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
+    }
+
+    fn string(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()?;
+        self.take(len.try_into()?)
+    }
+
+    /// Reads a non-negative mpint, without leading zeros.
+    fn mpint(&mut self) -> Result<&'a [u8]> {
+        let data = self.string()?;
+        ensure!(
+            data.first().is_none_or(|b| b & 0x80 == 0),
+            "negative mpint in OpenSSH key"
+        );
+        let start = data.iter().position(|b| *b != 0).unwrap_or(data.len());
+
+        Ok(&data[start..])
+    }
+}
+
+fn put_string(buf: &mut Vec<u8>, data: &[u8]) {
+    let len = u32::try_from(data.len()).expect("SSH strings are shorter than 4GiB");
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(data);
+}
+
+/// Writes the big endian encoded integer `data` as an mpint.
+fn put_mpint(buf: &mut Vec<u8>, data: &[u8]) {
+    let start = data.iter().position(|b| *b != 0).unwrap_or(data.len());
+    let data = &data[start..];
+    if data.first().is_some_and(|b| b & 0x80 != 0) {
+        let len = u32::try_from(data.len() + 1).expect("SSH strings are shorter than 4GiB");
+        buf.extend_from_slice(&len.to_be_bytes());
+        buf.push(0);
+        buf.extend_from_slice(data);
+    } else {
+        put_string(buf, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        composed::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder},
+        crypto::ecc_curve::ECCCurve,
+    };
+
+    #[test]
+    fn authorized_keys() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let key = SecretKeyParamsBuilder::default()
+            .key_type(KeyType::Ed25519Legacy)
+            .can_certify(true)
+            .primary_user_id("Me <me@example.org>".into())
+            .subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::ECDSA(ECCCurve::P256))
+                    .can_authenticate(true)
+                    .build()
+                    .unwrap(),
+            )
+            .subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                    .can_encrypt(true)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+            .generate(&mut rng)
+            .unwrap()
+            .sign(&mut rng, &"".into())
+            .unwrap();
+        let key = SignedPublicKey::from(key);
+
+        let lines = key.to_openssh_authorized_keys().unwrap();
+        let mut lines = lines.lines();
+        let line = lines.next().unwrap();
+        assert!(lines.next().is_none());
+
+        let subkey = &key.public_subkeys[0].key;
+        let (imported, comment) = import_openssh_public_key(line, *subkey.created_at()).unwrap();
+        assert_eq!(imported.public_params(), subkey.public_params());
+        assert_eq!(
+            comment,
+            format!("openpgp:0x{}", hex::encode_upper(subkey.key_id()))
+        );
+        assert!(line.starts_with("ecdsa-sha2-nistp256 "));
+    }
+
+    #[test]
+    fn mpint() {
+        let mut buf = Vec::new();
+        put_mpint(&mut buf, &[0, 0, 0x80, 1]);
+        put_mpint(&mut buf, &[0x7f]);
+        put_mpint(&mut buf, &[]);
+        assert_eq!(buf, [0, 0, 0, 3, 0, 0x80, 1, 0, 0, 0, 1, 0x7f, 0, 0, 0, 0]);
+
+        let mut reader = Reader(&buf);
+        assert_eq!(reader.mpint().unwrap(), [0x80, 1]);
+        assert_eq!(reader.mpint().unwrap(), [0x7f]);
+        assert!(reader.mpint().unwrap().is_empty());
+        assert!(reader.u32().is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use std::fmt::Display;
+
+use const_oid::AssociatedOid;
+use rsa::{
+    pkcs1::{self, DecodeRsaPrivateKey, DecodeRsaPublicKey},
+    pkcs8::{
+        EncodePrivateKey, EncodePublicKey, LineEnding, PrivateKeyInfo, SubjectPublicKeyInfoRef,
+    },
+    RsaPrivateKey, RsaPublicKey,
+};
+use zeroize::Zeroizing;
+
+use crate::{
+    crypto::{ecdsa, ed25519},
+    errors::{bail, format_err, unsupported_err, Error, Result},
+    types::{
+        EcdsaPublicParams, EddsaLegacyPublicParams, PlainSecretParams, PublicParams,
+        RsaPublicParams,
+    },
+};
+
+/// Encodes the public key material of an RSA, ECDSA or Ed25519 key as a PEM encoded
+/// PKCS#8 `PUBLIC KEY`.
+pub fn export_pkcs8_public_key(params: &PublicParams) -> Result<String> {
+    let pem = match params {
+        PublicParams::RSA(params) => params.key.to_public_key_pem(LineEnding::LF),
+        PublicParams::ECDSA(EcdsaPublicParams::P256 { key }) => {
+            key.to_public_key_pem(LineEnding::LF)
+        }
+        PublicParams::ECDSA(EcdsaPublicParams::P384 { key }) => {
+            key.to_public_key_pem(LineEnding::LF)
+        }
+        PublicParams::ECDSA(EcdsaPublicParams::P521 { key }) => {
+            key.to_public_key_pem(LineEnding::LF)
+        }
+        PublicParams::EdDSALegacy(EddsaLegacyPublicParams::Ed25519 { key }) => {
+            key.to_public_key_pem(LineEnding::LF)
+        }
+        PublicParams::Ed25519(params) => params.key.to_public_key_pem(LineEnding::LF),
+        _ => unsupported_err!("PKCS#8 export of {:?}", params),
+    };
+
+    pem.map_err(|err| format_err!("failed to encode PKCS#8 public key: {}", err))
+}
+
+/// Encodes the secret key material of an RSA, ECDSA or Ed25519 key as a PEM encoded
+/// PKCS#8 `PRIVATE KEY`, without password protection.
+pub fn export_pkcs8_secret_key(secret: &PlainSecretParams) -> Result<Zeroizing<String>> {
+    let pem = match secret {
+        PlainSecretParams::RSA(key) => key.to_pkcs8_pem(LineEnding::LF),
+        PlainSecretParams::ECDSA(ecdsa::SecretKey::P256(key)) => key.to_pkcs8_pem(LineEnding::LF),
+        PlainSecretParams::ECDSA(ecdsa::SecretKey::P384(key)) => key.to_pkcs8_pem(LineEnding::LF),
+        PlainSecretParams::ECDSA(ecdsa::SecretKey::P521(key)) => key.to_pkcs8_pem(LineEnding::LF),
+        PlainSecretParams::Ed25519(key) | PlainSecretParams::Ed25519Legacy(key) => {
+            key.to_pkcs8_pem(LineEnding::LF)
+        }
+        _ => unsupported_err!("PKCS#8 export of {:?}", secret),
+    };
+
+    pem.map_err(|err| format_err!("failed to encode PKCS#8 secret key: {}", err))
+}
+
+/// Parses a DER encoded PKCS#1 `RSA PUBLIC KEY`.
+pub(super) fn public_params_from_pkcs1(der: &[u8]) -> Result<PublicParams> {
+    let key = RsaPublicKey::from_pkcs1_der(der)
+        .map_err(|err| format_err!("invalid PKCS#1 public key: {}", err))?;
+
+    Ok(PublicParams::RSA(RsaPublicParams::from(key)))
+}
+
+/// Parses a DER encoded PKCS#8 `PUBLIC KEY`.
+pub(super) fn public_params_from_pkcs8(der: &[u8]) -> Result<PublicParams> {
+    let spki = SubjectPublicKeyInfoRef::try_from(der).map_err(invalid)?;
+
+    let oid = spki.algorithm.oid;
+    if oid == pkcs1::ALGORITHM_OID {
+        let key = RsaPublicKey::try_from(spki).map_err(invalid)?;
+        return Ok(PublicParams::RSA(RsaPublicParams::from(key)));
+    }
+    if oid == ed25519_dalek::pkcs8::ALGORITHM_OID {
+        let key = ed25519_dalek::VerifyingKey::try_from(spki).map_err(invalid)?;
+        return Ok(PublicParams::EdDSALegacy(
+            EddsaLegacyPublicParams::Ed25519 { key },
+        ));
+    }
+    if oid == elliptic_curve::ALGORITHM_OID {
+        let params = match spki.algorithm.parameters_oid().map_err(invalid)? {
+            p256::NistP256::OID => EcdsaPublicParams::P256 {
+                key: p256::PublicKey::try_from(spki).map_err(invalid)?,
+            },
+            p384::NistP384::OID => EcdsaPublicParams::P384 {
+                key: p384::PublicKey::try_from(spki).map_err(invalid)?,
+            },
+            p521::NistP521::OID => EcdsaPublicParams::P521 {
+                key: p521::PublicKey::try_from(spki).map_err(invalid)?,
+            },
+            curve => unsupported_err!("PKCS#8 public key on curve {}", curve),
+        };
+        return Ok(PublicParams::ECDSA(params));
+    }
+
+    unsupported_err!("PKCS#8 public key of type {}", oid)
+}
+
+/// Parses a DER encoded PKCS#1 `RSA PRIVATE KEY`.
+pub(super) fn secret_params_from_pkcs1(der: &[u8]) -> Result<PlainSecretParams> {
+    let key = RsaPrivateKey::from_pkcs1_der(der)
+        .map_err(|err| format_err!("invalid PKCS#1 secret key: {}", err))?;
+
+    Ok(PlainSecretParams::RSA(key.into()))
+}
+
+/// Parses a DER encoded SEC1 `EC PRIVATE KEY`.
+pub(super) fn secret_params_from_sec1(der: &[u8]) -> Result<PlainSecretParams> {
+    // The curve is identified by the optional parameters of the key, try all of them.
+    if let Ok(key) = p256::SecretKey::from_sec1_der(der) {
+        return Ok(PlainSecretParams::ECDSA(key.into()));
+    }
+    if let Ok(key) = p384::SecretKey::from_sec1_der(der) {
+        return Ok(PlainSecretParams::ECDSA(key.into()));
+    }
+    if let Ok(key) = p521::SecretKey::from_sec1_der(der) {
+        return Ok(PlainSecretParams::ECDSA(key.into()));
+    }
+
+    bail!("invalid or unsupported SEC1 secret key")
+}
+
+/// Parses a DER encoded, unencrypted PKCS#8 `PRIVATE KEY`.
+pub(super) fn secret_params_from_pkcs8(der: &[u8]) -> Result<PlainSecretParams> {
+    let info = PrivateKeyInfo::try_from(der).map_err(invalid)?;
+
+    let oid = info.algorithm.oid;
+    if oid == pkcs1::ALGORITHM_OID {
+        let key = RsaPrivateKey::try_from(info).map_err(invalid)?;
+        return Ok(PlainSecretParams::RSA(key.into()));
+    }
+    if oid == ed25519_dalek::pkcs8::ALGORITHM_OID {
+        let key = ed25519_dalek::SigningKey::try_from(info).map_err(invalid)?;
+        let key = ed25519::SecretKey::try_from_bytes(key.to_bytes(), ed25519::Mode::EdDSALegacy)?;
+        return Ok(PlainSecretParams::Ed25519Legacy(key));
+    }
+    if oid == elliptic_curve::ALGORITHM_OID {
+        let key: ecdsa::SecretKey = match info.algorithm.parameters_oid().map_err(invalid)? {
+            p256::NistP256::OID => p256::SecretKey::try_from(info).map_err(invalid)?.into(),
+            p384::NistP384::OID => p384::SecretKey::try_from(info).map_err(invalid)?.into(),
+            p521::NistP521::OID => p521::SecretKey::try_from(info).map_err(invalid)?.into(),
+            curve => unsupported_err!("PKCS#8 secret key on curve {}", curve),
+        };
+        return Ok(PlainSecretParams::ECDSA(key));
+    }
+
+    unsupported_err!("PKCS#8 secret key of type {}", oid)
+}
+
+fn invalid<E: Display>(err: E) -> Error {
+    format_err!("invalid PKCS#8 key: {}", err)
+}
@@ -46,8 +46,9 @@
 //! [signing and verifying with external hashing]: super::signed_key
 
 mod builder;
+mod profile;
 mod public;
 mod secret;
 mod shared;
 
-pub use self::{builder::*, public::*, secret::*, shared::*};
+pub use self::{builder::*, profile::*, public::*, secret::*, shared::*};
@@ -1,5 +1,6 @@
 use std::time::Duration;
 
+use ::rsa::traits::PublicKeyParts;
 use chrono::SubsecRound;
 use derive_builder::Builder;
 use rand::{CryptoRng, Rng};
@@ -17,13 +18,14 @@ use crate::{
         hash::HashAlgorithm, public_key::PublicKeyAlgorithm, rsa, sym::SymmetricKeyAlgorithm,
         x25519, x448,
     },
-    errors::Result,
+    errors::{ensure, Result},
     packet::{self, KeyFlags, PubKeyInner, UserAttribute, UserId},
+    policy::{Policy, StandardPolicy},
     types::{self, CompressionAlgorithm, PlainSecretParams, PublicParams, S2kParams},
 };
 
 #[derive(Debug, PartialEq, Eq, Builder)]
-#[builder(build_fn(validate = "Self::validate"))]
+#[builder(build_fn(private, name = "build_unchecked", validate = "Self::validate"))]
 pub struct SecretKeyParams {
     /// OpenPGP key version of primary
     #[builder(default)]
@@ -66,6 +68,13 @@ pub struct SecretKeyParams {
     #[builder(default)]
     preferred_aead_algorithms: SmallVec<[(SymmetricKeyAlgorithm, AeadAlgorithm); 4]>,
 
+    /// Existing key material for the primary, instead of generating a new key.
+    ///
+    /// Must match `key_type`. Together with a fixed `created_at`, this results in a stable
+    /// fingerprint.
+    #[builder(default)]
+    secret_params: Option<PlainSecretParams>,
+
     // -- Password-locking of the primary
     #[builder(default)]
     passphrase: Option<String>,
@@ -111,6 +120,12 @@ pub struct SubkeyParams {
     #[builder(default)]
     expiration: Option<Duration>,
 
+    /// Existing key material for this subkey, instead of generating a new key.
+    ///
+    /// Must match `key_type`.
+    #[builder(default)]
+    secret_params: Option<PlainSecretParams>,
+
     // -- Password-locking of this subkey
     #[builder(default)]
     passphrase: Option<String>,
@@ -146,17 +161,11 @@ impl SecretKeyParamsBuilder {
                 ));
             }
 
-            match key_type {
-                KeyType::Rsa(size) => {
-                    if *size < 2048 {
-                        return Err("Keys with less than 2048bits are considered insecure".into());
-                    }
-                }
-                KeyType::ECDSA(curve) => match curve {
+            if let KeyType::ECDSA(curve) = key_type {
+                match curve {
                     ECCCurve::P256 | ECCCurve::P384 | ECCCurve::P521 | ECCCurve::Secp256k1 => {}
                     _ => return Err(format!("Curve {} is not supported for ECDSA", curve.name())),
-                },
-                _ => {}
+                }
             }
         }
 
@@ -218,6 +227,28 @@ impl SecretKeyParamsBuilder {
         Ok(())
     }
 
+    /// Builds the parameters, checking the key types of the primary key and all subkeys
+    /// against the [`StandardPolicy`].
+    pub fn build(&self) -> std::result::Result<SecretKeyParams, SecretKeyParamsBuilderError> {
+        self.build_with_policy(&StandardPolicy::default())
+    }
+
+    /// Builds the parameters, checking the key types of the primary key and all subkeys
+    /// against `policy`.
+    pub fn build_with_policy(
+        &self,
+        policy: &dyn Policy,
+    ) -> std::result::Result<SecretKeyParams, SecretKeyParamsBuilderError> {
+        let subkey_types = self.subkeys.iter().flatten().map(|s| &s.key_type);
+        for key_type in self.key_type.iter().chain(subkey_types) {
+            policy
+                .key_generation(key_type)
+                .map_err(|err| err.to_string())?;
+        }
+
+        self.build_unchecked()
+    }
+
     pub fn user_id<VALUE: Into<String>>(&mut self, value: VALUE) -> &mut Self {
         if let Some(ref mut user_ids) = self.user_ids {
             user_ids.push(value.into());
@@ -248,7 +279,10 @@ impl SecretKeyParams {
         let s2k = self
             .s2k
             .unwrap_or_else(|| S2kParams::new_default(&mut rng, self.version));
-        let (public_params, secret_params) = self.key_type.generate(&mut rng)?;
+        let (public_params, secret_params) = match self.secret_params {
+            Some(secret_params) => self.key_type.import(secret_params)?,
+            None => self.key_type.generate(&mut rng)?,
+        };
         let pub_key = PubKeyInner::new(
             self.version,
             self.key_type.to_alg(),
@@ -301,49 +335,62 @@ impl SecretKeyParams {
             Default::default(),
             self.subkeys
                 .into_iter()
-                .map(|subkey| {
-                    let passphrase = subkey.passphrase;
-                    let s2k = subkey
-                        .s2k
-                        .unwrap_or_else(|| S2kParams::new_default(&mut rng, subkey.version));
-                    let (public_params, secret_params) = subkey.key_type.generate(&mut rng)?;
-                    let mut keyflags = KeyFlags::default();
-                    keyflags.set_encrypt_comms(subkey.can_encrypt);
-                    keyflags.set_encrypt_storage(subkey.can_encrypt);
-                    keyflags.set_sign(subkey.can_sign);
-                    keyflags.set_authentication(subkey.can_authenticate);
-
-                    let pub_key = PubKeyInner::new(
-                        subkey.version,
-                        subkey.key_type.to_alg(),
-                        subkey.created_at,
-                        subkey.expiration.map(|v| v.as_secs() as u16),
-                        public_params,
-                    )?;
-                    let pub_key = packet::PublicSubkey::from_inner(pub_key)?;
-                    let mut sub = packet::SecretSubkey::new(pub_key, secret_params)?;
-
-                    // Produce embedded back signature for signing-capable subkeys
-                    let embedded = if subkey.can_sign {
-                        let backsig =
-                            sub.sign_primary_key_binding(&mut rng, &primary_pub_key, &"".into())?;
-
-                        Some(backsig)
-                    } else {
-                        None
-                    };
-
-                    if let Some(passphrase) = passphrase {
-                        sub.set_password_with_s2k(&passphrase.as_str().into(), s2k)?;
-                    }
-
-                    Ok(SecretSubkey::new(sub, keyflags, embedded))
-                })
+                .map(|subkey| subkey.generate(&mut rng, &primary_pub_key))
                 .collect::<Result<Vec<_>>>()?,
         ))
     }
 }
 
+impl SubkeyParams {
+    /// Generates the subkey, to be bound to `primary_pub_key`.
+    ///
+    /// For signing capable subkeys this includes the embedded primary key binding signature.
+    pub fn generate<R: Rng + CryptoRng>(
+        self,
+        mut rng: R,
+        primary_pub_key: &packet::PublicKey,
+    ) -> Result<SecretSubkey> {
+        let passphrase = self.passphrase;
+        let s2k = self
+            .s2k
+            .unwrap_or_else(|| S2kParams::new_default(&mut rng, self.version));
+        let (public_params, secret_params) = match self.secret_params {
+            Some(secret_params) => self.key_type.import(secret_params)?,
+            None => self.key_type.generate(&mut rng)?,
+        };
+        let mut keyflags = KeyFlags::default();
+        keyflags.set_encrypt_comms(self.can_encrypt);
+        keyflags.set_encrypt_storage(self.can_encrypt);
+        keyflags.set_sign(self.can_sign);
+        keyflags.set_authentication(self.can_authenticate);
+
+        let pub_key = PubKeyInner::new(
+            self.version,
+            self.key_type.to_alg(),
+            self.created_at,
+            self.expiration.map(|v| v.as_secs() as u16),
+            public_params,
+        )?;
+        let pub_key = packet::PublicSubkey::from_inner(pub_key)?;
+        let mut sub = packet::SecretSubkey::new(pub_key, secret_params)?;
+
+        // Produce embedded back signature for signing-capable subkeys
+        let embedded = if self.can_sign {
+            let backsig = sub.sign_primary_key_binding(&mut rng, primary_pub_key, &"".into())?;
+
+            Some(backsig)
+        } else {
+            None
+        };
+
+        if let Some(passphrase) = passphrase {
+            sub.set_password_with_s2k(&passphrase.as_str().into(), s2k)?;
+        }
+
+        Ok(SecretSubkey::new(sub, keyflags, embedded))
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum KeyType {
     /// Encryption & Signing with RSA and the given bitsize.
@@ -598,6 +645,42 @@ impl KeyType {
 
         Ok((pub_params, types::SecretParams::Plain(plain)))
     }
+
+    /// Uses existing key material, instead of generating a new key.
+    ///
+    /// Fails if `secret` does not match this key type, including the size of RSA and DSA
+    /// keys, and the curve of ECC keys.
+    pub fn import(&self, secret: PlainSecretParams) -> Result<(PublicParams, types::SecretParams)> {
+        let public_params = PublicParams::try_from(&secret)?;
+
+        let matches = match (self, &public_params) {
+            (KeyType::Rsa(bits), PublicParams::RSA(params)) => {
+                params.key.n().bits() == *bits as usize
+            }
+            (KeyType::Dsa(size), PublicParams::DSA(params)) => {
+                params.key.components().p().bits() == *size as usize
+            }
+            (KeyType::ECDH(curve), PublicParams::ECDH(params)) => &params.curve() == curve,
+            (KeyType::ECDSA(curve), PublicParams::ECDSA(params)) => &params.curve() == curve,
+            (KeyType::Ed25519Legacy, PublicParams::EdDSALegacy(_))
+            | (KeyType::Ed25519, PublicParams::Ed25519(_))
+            | (KeyType::Ed448, PublicParams::Ed448(_))
+            | (KeyType::X25519, PublicParams::X25519(_))
+            | (KeyType::X448, PublicParams::X448(_)) => true,
+            #[cfg(feature = "draft-pqc")]
+            (KeyType::MlKem768X25519, PublicParams::MlKem768X25519(_))
+            | (KeyType::MlKem1024X448, PublicParams::MlKem1024X448(_))
+            | (KeyType::MlDsa65Ed25519, PublicParams::MlDsa65Ed25519(_))
+            | (KeyType::MlDsa87Ed448, PublicParams::MlDsa87Ed448(_))
+            | (KeyType::SlhDsaShake128s, PublicParams::SlhDsaShake128s(_))
+            | (KeyType::SlhDsaShake128f, PublicParams::SlhDsaShake128f(_))
+            | (KeyType::SlhDsaShake256s, PublicParams::SlhDsaShake256s(_)) => true,
+            _ => false,
+        };
+        ensure!(matches, "key material does not match key type {:?}", self);
+
+        Ok((public_params, types::SecretParams::Plain(secret)))
+    }
 }
 
 #[cfg(test)]
@@ -612,7 +695,7 @@ mod tests {
     use crate::{
         composed::{Deserializable, SignedPublicKey, SignedSecretKey},
         packet::Features,
-        types::KeyVersion,
+        types::{KeyDetails, KeyVersion},
     };
 
     #[test]
@@ -1974,4 +2057,79 @@ mod tests {
             SignedPublicKey::from_string(&armor).expect("failed to parse public key");
         signed_key2.verify().expect("invalid public key");
     }
+
+    #[test]
+    fn import_key_material() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let created_at = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+
+        let primary = crate::crypto::ed25519::SecretKey::try_from_bytes(
+            [7u8; 32],
+            crate::crypto::ed25519::Mode::Ed25519,
+        )
+        .unwrap();
+        let subkey: crate::crypto::x25519::SecretKey =
+            x25519_dalek::StaticSecret::from([9u8; 32]).into();
+
+        let build = |rng: &mut ChaCha8Rng| {
+            SecretKeyParamsBuilder::default()
+                .version(KeyVersion::V6)
+                .key_type(KeyType::Ed25519)
+                .secret_params(Some(PlainSecretParams::Ed25519(primary.clone())))
+                .created_at(created_at)
+                .can_certify(true)
+                .can_sign(true)
+                .primary_user_id("Me <me@mail.com>".into())
+                .subkey(
+                    SubkeyParamsBuilder::default()
+                        .version(KeyVersion::V6)
+                        .key_type(KeyType::X25519)
+                        .secret_params(Some(PlainSecretParams::X25519(subkey.clone())))
+                        .created_at(created_at)
+                        .can_encrypt(true)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+                .generate(&mut *rng)
+                .unwrap()
+                .sign(rng, &"".into())
+                .unwrap()
+        };
+
+        let key1 = build(&mut rng);
+        let key2 = build(&mut rng);
+        key1.verify().unwrap();
+
+        assert_eq!(key1.fingerprint(), key2.fingerprint());
+        assert_eq!(
+            key1.secret_subkeys[0].key.fingerprint(),
+            key2.secret_subkeys[0].key.fingerprint()
+        );
+    }
+
+    #[test]
+    fn import_key_material_mismatch() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let secret = p256::SecretKey::random(&mut rng);
+
+        let params = SecretKeyParamsBuilder::default()
+            .key_type(KeyType::ECDSA(ECCCurve::P384))
+            .secret_params(Some(PlainSecretParams::ECDSA(secret.clone().into())))
+            .can_certify(true)
+            .primary_user_id("Me <me@mail.com>".into())
+            .build()
+            .unwrap();
+        assert!(params.generate(&mut rng).is_err());
+
+        let params = SecretKeyParamsBuilder::default()
+            .key_type(KeyType::Ed25519)
+            .secret_params(Some(PlainSecretParams::ECDSA(secret.into())))
+            .can_certify(true)
+            .primary_user_id("Me <me@mail.com>".into())
+            .build()
+            .unwrap();
+        assert!(params.generate(&mut rng).is_err());
+    }
 }
@@ -0,0 +1,250 @@
+use smallvec::{smallvec, SmallVec};
+
+use crate::{
+    composed::{KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder},
+    crypto::{
+        aead::AeadAlgorithm, ecc_curve::ECCCurve, hash::HashAlgorithm, sym::SymmetricKeyAlgorithm,
+    },
+    types::{CompressionAlgorithm, KeyVersion},
+};
+
+/// Predefined sets of parameters for generating keys.
+///
+/// # Example
+///
+/// ```rust
+/// use pgp::composed::KeyProfile;
+/// use pgp::types::Password;
+/// use rand::thread_rng;
+///
+/// let mut rng = thread_rng();
+/// let key = KeyProfile::Rfc9580
+///     .builder(["Alice <alice@example.org>"], Some("secret".into()))
+///     .build()
+///     .expect("valid parameters")
+///     .generate(&mut rng)
+///     .expect("key generation")
+///     .sign(&mut rng, &Password::from("secret"))
+///     .expect("self-signatures");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyProfile {
+    /// A v6 key as specified in RFC 9580: an Ed25519 primary key for certification and signing,
+    /// and an X25519 encryption subkey.
+    ///
+    /// Advertises support for SEIPDv2, secret key material is protected with Argon2 and OCB.
+    Rfc9580,
+    /// A v4 key that can be used with GnuPG 2.2 and other implementations of RFC 4880:
+    /// an EdDSA (legacy) primary key for certification and signing, and an ECDH Curve25519
+    /// encryption subkey.
+    ///
+    /// Only advertises support for SEIPDv1, secret key material is protected with the
+    /// iterated and salted S2K and CFB.
+    Compatibility,
+    /// A v6 key using the composite algorithms of draft-ietf-openpgp-pqc: an ML-DSA-65+Ed25519
+    /// primary key for certification and signing, and an ML-KEM-768+X25519 encryption subkey.
+    ///
+    /// Advertises support for SEIPDv2, secret key material is protected with Argon2 and OCB.
+    #[cfg(feature = "draft-pqc")]
+    PqcComposite,
+}
+
+impl KeyProfile {
+    /// Returns a builder that is populated according to this profile.
+    ///
+    /// The first of `user_ids` becomes the primary user ID. If given, `passphrase` protects the
+    /// primary key and all subkeys, the S2K parameters are chosen at generation time.
+    /// All other settings can still be adjusted on the returned builder.
+    pub fn builder(
+        self,
+        user_ids: impl IntoIterator<Item = impl Into<String>>,
+        passphrase: Option<String>,
+    ) -> SecretKeyParamsBuilder {
+        let (version, primary, encryption) = match self {
+            KeyProfile::Rfc9580 => (KeyVersion::V6, KeyType::Ed25519, KeyType::X25519),
+            KeyProfile::Compatibility => (
+                KeyVersion::V4,
+                KeyType::Ed25519Legacy,
+                KeyType::ECDH(ECCCurve::Curve25519),
+            ),
+            #[cfg(feature = "draft-pqc")]
+            KeyProfile::PqcComposite => (
+                KeyVersion::V6,
+                KeyType::MlDsa65Ed25519,
+                KeyType::MlKem768X25519,
+            ),
+        };
+        let seipd_v2 = version == KeyVersion::V6;
+
+        let mut builder = SecretKeyParamsBuilder::default();
+        builder
+            .version(version)
+            .key_type(primary)
+            .can_certify(true)
+            .can_sign(true)
+            .feature_seipd_v1(true)
+            .feature_seipd_v2(seipd_v2)
+            .preferred_symmetric_algorithms(smallvec![
+                SymmetricKeyAlgorithm::AES256,
+                SymmetricKeyAlgorithm::AES192,
+                SymmetricKeyAlgorithm::AES128,
+            ])
+            .preferred_hash_algorithms(smallvec![
+                HashAlgorithm::Sha512,
+                HashAlgorithm::Sha384,
+                HashAlgorithm::Sha256,
+            ])
+            .passphrase(passphrase.clone())
+            .subkey(
+                SubkeyParamsBuilder::default()
+                    .version(version)
+                    .key_type(encryption)
+                    .can_encrypt(true)
+                    .passphrase(passphrase)
+                    .build()
+                    .expect("all required fields are set"),
+            );
+
+        if seipd_v2 {
+            let aead: SmallVec<_> = [SymmetricKeyAlgorithm::AES256, SymmetricKeyAlgorithm::AES128]
+                .into_iter()
+                .flat_map(|sym| [(sym, AeadAlgorithm::Ocb), (sym, AeadAlgorithm::Gcm)])
+                .collect();
+            builder
+                .preferred_aead_algorithms(aead)
+                .preferred_compression_algorithms(smallvec![CompressionAlgorithm::Uncompressed]);
+        } else {
+            builder.preferred_compression_algorithms(smallvec![
+                CompressionAlgorithm::ZLIB,
+                CompressionAlgorithm::ZIP,
+                CompressionAlgorithm::Uncompressed,
+            ]);
+        }
+
+        let mut user_ids = user_ids.into_iter().map(Into::into);
+        if let Some(primary_user_id) = user_ids.next() {
+            builder.primary_user_id(primary_user_id);
+        }
+        for user_id in user_ids {
+            builder.user_id(user_id);
+        }
+
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        crypto::public_key::PublicKeyAlgorithm,
+        types::{KeyDetails, Password, S2kParams, SecretParams, StringToKey},
+    };
+
+    fn profiles() -> Vec<KeyProfile> {
+        vec![
+            KeyProfile::Rfc9580,
+            KeyProfile::Compatibility,
+            #[cfg(feature = "draft-pqc")]
+            KeyProfile::PqcComposite,
+        ]
+    }
+
+    #[test]
+    fn generate_from_profiles() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        for profile in profiles() {
+            let key = profile
+                .builder(
+                    ["Alice <alice@example.org>", "Alice <alice@work.example>"],
+                    Some("secret".into()),
+                )
+                .build()
+                .unwrap()
+                .generate(&mut rng)
+                .unwrap()
+                .sign(&mut rng, &Password::from("secret"))
+                .unwrap();
+            key.verify().unwrap();
+
+            let validity = key.validity();
+            assert!(validity.is_valid());
+            assert_eq!(validity.valid_users().count(), 2);
+            assert_eq!(
+                validity.primary_user().unwrap().user.id.id(),
+                b"Alice <alice@example.org>"
+            );
+
+            let binding = validity.binding().unwrap();
+            assert!(binding.key_flags().certify() && binding.key_flags().sign());
+            assert_eq!(
+                binding.preferred_symmetric_algs()[0],
+                SymmetricKeyAlgorithm::AES256
+            );
+
+            let subkey = &validity.subkeys()[0];
+            assert!(subkey.status.is_valid());
+            assert!(subkey.key_flags().encrypt_comms());
+
+            let features = binding.features().unwrap();
+            assert!(features.seipd_v1());
+
+            let s2k = |params: &SecretParams| match params {
+                SecretParams::Encrypted(p) => p.string_to_key_params().clone(),
+                SecretParams::Plain(_) => panic!("unprotected secret key"),
+            };
+            let primary_s2k = s2k(key.primary_key.secret_params());
+            let subkey_s2k = s2k(key.secret_subkeys[0].key.secret_params());
+
+            match profile {
+                KeyProfile::Compatibility => {
+                    assert_eq!(key.version(), KeyVersion::V4);
+                    assert_eq!(key.algorithm(), PublicKeyAlgorithm::EdDSALegacy);
+                    assert!(!features.seipd_v2());
+                    for s2k in [primary_s2k, subkey_s2k] {
+                        assert!(matches!(
+                            s2k,
+                            S2kParams::Cfb {
+                                s2k: StringToKey::IteratedAndSalted { .. },
+                                ..
+                            }
+                        ));
+                    }
+                }
+                _ => {
+                    assert_eq!(key.version(), KeyVersion::V6);
+                    assert!(features.seipd_v2());
+                    assert!(!binding.preferred_aead_algs().is_empty());
+                    for s2k in [primary_s2k, subkey_s2k] {
+                        assert!(matches!(
+                            s2k,
+                            S2kParams::Aead {
+                                s2k: StringToKey::Argon2 { .. },
+                                ..
+                            }
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn compatibility_requires_user_id() {
+        let no_user_ids: [&str; 0] = [];
+        assert!(KeyProfile::Compatibility
+            .builder(no_user_ids, None)
+            .build()
+            .is_err());
+        assert!(KeyProfile::Rfc9580
+            .builder(no_user_ids, None)
+            .build()
+            .is_ok());
+    }
+}
@@ -2,6 +2,7 @@ mod builder;
 mod decrypt;
 mod parser;
 mod reader;
+mod recipients;
 mod types;
 
 pub use self::{
@@ -11,5 +12,6 @@ pub use self::{
     },
     decrypt::*,
     reader::*,
+    recipients::*,
     types::*,
 };
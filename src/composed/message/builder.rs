@@ -15,7 +15,7 @@ use zeroize::Zeroizing;
 use super::ArmorOptions;
 use crate::{
     armor,
-    composed::Esk,
+    composed::{Esk, RecipientSelection, SignedSecretKey, SigningKey},
     crypto::{
         aead::{AeadAlgorithm, ChunkSize},
         hash::HashAlgorithm,
@@ -33,8 +33,8 @@ use crate::{
     },
     ser::Serialize,
     types::{
-        CompressionAlgorithm, Fingerprint, KeyId, KeyVersion, PacketHeaderVersion, PacketLength,
-        Password, SecretKeyTrait, StringToKey, Tag,
+        CompressionAlgorithm, Fingerprint, KeyDetails, KeyId, KeyVersion, PacketHeaderVersion,
+        PacketLength, Password, SecretKeyTrait, StringToKey, Tag,
     },
     util::{fill_buffer, TeeWriter},
 };
@@ -83,6 +83,7 @@ pub struct EncryptionSeipdV1 {
     sym_esks: Vec<SymKeyEncryptedSessionKey>,
     pub_esks: Vec<PublicKeyEncryptedSessionKey>,
     sym_alg: SymmetricKeyAlgorithm,
+    intended_recipients: Vec<Fingerprint>,
 }
 
 #[derive(derive_more::Debug, PartialEq, Clone)]
@@ -95,6 +96,7 @@ pub struct EncryptionSeipdV2 {
     chunk_size: ChunkSize,
     #[debug("{}", hex::encode(salt))]
     salt: [u8; 32],
+    intended_recipients: Vec<Fingerprint>,
 }
 
 pub trait Encryption: PartialEq {
@@ -112,6 +114,16 @@ pub trait Encryption: PartialEq {
         W: std::io::Write;
 
     fn is_plaintext(&self) -> bool;
+
+    /// The primary key fingerprints of the recipients, to be recorded in the signatures.
+    fn intended_recipients(&self) -> &[Fingerprint] {
+        &[]
+    }
+
+    /// Mutable access to the intended recipients, `None` if the message is not encrypted.
+    fn intended_recipients_mut(&mut self) -> Option<&mut Vec<Fingerprint>> {
+        None
+    }
 }
 
 /// Configures a signing key and how to use it.
@@ -174,6 +186,7 @@ fn prepare<R>(
     mut rng: R,
     typ: SignatureType,
     keys: &[SigningConfig<'_>],
+    intended_recipients: &[Fingerprint],
 ) -> Result<Vec<(crate::packet::SignatureConfig, OnePassSignature)>>
 where
     R: Rng + CryptoRng,
@@ -189,12 +202,17 @@ where
         let algorithm = config.key.algorithm();
         let hash_alg = config.hash_algorithm;
 
-        let hashed_subpackets = vec![
+        let mut hashed_subpackets = vec![
             Subpacket::regular(SubpacketData::IssuerFingerprint(config.key.fingerprint()))?,
             Subpacket::regular(SubpacketData::SignatureCreationTime(
                 chrono::Utc::now().trunc_subsecs(0),
             ))?,
         ];
+        for fp in intended_recipients {
+            hashed_subpackets.push(Subpacket::regular(
+                SubpacketData::IntendedRecipientFingerprint(fp.clone()),
+            )?);
+        }
 
         // prepare signing
         let mut sig_config = match config.key.version() {
@@ -259,6 +277,7 @@ impl<'a, R: Read> Builder<'a, R, NoEncryption> {
                 session_key,
                 sym_esks: Vec::new(),
                 pub_esks: Vec::new(),
+                intended_recipients: Vec::new(),
             },
             signing: self.signing,
         }
@@ -296,6 +315,7 @@ impl<'a, R: Read> Builder<'a, R, NoEncryption> {
                 salt,
                 sym_esks: Vec::new(),
                 pub_esks: Vec::new(),
+                intended_recipients: Vec::new(),
             },
             signing: self.signing,
         }
@@ -320,6 +340,27 @@ impl<R: Read> Builder<'_, R, EncryptionSeipdV1> {
         Ok(self)
     }
 
+    /// Encrypt to all keys selected in `recipients`.
+    ///
+    /// The builder should be configured with the algorithm negotiated in
+    /// [`RecipientSelection::encryption`].
+    pub fn encrypt_to_recipients<RAND>(
+        &mut self,
+        mut rng: RAND,
+        recipients: &RecipientSelection<'_>,
+    ) -> Result<&mut Self>
+    where
+        RAND: CryptoRng + Rng,
+    {
+        for key in recipients.keys() {
+            self.encrypt_to_key(&mut rng, key)?;
+        }
+        for recipient in recipients.recipients() {
+            self.add_intended_recipient(recipient.certificate.fingerprint());
+        }
+        Ok(self)
+    }
+
     /// Encrypt to a public key, but leave the recipient field unset
     pub fn encrypt_to_key_anonymous<RAND, K>(
         &mut self,
@@ -388,6 +429,27 @@ impl<R: Read> Builder<'_, R, EncryptionSeipdV2> {
         Ok(self)
     }
 
+    /// Encrypt to all keys selected in `recipients`.
+    ///
+    /// The builder should be configured with the algorithm negotiated in
+    /// [`RecipientSelection::encryption`].
+    pub fn encrypt_to_recipients<RAND>(
+        &mut self,
+        mut rng: RAND,
+        recipients: &RecipientSelection<'_>,
+    ) -> Result<&mut Self>
+    where
+        RAND: CryptoRng + Rng,
+    {
+        for key in recipients.keys() {
+            self.encrypt_to_key(&mut rng, key)?;
+        }
+        for recipient in recipients.recipients() {
+            self.add_intended_recipient(recipient.certificate.fingerprint());
+        }
+        Ok(self)
+    }
+
     /// Encrypt to a public key, but leave the recipient field unset
     pub fn encrypt_to_key_anonymous<RAND, K>(
         &mut self,
@@ -518,6 +580,23 @@ impl<'a, R: Read, E: Encryption> Builder<'a, R, E> {
         self
     }
 
+    /// Record the fingerprint of a recipient's primary key in the signatures of this message,
+    /// as an Intended Recipient Fingerprint subpacket.
+    ///
+    /// This lets recipients detect if a signed message was re-encrypted and forwarded to them
+    /// by someone else. `encrypt_to_recipients` does this for all selected certificates, when
+    /// using `encrypt_to_key` it has to be done explicitly.
+    ///
+    /// Has no effect if the message is not encrypted.
+    pub fn add_intended_recipient(&mut self, fingerprint: Fingerprint) -> &mut Self {
+        if let Some(recipients) = self.encryption.intended_recipients_mut() {
+            if !recipients.contains(&fingerprint) {
+                recipients.push(fingerprint);
+            }
+        }
+        self
+    }
+
     pub fn sign(
         &mut self,
         key: &'a dyn SecretKeyTrait,
@@ -529,6 +608,23 @@ impl<'a, R: Read, E: Encryption> Builder<'a, R, E> {
         self
     }
 
+    /// Sign with the currently valid signing key of `key`, using its default hash algorithm.
+    ///
+    /// See [`SignedSecretKey::signing_key`] for how the signing key is selected.
+    pub fn sign_with_key(
+        &mut self,
+        key: &'a SignedSecretKey,
+        key_pw: Password,
+    ) -> Result<&mut Self> {
+        let signer = key.signing_key()?;
+        Ok(self.sign_with(signer, key_pw))
+    }
+
+    /// Sign with a selected signing key and its hash algorithm.
+    pub fn sign_with(&mut self, signer: SigningKey<'a>, key_pw: Password) -> &mut Self {
+        self.sign(signer.key(), key_pw, signer.hash_alg())
+    }
+
     /// Write the data out to a writer.
     pub fn to_writer<RAND, W>(self, rng: RAND, out: W) -> Result<()>
     where
@@ -752,6 +848,7 @@ where
         partial_chunk_size,
         source,
         signers,
+        encryption.intended_recipients(),
         source_len,
     )?;
 
@@ -813,6 +910,7 @@ impl Encryption for EncryptionSeipdV1 {
             sym_esks,
             pub_esks,
             sym_alg,
+            intended_recipients: _,
         } = self;
         // Write out symmetric esks
         for sym_esk in sym_esks {
@@ -842,6 +940,14 @@ impl Encryption for EncryptionSeipdV1 {
     fn is_plaintext(&self) -> bool {
         false
     }
+
+    fn intended_recipients(&self) -> &[Fingerprint] {
+        &self.intended_recipients
+    }
+
+    fn intended_recipients_mut(&mut self) -> Option<&mut Vec<Fingerprint>> {
+        Some(&mut self.intended_recipients)
+    }
 }
 
 impl Encryption for EncryptionSeipdV2 {
@@ -866,6 +972,7 @@ impl Encryption for EncryptionSeipdV2 {
             aead,
             chunk_size,
             salt,
+            intended_recipients: _,
         } = self;
         ensure_eq!(
             session_key.len(),
@@ -914,6 +1021,14 @@ impl Encryption for EncryptionSeipdV2 {
     fn is_plaintext(&self) -> bool {
         false
     }
+
+    fn intended_recipients(&self) -> &[Fingerprint] {
+        &self.intended_recipients
+    }
+
+    fn intended_recipients_mut(&mut self) -> Option<&mut Vec<Fingerprint>> {
+        Some(&mut self.intended_recipients)
+    }
 }
 
 fn encrypt_write<R: std::io::Read, W: std::io::Write>(
@@ -1060,6 +1175,7 @@ impl<R: std::io::Read> std::io::Read for SignatureHashers<R> {
 }
 
 impl<'a, R: std::io::Read> SignGenerator<'a, R> {
+    #[allow(clippy::too_many_arguments)]
     fn new<RAND>(
         mut rng: RAND,
         typ: SignatureType,
@@ -1067,12 +1183,13 @@ impl<'a, R: std::io::Read> SignGenerator<'a, R> {
         chunk_size: u32,
         source: R,
         signers: Vec<SigningConfig<'a>>,
+        intended_recipients: &[Fingerprint],
         source_len: Option<u32>,
     ) -> Result<Self>
     where
         RAND: CryptoRng + Rng,
     {
-        let prep = prepare(&mut rng, typ, &signers)?;
+        let prep = prepare(&mut rng, typ, &signers, intended_recipients)?;
         let mut configs = VecDeque::with_capacity(prep.len());
         let mut sign_hashers = VecDeque::with_capacity(prep.len());
         let mut ops = VecDeque::with_capacity(prep.len());
@@ -0,0 +1,603 @@
+//! Selection of recipient encryption keys, and negotiation of the encryption algorithms.
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    composed::{CertificateValidity, SignedPublicKey},
+    crypto::{
+        aead::AeadAlgorithm, hash::HashAlgorithm, public_key::PublicKeyAlgorithm,
+        sym::SymmetricKeyAlgorithm,
+    },
+    errors::{ensure, Result},
+    packet::{self, KeyFlags},
+    policy::{Policy, StandardPolicy},
+    types::{
+        Fingerprint, KeyDetails, KeyId, KeyVersion, PublicKeyTrait, PublicParams, SignatureBytes,
+    },
+};
+
+/// Symmetric algorithms that may be negotiated, in order of preference of this implementation.
+///
+/// Recipient preferences are only honored for these algorithms.
+const SYMMETRIC_ALGORITHMS: &[SymmetricKeyAlgorithm] = &[
+    SymmetricKeyAlgorithm::AES256,
+    SymmetricKeyAlgorithm::AES192,
+    SymmetricKeyAlgorithm::AES128,
+    SymmetricKeyAlgorithm::Camellia256,
+    SymmetricKeyAlgorithm::Camellia192,
+    SymmetricKeyAlgorithm::Camellia128,
+    SymmetricKeyAlgorithm::Twofish,
+];
+
+/// AEAD algorithms that may be negotiated, in order of preference of this implementation.
+const AEAD_ALGORITHMS: &[AeadAlgorithm] =
+    &[AeadAlgorithm::Ocb, AeadAlgorithm::Gcm, AeadAlgorithm::Eax];
+
+/// The algorithm that every implementation must support
+/// (<https://www.rfc-editor.org/rfc/rfc9580.html#name-symmetric-key-algorithms>).
+const MANDATORY_SYMMETRIC: SymmetricKeyAlgorithm = SymmetricKeyAlgorithm::AES128;
+
+/// The AEAD mode that every implementation must support
+/// (<https://www.rfc-editor.org/rfc/rfc9580.html#name-aead-algorithms>).
+const MANDATORY_AEAD: (SymmetricKeyAlgorithm, AeadAlgorithm) =
+    (SymmetricKeyAlgorithm::AES128, AeadAlgorithm::Ocb);
+
+/// The negotiated encryption container and algorithms for a set of recipients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NegotiatedEncryption {
+    /// Use a v1 SEIPD packet with the given symmetric algorithm.
+    SeipdV1 { sym_alg: SymmetricKeyAlgorithm },
+    /// Use a v2 SEIPD packet with the given symmetric and AEAD algorithm.
+    SeipdV2 {
+        sym_alg: SymmetricKeyAlgorithm,
+        aead: AeadAlgorithm,
+    },
+}
+
+impl NegotiatedEncryption {
+    /// The negotiated symmetric algorithm.
+    pub fn sym_alg(&self) -> SymmetricKeyAlgorithm {
+        match self {
+            Self::SeipdV1 { sym_alg } => *sym_alg,
+            Self::SeipdV2 { sym_alg, .. } => *sym_alg,
+        }
+    }
+}
+
+/// A (primary or sub-) key that was selected to encrypt to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionKey<'a> {
+    Primary(&'a packet::PublicKey),
+    Subkey(&'a packet::PublicSubkey),
+}
+
+/// A recipient certificate, and the keys that were selected from it.
+#[derive(Debug, Clone)]
+pub struct SelectedRecipient<'a> {
+    pub certificate: &'a SignedPublicKey,
+    /// All valid encryption capable keys of the certificate.
+    pub keys: Vec<EncryptionKey<'a>>,
+    /// Valid additional decryption subkeys (ADSKs) of the certificate, that are not already
+    /// in `keys`.
+    pub adsks: Vec<EncryptionKey<'a>>,
+}
+
+/// The result of selecting the encryption keys for a set of recipient certificates, and
+/// negotiating the algorithms to use for them.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> pgp::errors::Result<()> {
+/// use pgp::composed::{
+///     Deserializable, MessageBuilder, NegotiatedEncryption, RecipientSelection, SignedPublicKey,
+/// };
+/// use pgp::crypto::aead::ChunkSize;
+///
+/// let (alice, _) = SignedPublicKey::from_armor_file("alice.pub.asc")?;
+/// let (bob, _) = SignedPublicKey::from_armor_file("bob.pub.asc")?;
+/// let selection = RecipientSelection::select([&alice, &bob])?;
+///
+/// let mut rng = rand::thread_rng();
+/// let builder = MessageBuilder::from_bytes("", "hello");
+/// let message = match selection.encryption() {
+///     NegotiatedEncryption::SeipdV1 { sym_alg } => {
+///         let mut builder = builder.seipd_v1(&mut rng, sym_alg);
+///         builder.encrypt_to_recipients(&mut rng, &selection)?;
+///         builder.to_vec(&mut rng)?
+///     }
+///     NegotiatedEncryption::SeipdV2 { sym_alg, aead } => {
+///         let mut builder = builder.seipd_v2(&mut rng, sym_alg, aead, ChunkSize::default());
+///         builder.encrypt_to_recipients(&mut rng, &selection)?;
+///         builder.to_vec(&mut rng)?
+///     }
+/// };
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecipientSelection<'a> {
+    recipients: Vec<SelectedRecipient<'a>>,
+    encryption: NegotiatedEncryption,
+    include_adsks: bool,
+}
+
+impl<'a> RecipientSelection<'a> {
+    /// Selects the encryption keys of `certificates`, and negotiates the algorithms to use.
+    ///
+    /// See [`Self::select_at`].
+    pub fn select(certificates: impl IntoIterator<Item = &'a SignedPublicKey>) -> Result<Self> {
+        Self::select_at(certificates, Utc::now())
+    }
+
+    /// Selects the encryption keys of `certificates`, and negotiates the algorithms to use,
+    /// evaluating the certificates at `reference_time`.
+    ///
+    /// For each certificate, all keys are selected that are valid (bound, not expired, not
+    /// revoked) and are flagged for encrypting communications or storage.
+    /// This fails if any certificate is not valid or has no such key.
+    ///
+    /// A v2 SEIPD container is only chosen if all recipients advertise support for it.
+    /// The symmetric (and AEAD) algorithm is the most preferred one that all recipients accept,
+    /// falling back to the mandatory to implement algorithms.
+    ///
+    /// The certificates are evaluated with the [`StandardPolicy`], see
+    /// [`Self::select_with_policy`].
+    pub fn select_at(
+        certificates: impl IntoIterator<Item = &'a SignedPublicKey>,
+        reference_time: DateTime<Utc>,
+    ) -> Result<Self> {
+        Self::select_with_policy(certificates, reference_time, &StandardPolicy::default())
+    }
+
+    /// Like [`Self::select_at`], but the certificates are evaluated with `policy`.
+    ///
+    /// Keys and binding signatures that `policy` rejects are not selected.
+    pub fn select_with_policy(
+        certificates: impl IntoIterator<Item = &'a SignedPublicKey>,
+        reference_time: DateTime<Utc>,
+        policy: &dyn Policy,
+    ) -> Result<Self> {
+        let mut recipients = Vec::new();
+        let mut preferences = Vec::new();
+
+        for certificate in certificates {
+            let validity = certificate.validity_at_with_policy(reference_time, policy);
+            ensure!(
+                validity.is_valid(),
+                "certificate {} is not valid: {:?}",
+                certificate.fingerprint(),
+                validity.status()
+            );
+
+            let keys = encryption_keys(&validity);
+            ensure!(
+                !keys.is_empty(),
+                "certificate {} has no valid encryption key",
+                certificate.fingerprint()
+            );
+
+            let adsks = validity
+                .adsks()
+                .map(|s| EncryptionKey::Subkey(s.key))
+                .filter(|k| !keys.contains(k))
+                .collect();
+
+            preferences.push(Preferences::from_validity(&validity));
+            recipients.push(SelectedRecipient {
+                certificate,
+                keys,
+                adsks,
+            });
+        }
+        ensure!(!recipients.is_empty(), "no recipients");
+
+        let encryption = negotiate(&preferences);
+
+        Ok(RecipientSelection {
+            recipients,
+            encryption,
+            include_adsks: false,
+        })
+    }
+
+    /// Whether to also encrypt to the additional decryption subkeys (ADSKs) of the recipients.
+    ///
+    /// ADSKs are used for escrow: a recipient asks senders to make messages decryptable by
+    /// a third party as well. They are not included by default.
+    pub fn include_adsks(mut self, include: bool) -> Self {
+        self.include_adsks = include;
+        self
+    }
+
+    /// The selected recipients.
+    pub fn recipients(&self) -> &[SelectedRecipient<'a>] {
+        &self.recipients
+    }
+
+    /// All selected encryption keys, over all recipients.
+    ///
+    /// This includes the ADSKs of the recipients, if enabled with [`Self::include_adsks`].
+    pub fn keys(&self) -> impl Iterator<Item = &EncryptionKey<'a>> {
+        self.recipients.iter().flat_map(|r| {
+            let adsks = if self.include_adsks {
+                &r.adsks[..]
+            } else {
+                &[]
+            };
+            r.keys.iter().chain(adsks)
+        })
+    }
+
+    /// The negotiated encryption container and algorithms.
+    pub fn encryption(&self) -> NegotiatedEncryption {
+        self.encryption
+    }
+}
+
+/// The algorithm preferences of one recipient.
+#[derive(Debug)]
+struct Preferences {
+    seipd_v2: bool,
+    symmetric: Vec<SymmetricKeyAlgorithm>,
+    aead: Vec<(SymmetricKeyAlgorithm, AeadAlgorithm)>,
+}
+
+impl Preferences {
+    fn from_validity(validity: &CertificateValidity<'_>) -> Self {
+        let sigs = validity.preference_signatures();
+
+        let seipd_v2 = sigs
+            .iter()
+            .find_map(|s| s.features())
+            .is_some_and(|f| f.seipd_v2());
+        let symmetric = sigs
+            .iter()
+            .map(|s| s.preferred_symmetric_algs())
+            .find(|p| !p.is_empty())
+            .unwrap_or_default()
+            .to_vec();
+        let aead = sigs
+            .iter()
+            .map(|s| s.preferred_aead_algs())
+            .find(|p| !p.is_empty())
+            .unwrap_or_default()
+            .to_vec();
+
+        Preferences {
+            seipd_v2,
+            symmetric,
+            aead,
+        }
+    }
+
+    fn accepts_symmetric(&self, alg: SymmetricKeyAlgorithm) -> bool {
+        alg == MANDATORY_SYMMETRIC || self.symmetric.contains(&alg)
+    }
+
+    fn accepts_aead(&self, mode: (SymmetricKeyAlgorithm, AeadAlgorithm)) -> bool {
+        mode == MANDATORY_AEAD || self.aead.contains(&mode)
+    }
+}
+
+fn negotiate(preferences: &[Preferences]) -> NegotiatedEncryption {
+    if preferences.iter().all(|p| p.seipd_v2) {
+        // Candidates in order of the recipients' preferences, then in our own order.
+        let candidates = preferences
+            .iter()
+            .flat_map(|p| p.aead.iter().copied())
+            .chain(
+                SYMMETRIC_ALGORITHMS
+                    .iter()
+                    .flat_map(|sym| AEAD_ALGORITHMS.iter().map(move |aead| (*sym, *aead))),
+            );
+        let (sym_alg, aead) = candidates
+            .filter(|(sym, aead)| {
+                SYMMETRIC_ALGORITHMS.contains(sym) && AEAD_ALGORITHMS.contains(aead)
+            })
+            .find(|mode| preferences.iter().all(|p| p.accepts_aead(*mode)))
+            .unwrap_or(MANDATORY_AEAD);
+
+        NegotiatedEncryption::SeipdV2 { sym_alg, aead }
+    } else {
+        let sym_alg = preferences
+            .iter()
+            .flat_map(|p| p.symmetric.iter().copied())
+            .chain(SYMMETRIC_ALGORITHMS.iter().copied())
+            .filter(|sym| SYMMETRIC_ALGORITHMS.contains(sym))
+            .find(|sym| preferences.iter().all(|p| p.accepts_symmetric(*sym)))
+            .unwrap_or(MANDATORY_SYMMETRIC);
+
+        NegotiatedEncryption::SeipdV1 { sym_alg }
+    }
+}
+
+/// All valid, encryption capable keys of a certificate.
+fn encryption_keys<'a>(validity: &CertificateValidity<'a>) -> Vec<EncryptionKey<'a>> {
+    let is_encryption = |flags: KeyFlags| flags.encrypt_comms() || flags.encrypt_storage();
+
+    let mut keys = Vec::new();
+    let primary = validity.primary_key();
+    if is_encryption(validity.key_flags()) && primary.is_encryption_key() {
+        keys.push(EncryptionKey::Primary(primary));
+    }
+
+    keys.extend(
+        validity
+            .valid_subkeys()
+            .filter(|s| is_encryption(s.key_flags()) && s.key.is_encryption_key())
+            .map(|s| EncryptionKey::Subkey(s.key)),
+    );
+
+    keys
+}
+
+impl KeyDetails for EncryptionKey<'_> {
+    fn version(&self) -> KeyVersion {
+        match self {
+            Self::Primary(k) => k.version(),
+            Self::Subkey(k) => k.version(),
+        }
+    }
+
+    fn fingerprint(&self) -> Fingerprint {
+        match self {
+            Self::Primary(k) => k.fingerprint(),
+            Self::Subkey(k) => k.fingerprint(),
+        }
+    }
+
+    fn key_id(&self) -> KeyId {
+        match self {
+            Self::Primary(k) => k.key_id(),
+            Self::Subkey(k) => k.key_id(),
+        }
+    }
+
+    fn algorithm(&self) -> PublicKeyAlgorithm {
+        match self {
+            Self::Primary(k) => k.algorithm(),
+            Self::Subkey(k) => k.algorithm(),
+        }
+    }
+}
+
+impl PublicKeyTrait for EncryptionKey<'_> {
+    fn verify_signature(
+        &self,
+        hash: HashAlgorithm,
+        data: &[u8],
+        sig: &SignatureBytes,
+    ) -> Result<()> {
+        match self {
+            Self::Primary(k) => k.verify_signature(hash, data, sig),
+            Self::Subkey(k) => k.verify_signature(hash, data, sig),
+        }
+    }
+
+    fn public_params(&self) -> &PublicParams {
+        match self {
+            Self::Primary(k) => k.public_params(),
+            Self::Subkey(k) => k.public_params(),
+        }
+    }
+
+    fn created_at(&self) -> &DateTime<Utc> {
+        match self {
+            Self::Primary(k) => k.created_at(),
+            Self::Subkey(k) => k.created_at(),
+        }
+    }
+
+    fn expiration(&self) -> Option<u16> {
+        match self {
+            Self::Primary(k) => k.expiration(),
+            Self::Subkey(k) => k.expiration(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use chrono::Duration;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use smallvec::smallvec;
+
+    use super::*;
+    use crate::{
+        composed::{KeyType, Message, MessageBuilder},
+        crypto::aead::ChunkSize,
+        types::Password,
+        util::test::{alice_key, alice_params, generate, key_params, TestSubkey},
+    };
+
+    #[test]
+    fn negotiate_seipd_v2() {
+        use AeadAlgorithm::*;
+        use SymmetricKeyAlgorithm::*;
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut params = alice_params(KeyVersion::V6);
+        params
+            .feature_seipd_v2(true)
+            .preferred_symmetric_algorithms(smallvec![AES256, AES128])
+            .preferred_aead_algorithms(smallvec![(AES256, Gcm), (AES256, Ocb), (AES128, Ocb)]);
+        let a = generate(&mut rng, &params, &Password::empty()).signed_public_key();
+
+        let mut params = alice_params(KeyVersion::V4);
+        params
+            .feature_seipd_v2(true)
+            .preferred_symmetric_algorithms(smallvec![AES256, AES128])
+            .preferred_aead_algorithms(smallvec![(AES256, Ocb), (AES128, Ocb)]);
+        let b = generate(&mut rng, &params, &Password::empty()).signed_public_key();
+
+        let selection = RecipientSelection::select([&a, &b]).unwrap();
+        assert_eq!(
+            selection.encryption(),
+            NegotiatedEncryption::SeipdV2 {
+                sym_alg: AES256,
+                aead: Ocb
+            }
+        );
+
+        // only the encryption subkeys are selected
+        assert_eq!(selection.recipients().len(), 2);
+        for (recipient, cert) in selection.recipients().iter().zip([&a, &b]) {
+            assert_eq!(recipient.certificate, cert);
+            assert_eq!(
+                recipient.keys,
+                vec![EncryptionKey::Subkey(&cert.public_subkeys[0].key)]
+            );
+        }
+    }
+
+    #[test]
+    fn negotiate_seipd_v1() {
+        use SymmetricKeyAlgorithm::*;
+
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut gen_cert = |version, seipd_v2, symmetric| {
+            let mut params = alice_params(version);
+            params
+                .feature_seipd_v2(seipd_v2)
+                .preferred_symmetric_algorithms(symmetric);
+            generate(&mut rng, &params, &Password::empty()).signed_public_key()
+        };
+
+        let a = gen_cert(KeyVersion::V4, false, smallvec![Camellia256, AES192]);
+        let b = gen_cert(KeyVersion::V6, true, smallvec![AES192, Camellia256]);
+
+        let selection = RecipientSelection::select([&a, &b]).unwrap();
+        assert_eq!(
+            selection.encryption(),
+            NegotiatedEncryption::SeipdV1 {
+                sym_alg: Camellia256
+            }
+        );
+
+        // nothing in common falls back to AES128, which every implementation supports
+        let c = gen_cert(KeyVersion::V4, false, smallvec![AES256]);
+        let selection = RecipientSelection::select([&a, &c]).unwrap();
+        assert_eq!(selection.encryption().sym_alg(), AES128);
+
+        // only the algorithms supported here are negotiated
+        let d = gen_cert(KeyVersion::V4, false, smallvec![TripleDES]);
+        let e = gen_cert(KeyVersion::V4, false, smallvec![TripleDES]);
+        let selection = RecipientSelection::select([&d, &e]).unwrap();
+        assert_eq!(selection.encryption().sym_alg(), AES128);
+    }
+
+    #[test]
+    fn reject_unusable_certificates() {
+        let key = alice_key(KeyVersion::V4, 0);
+
+        // no encryption subkey
+        let mut cert = key.signed_public_key();
+        cert.public_subkeys.remove(0);
+        assert!(RecipientSelection::select([&cert]).is_err());
+
+        // not valid yet
+        let cert = key.signed_public_key();
+        let before = *cert.primary_key.created_at() - Duration::days(1);
+        assert!(RecipientSelection::select_at([&cert], before).is_err());
+
+        // no recipients at all
+        assert!(RecipientSelection::select([]).is_err());
+    }
+
+    #[test]
+    fn reject_by_policy() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let params = key_params(
+            KeyVersion::V4,
+            KeyType::Ed25519Legacy,
+            &[TestSubkey::Encrypt(KeyType::Rsa(2048))],
+        );
+        let cert = generate(&mut rng, &params, &Password::empty()).signed_public_key();
+        assert!(RecipientSelection::select([&cert]).is_ok());
+
+        // the only encryption subkey is rejected
+        let policy = StandardPolicy {
+            min_rsa_bits: 3072,
+            ..Default::default()
+        };
+        assert!(RecipientSelection::select_with_policy([&cert], Utc::now(), &policy).is_err());
+    }
+
+    #[test]
+    fn encrypt_to_recipients_roundtrip() {
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+
+        for (version, seipd_v2) in [(KeyVersion::V4, false), (KeyVersion::V6, true)] {
+            let mut params = alice_params(version);
+            params.feature_seipd_v2(seipd_v2);
+            let a = generate(&mut rng, &params, &Password::empty());
+            let b = generate(&mut rng, &params, &Password::empty());
+            let (pa, pb) = (a.signed_public_key(), b.signed_public_key());
+
+            let selection = RecipientSelection::select([&pa, &pb]).unwrap();
+            let builder = MessageBuilder::from_bytes("", &b"hello world"[..]);
+            let encrypted = match selection.encryption() {
+                NegotiatedEncryption::SeipdV1 { sym_alg } => {
+                    assert!(!seipd_v2);
+                    let mut builder = builder.seipd_v1(&mut rng, sym_alg);
+                    builder.encrypt_to_recipients(&mut rng, &selection).unwrap();
+                    builder.to_vec(&mut rng).unwrap()
+                }
+                NegotiatedEncryption::SeipdV2 { sym_alg, aead } => {
+                    assert!(seipd_v2);
+                    let mut builder =
+                        builder.seipd_v2(&mut rng, sym_alg, aead, ChunkSize::default());
+                    builder.encrypt_to_recipients(&mut rng, &selection).unwrap();
+                    builder.to_vec(&mut rng).unwrap()
+                }
+            };
+
+            for key in [&a, &b] {
+                let message = Message::from_bytes(&encrypted[..]).unwrap();
+                let mut decrypted = message.decrypt(&Password::empty(), key).unwrap();
+                assert_eq!(decrypted.as_data_vec().unwrap(), b"hello world");
+            }
+        }
+    }
+
+    #[test]
+    fn encrypt_to_adsk() {
+        let mut rng = ChaCha8Rng::seed_from_u64(6);
+
+        let mut a = alice_key(KeyVersion::V4, 0);
+        let escrow = alice_key(KeyVersion::V4, 1);
+        let adsk = escrow.secret_subkeys[0].key.public_key();
+        a.add_adsk(&mut rng, &Password::empty(), adsk).unwrap();
+        a.verify().unwrap();
+
+        let pa = a.signed_public_key();
+        let validity = pa.validity();
+        let adsks: Vec<_> = validity.adsks().map(|s| s.key.fingerprint()).collect();
+        assert_eq!(adsks, [adsk.fingerprint()]);
+
+        let selection = RecipientSelection::select([&pa]).unwrap();
+        assert_eq!(selection.keys().count(), 1);
+        let selection = selection.include_adsks(true);
+        assert_eq!(selection.keys().count(), 2);
+
+        let NegotiatedEncryption::SeipdV1 { sym_alg } = selection.encryption() else {
+            panic!("unexpected encryption {:?}", selection.encryption());
+        };
+        let mut builder =
+            MessageBuilder::from_bytes("", &b"hello world"[..]).seipd_v1(&mut rng, sym_alg);
+        builder.encrypt_to_recipients(&mut rng, &selection).unwrap();
+        let encrypted = builder.to_vec(&mut rng).unwrap();
+
+        for key in [&a, &escrow] {
+            let message = Message::from_bytes(&encrypted[..]).unwrap();
+            let mut decrypted = message.decrypt(&Password::empty(), key).unwrap();
+            assert_eq!(decrypted.as_data_vec().unwrap(), b"hello world");
+        }
+
+        // ADSKs are not used for regular encryption
+        assert!(!adsks.contains(&selection.recipients()[0].keys[0].fingerprint()));
+    }
+}
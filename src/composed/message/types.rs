@@ -8,7 +8,12 @@ use super::reader::{
 };
 use crate::{
     armor,
-    composed::{message::decrypt::*, signed_key::SignedSecretKey},
+    composed::{
+        message::decrypt::*,
+        signed_key::{SignedPublicKey, SignedSecretKey},
+        store::{CertStore, StoreEntry},
+        VerificationReport,
+    },
     crypto::sym::SymmetricKeyAlgorithm,
     errors::{bail, ensure, ensure_eq, format_err, Error, Result},
     packet::{
@@ -17,8 +22,11 @@ use crate::{
         SymKeyEncryptedSessionKey,
     },
     parsing_reader::BufReadParsing,
+    policy::Policy,
     ser::Serialize,
-    types::{EskType, KeyDetails, Password, PkeskVersion, PublicKeyTrait, SecretParams, Tag},
+    types::{
+        EskType, Fingerprint, KeyDetails, Password, PkeskVersion, PublicKeyTrait, SecretParams, Tag,
+    },
     util::impl_try_from_into,
 };
 
@@ -694,6 +702,72 @@ impl<'a> Message<'a> {
         Ok(out)
     }
 
+    /// Recursively find all signatures in this message and report on each of them, looking
+    /// up the signers in `certs`, and only accepting signatures and keys that `policy` accepts.
+    ///
+    /// Reports are in order from the outermost to the innermost signature. Unlike
+    /// [`Self::verify_nested`], they tell apart unknown signers, bad signatures and signing
+    /// keys that were not valid when the signature was made.
+    ///
+    /// Only signed and one pass signed messages can be verified.
+    /// The message must have been read to the end before calling this.
+    pub fn verify_reports<'b>(
+        &'b self,
+        certs: &[&'b SignedPublicKey],
+        policy: &dyn Policy,
+    ) -> Result<Vec<VerificationReport<'b>>> {
+        let mut reports = Vec::new();
+
+        let mut current_message = self;
+        // do not recurse arbitrarily deep
+        for _ in 0..1024 {
+            let (signature, inner) = match current_message {
+                Message::SignedOnePass { reader, .. } => {
+                    let Some(signature) = reader.signature() else {
+                        bail!("cannot verify message before reading the final signature packet");
+                    };
+                    (signature, reader.get_ref())
+                }
+                Message::Signed { reader, .. } => (reader.signature(), reader.get_ref()),
+                Message::Literal { .. } => {
+                    break;
+                }
+                Message::Compressed { .. } => {
+                    bail!("message must be decompressed before verifying");
+                }
+                Message::Encrypted { .. } => {
+                    bail!("message must be decrypted before verifying");
+                }
+            };
+
+            reports.push(VerificationReport::new(signature, certs, policy, |key| {
+                current_message.verify(key).map(|_| ())
+            }));
+            current_message = inner;
+        }
+
+        Ok(reports)
+    }
+
+    /// Like [`Self::verify_reports`], but for a message that was decrypted by a key of the
+    /// certificate with the primary key fingerprint `recipient`.
+    ///
+    /// Signatures that list their intended recipients are only valid if `recipient` is among
+    /// them, which detects signed messages that were decrypted and then re-encrypted to a
+    /// different recipient (see [`VerificationReport::check_intended_recipient`]).
+    pub fn verify_reports_for_recipient<'b>(
+        &'b self,
+        certs: &[&'b SignedPublicKey],
+        policy: &dyn Policy,
+        recipient: &Fingerprint,
+    ) -> Result<Vec<VerificationReport<'b>>> {
+        let mut reports = self.verify_reports(certs, policy)?;
+        for report in &mut reports {
+            report.check_intended_recipient(recipient);
+        }
+        Ok(reports)
+    }
+
     /// Reads the contents and discards it, then verifies the message.
     pub fn verify_read(&mut self, key: &dyn PublicKeyTrait) -> Result<&Signature> {
         self.drain()?;
@@ -788,6 +862,50 @@ impl<'a> Message<'a> {
         }
     }
 
+    /// Verify this message, looking up the signer in `store`.
+    ///
+    /// Only signed and one pass signed messages can be verified.
+    /// The message must have been read to the end before calling this.
+    ///
+    /// If the signature was made by a key in the store, returns the certificate of the signer
+    /// and the matching signature.
+    pub fn verify_with_store<'s, C: StoreEntry>(
+        &self,
+        store: &'s CertStore<C>,
+    ) -> Result<(&'s C, &Signature)> {
+        let signature = match self {
+            Message::SignedOnePass { reader, .. } => match reader.signature() {
+                Some(signature) => signature,
+                None => {
+                    bail!("cannot verify message before reading the final signature packet")
+                }
+            },
+            Message::Signed { reader, .. } => reader.signature(),
+            Message::Compressed { .. } => {
+                bail!("message must be decompressed before verifying");
+            }
+            Message::Encrypted { .. } => {
+                bail!("message must be decrypted before verifying");
+            }
+            Message::Literal { .. } => {
+                bail!("message was not signed");
+            }
+        };
+
+        let mut err = None;
+        for (cert, key) in store.lookup_by_issuer(signature) {
+            match self.verify(key) {
+                Ok(sig) => return Ok((cert, sig)),
+                Err(e) => err = Some(e),
+            }
+        }
+
+        match err {
+            Some(err) => Err(err),
+            None => bail!("no key found for the signature issuer"),
+        }
+    }
+
     /// Decrypt the message using the given key.
     /// Returns a message decryptor.
     pub fn decrypt(self, key_pw: &Password, key: &SignedSecretKey) -> Result<Message<'a>> {
@@ -876,6 +994,11 @@ impl<'a> Message<'a> {
             } => {
                 // Lets go and find things, with which we can decrypt
                 let allow_legacy = ring.allow_legacy;
+                if allow_legacy && edata.tag() == Tag::SymEncryptedData {
+                    if let Some(policy) = ring.policy {
+                        policy.legacy_encryption()?;
+                    }
+                }
                 let (session_key, result) = ring.find_session_key(&esk, abort_early)?;
                 let Some(session_key) = session_key else {
                     return Err(Error::MissingKey);
@@ -1080,6 +1203,14 @@ pub struct TheRing<'a> {
     ///
     /// Defaults to `false`.
     pub allow_legacy: bool,
+    /// If set, SED packets are only decrypted if both `allow_legacy` is set and the policy
+    /// allows it.
+    ///
+    /// This is the only check the policy is used for during decryption, the recipient keys
+    /// and the algorithms of the session keys are not checked.
+    ///
+    /// Defaults to `None`.
+    pub policy: Option<&'a dyn Policy>,
 }
 
 impl TheRing<'_> {
@@ -1144,6 +1275,10 @@ impl TheRing<'_> {
                     ($skey:expr, $pkey:expr, $values:expr) => {
                         debug!("found matching key {:?}, trying to decrypt", $skey.key_id());
                         match $skey.secret_params() {
+                            SecretParams::Encrypted(params) if params.gnu_stub().is_some() => {
+                                debug!("key has no secret key material, skipping");
+                                result.secret_keys[i] = InnerRingResult::SecretKeyUnavailable;
+                            }
                             SecretParams::Encrypted(_) => {
                                 // unlock
                                 for pw in &self.key_passwords {
@@ -1318,6 +1453,9 @@ pub enum InnerRingResult {
     InconsistentSessionKey,
     /// Decryption of the ESK has failed.
     Invalid,
+    /// The matching secret key is a stub without secret key material, e.g. because it is
+    /// stored on a smartcard.
+    SecretKeyUnavailable,
     /// A session key was successfully produced
     /// The actual encrypted data decryption process can still fail.
     Ok,
@@ -215,7 +215,7 @@ pub trait Deserializable: Sized {
 
 /// Process results from low level packet parser:
 ///
-/// - Skip Marker and Padding packets.
+/// - Skip Marker, Padding and Trust packets.
 /// - Pass through other packets.
 /// - Skip any `Error::Unsupported`, those were marked as "safe to ignore" by the low level parser.
 /// - Skip `Error::Incomplete`
@@ -234,6 +234,10 @@ pub(crate) fn filter_parsed_packet_results(p: Result<Packet>) -> Option<Result<P
                 debug!("skipping padding packet");
                 return None;
             }
+            if let Packet::Trust(_) = packet {
+                debug!("skipping trust packet");
+                return None;
+            }
             Some(p)
         }
         Err(e) => {
@@ -9,6 +9,11 @@ use crate::{
     types::{PublicKeyTrait, Tag},
 };
 
+mod detached;
+mod report;
+
+pub use self::{detached::*, report::*};
+
 /// Standalone signature as defined by the cleartext framework.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StandaloneSignature {
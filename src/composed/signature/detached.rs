@@ -0,0 +1,593 @@
+//! Creation and verification of detached signatures over streamed data.
+
+use std::{io::Read, iter::Peekable, path::Path};
+
+use chrono::{SubsecRound, Utc};
+use digest::DynDigest;
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    armor,
+    composed::{ArmorOptions, Deserializable, SignedSecretKey, SigningKey, StandaloneSignature},
+    crypto::hash::HashAlgorithm,
+    errors::{bail, ensure, format_err, unsupported_err, Error, Result},
+    packet::{
+        Packet, PacketTrait, Signature, SignatureConfig, SignatureType, SignatureVersionSpecific,
+        Subpacket, SubpacketData,
+    },
+    policy::{Policy, StandardPolicy},
+    ser::Serialize,
+    types::{KeyVersion, Password, PublicKeyTrait, SecretKeyTrait, Tag},
+    util::NormalizingHasher,
+};
+
+/// Size of the buffer the data to be signed is read in.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// One or more detached signatures over the same data, written as a single signature block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetachedSignatures {
+    pub signatures: Vec<StandaloneSignature>,
+}
+
+impl DetachedSignatures {
+    pub fn new(signatures: Vec<StandaloneSignature>) -> Self {
+        DetachedSignatures { signatures }
+    }
+
+    /// Verifies all signatures over the data read from `source`.
+    ///
+    /// See [`verify_detached`].
+    pub fn verify_reader<R: Read>(
+        &self,
+        keys: &[&dyn PublicKeyTrait],
+        source: R,
+    ) -> Result<Vec<DetachedVerification>> {
+        verify_detached(&self.signatures, keys, source)
+    }
+
+    /// Verifies all signatures over the data read from `source`, applying `policy`.
+    ///
+    /// See [`verify_detached_with_policy`].
+    pub fn verify_reader_with_policy<R: Read>(
+        &self,
+        keys: &[&dyn PublicKeyTrait],
+        source: R,
+        policy: &dyn Policy,
+    ) -> Result<Vec<DetachedVerification>> {
+        verify_detached_with_policy(&self.signatures, keys, source, policy)
+    }
+
+    pub fn to_armored_writer(
+        &self,
+        writer: &mut impl std::io::Write,
+        opts: ArmorOptions<'_>,
+    ) -> Result<()> {
+        armor::write(
+            self,
+            armor::BlockType::Signature,
+            writer,
+            opts.headers,
+            opts.include_checksum,
+        )
+    }
+
+    pub fn to_armored_bytes(&self, opts: ArmorOptions<'_>) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+
+        self.to_armored_writer(&mut buf, opts)?;
+
+        Ok(buf)
+    }
+
+    pub fn to_armored_string(&self, opts: ArmorOptions<'_>) -> Result<String> {
+        let res = String::from_utf8(self.to_armored_bytes(opts)?).map_err(|e| e.utf8_error())?;
+        Ok(res)
+    }
+}
+
+impl Serialize for DetachedSignatures {
+    fn to_writer<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
+        self.signatures.to_writer(writer)
+    }
+
+    fn write_len(&self) -> usize {
+        self.signatures.write_len()
+    }
+}
+
+impl Deserializable for DetachedSignatures {
+    /// Parse all signatures of a signature block.
+    fn from_packets<'a, I: Iterator<Item = Result<Packet>> + 'a>(
+        packets: Peekable<I>,
+    ) -> Box<dyn Iterator<Item = Result<Self>> + 'a> {
+        Box::new(DetachedSignaturesParser { source: packets })
+    }
+
+    fn matches_block_type(typ: armor::BlockType) -> bool {
+        matches!(typ, armor::BlockType::Signature)
+    }
+}
+
+pub struct DetachedSignaturesParser<I: Sized + Iterator<Item = Result<Packet>>> {
+    source: Peekable<I>,
+}
+
+impl<I: Sized + Iterator<Item = Result<Packet>>> Iterator for DetachedSignaturesParser<I> {
+    type Item = Result<DetachedSignatures>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut signatures = Vec::new();
+        for packet in self.source.by_ref() {
+            let signature = packet.and_then(|packet| match packet.tag() {
+                Tag::Signature => packet.try_into().map(StandaloneSignature::new),
+                _ => Err(format_err!("unexpected packet {:?}", packet.tag())),
+            });
+            match signature {
+                Ok(signature) => signatures.push(signature),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+
+        if signatures.is_empty() {
+            return None;
+        }
+
+        Some(Ok(DetachedSignatures::new(signatures)))
+    }
+}
+
+/// A configured signer of a [`DetachedSignatureBuilder`].
+struct Signer<'a> {
+    key: &'a dyn SecretKeyTrait,
+    key_pw: Password,
+    hash_algorithm: HashAlgorithm,
+}
+
+/// Creates detached signatures by one or more signers, reading the data only once.
+///
+/// # Example
+///
+/// ```rust
+/// # use pgp::composed::{Deserializable, SignedSecretKey};
+/// use pgp::composed::DetachedSignatureBuilder;
+/// use pgp::crypto::hash::HashAlgorithm;
+/// use pgp::types::Password;
+/// use rand::thread_rng;
+///
+/// # let (key, _) = SignedSecretKey::from_armor_file("./tests/autocrypt/alice@autocrypt.example.sec.asc")?;
+/// let data = std::io::Cursor::new(b"release artifact");
+///
+/// let mut builder = DetachedSignatureBuilder::new();
+/// builder.sign(&key.primary_key, Password::empty(), HashAlgorithm::Sha256);
+/// let signatures = builder.sign_reader(thread_rng(), data)?;
+///
+/// let armored = signatures.to_armored_string(Default::default())?;
+/// # Ok::<(), pgp::errors::Error>(())
+/// ```
+pub struct DetachedSignatureBuilder<'a> {
+    signers: Vec<Signer<'a>>,
+    sign_typ: SignatureType,
+}
+
+impl Default for DetachedSignatureBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> DetachedSignatureBuilder<'a> {
+    pub fn new() -> Self {
+        DetachedSignatureBuilder {
+            signers: Vec::new(),
+            sign_typ: SignatureType::Binary,
+        }
+    }
+
+    /// Configure the signatures to use `SignatureType::Binary`.
+    ///
+    /// This is the default.
+    pub fn sign_binary(&mut self) -> &mut Self {
+        self.sign_typ = SignatureType::Binary;
+        self
+    }
+
+    /// Configure the signatures to use `SignatureType::Text`, line endings are hashed in
+    /// normalized form.
+    pub fn sign_text(&mut self) -> &mut Self {
+        self.sign_typ = SignatureType::Text;
+        self
+    }
+
+    pub fn sign(
+        &mut self,
+        key: &'a dyn SecretKeyTrait,
+        key_pw: Password,
+        hash_algorithm: HashAlgorithm,
+    ) -> &mut Self {
+        self.signers.push(Signer {
+            key,
+            key_pw,
+            hash_algorithm,
+        });
+        self
+    }
+
+    /// Sign with the currently valid signing key of `key`, using its default hash algorithm.
+    ///
+    /// See [`SignedSecretKey::signing_key`] for how the signing key is selected.
+    pub fn sign_with_key(
+        &mut self,
+        key: &'a SignedSecretKey,
+        key_pw: Password,
+    ) -> Result<&mut Self> {
+        let signer = key.signing_key()?;
+        Ok(self.sign_with(signer, key_pw))
+    }
+
+    /// Sign with a selected signing key and its hash algorithm.
+    pub fn sign_with(&mut self, signer: SigningKey<'a>, key_pw: Password) -> &mut Self {
+        self.sign(signer.key(), key_pw, signer.hash_alg())
+    }
+
+    /// Reads `source` to its end and returns one signature per configured signer.
+    pub fn sign_reader<RAND, R>(self, mut rng: RAND, mut source: R) -> Result<DetachedSignatures>
+    where
+        RAND: Rng + CryptoRng,
+        R: Read,
+    {
+        ensure!(!self.signers.is_empty(), "no signers configured");
+
+        let mut hashers = self
+            .signers
+            .iter()
+            .map(|signer| signature_config(&mut rng, self.sign_typ, signer)?.into_hasher())
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut buffer = vec![0u8; BUFFER_SIZE];
+        loop {
+            let read = match source.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+            for hasher in &mut hashers {
+                hasher.update(&buffer[..read]);
+            }
+        }
+
+        let signatures = hashers
+            .into_iter()
+            .zip(&self.signers)
+            .map(|(hasher, signer)| {
+                let signature = hasher.sign(signer.key, &signer.key_pw)?;
+                Ok(StandaloneSignature::new(signature))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(DetachedSignatures::new(signatures))
+    }
+
+    /// Reads the file at `path` and returns one signature per configured signer.
+    pub fn sign_file<RAND>(self, rng: RAND, path: impl AsRef<Path>) -> Result<DetachedSignatures>
+    where
+        RAND: Rng + CryptoRng,
+    {
+        let file = std::fs::File::open(path)?;
+        self.sign_reader(rng, file)
+    }
+}
+
+fn signature_config<R: Rng + CryptoRng>(
+    mut rng: R,
+    typ: SignatureType,
+    signer: &Signer<'_>,
+) -> Result<SignatureConfig> {
+    let key = signer.key;
+    let hash_alg = signer.hash_algorithm;
+
+    let mut config = match key.version() {
+        KeyVersion::V4 => SignatureConfig::v4(typ, key.algorithm(), hash_alg),
+        KeyVersion::V6 => SignatureConfig::v6(&mut rng, typ, key.algorithm(), hash_alg)?,
+        v => bail!("unsupported key version {:?}", v),
+    };
+    config.hashed_subpackets = vec![
+        Subpacket::regular(SubpacketData::IssuerFingerprint(key.fingerprint()))?,
+        Subpacket::regular(SubpacketData::SignatureCreationTime(
+            Utc::now().trunc_subsecs(0),
+        ))?,
+    ];
+    if key.version() <= KeyVersion::V4 {
+        config.unhashed_subpackets = vec![Subpacket::regular(SubpacketData::Issuer(key.key_id()))?];
+    }
+
+    Ok(config)
+}
+
+/// The outcome of verifying one signature with [`verify_detached`].
+#[derive(Debug)]
+pub enum DetachedVerification {
+    /// The signature is valid, it was made by the key with this index.
+    Valid(usize),
+    /// None of the keys matches the issuer of the signature.
+    UnknownSigner,
+    /// The signature could not be verified.
+    Invalid(Error),
+}
+
+impl DetachedVerification {
+    pub fn is_valid(&self) -> bool {
+        matches!(self, DetachedVerification::Valid(_))
+    }
+}
+
+/// Verifies detached `signatures` over the data read from `source`, against `keys`.
+///
+/// The data is read only once, and hashed once per hash algorithm and mode (binary or text)
+/// in use. Version 6 signatures are salted, each of them needs its own hash.
+///
+/// Returns the outcome of each signature, in the same order. Errors are only returned if
+/// reading the data fails.
+///
+/// Signatures and keys are checked against the [`StandardPolicy`], see
+/// [`verify_detached_with_policy`].
+pub fn verify_detached<R: Read>(
+    signatures: &[StandaloneSignature],
+    keys: &[&dyn PublicKeyTrait],
+    source: R,
+) -> Result<Vec<DetachedVerification>> {
+    verify_detached_with_policy(signatures, keys, source, &StandardPolicy::default())
+}
+
+/// Like [`verify_detached`], but signatures and the keys that made them are checked against
+/// `policy`. Signatures that violate it are reported as [`DetachedVerification::Invalid`].
+pub fn verify_detached_with_policy<R: Read>(
+    signatures: &[StandaloneSignature],
+    keys: &[&dyn PublicKeyTrait],
+    mut source: R,
+    policy: &dyn Policy,
+) -> Result<Vec<DetachedVerification>> {
+    // Signatures that share the same hashing of the data use the same hasher.
+    let mut groups: Vec<(HashAlgorithm, Option<&[u8]>, bool)> = Vec::new();
+    let mut hashers = Vec::new();
+    let prepared: Vec<Result<usize>> = signatures
+        .iter()
+        .map(|sig| {
+            let sig = &sig.signature;
+            let Some(config) = sig.config() else {
+                unsupported_err!("signature version {:?}", sig.version());
+            };
+            ensure!(
+                matches!(config.typ, SignatureType::Binary | SignatureType::Text),
+                "not a signature over data: {:?}",
+                config.typ
+            );
+            policy.signature(sig)?;
+
+            let salt = match &config.version_specific {
+                SignatureVersionSpecific::V6 { salt } => Some(&salt[..]),
+                _ => None,
+            };
+            let text_mode = config.typ == SignatureType::Text;
+            let group = (config.hash_alg, salt, text_mode);
+            if let Some(i) = groups.iter().position(|g| g == &group) {
+                return Ok(i);
+            }
+
+            hashers.push(NormalizingHasher::new(sig.data_hasher()?, text_mode));
+            groups.push(group);
+            Ok(hashers.len() - 1)
+        })
+        .collect();
+
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    loop {
+        let read = match source.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        for hasher in &mut hashers {
+            hasher.hash_buf(&buffer[..read]);
+        }
+    }
+
+    let hashers: Vec<_> = hashers.into_iter().map(NormalizingHasher::done).collect();
+    let results = prepared
+        .into_iter()
+        .zip(signatures)
+        .map(|(prepared, sig)| match prepared {
+            Ok(i) => verify_hashed(&sig.signature, keys, &*hashers[i], policy),
+            Err(err) => DetachedVerification::Invalid(err),
+        })
+        .collect();
+
+    Ok(results)
+}
+
+fn verify_hashed(
+    signature: &Signature,
+    keys: &[&dyn PublicKeyTrait],
+    hasher: &(dyn DynDigest + Send),
+    policy: &dyn Policy,
+) -> DetachedVerification {
+    let mut result = DetachedVerification::UnknownSigner;
+    for (i, key) in keys.iter().enumerate() {
+        if !Signature::match_identity(signature, *key) {
+            continue;
+        }
+        match signature.verify_hashed(*key, hasher.box_clone()) {
+            Ok(()) => match policy.public_key(*key) {
+                Ok(()) => return DetachedVerification::Valid(i),
+                Err(violation) => result = DetachedVerification::Invalid(violation.into()),
+            },
+            Err(err) => result = DetachedVerification::Invalid(err),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        composed::KeyType,
+        policy::{NullPolicy, PolicyViolation},
+        util::test::gen_key,
+    };
+
+    #[test]
+    fn sign_multiple() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let v4 = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519Legacy, &[]);
+        let v6 = gen_key(&mut rng, KeyVersion::V6, KeyType::Ed25519, &[]);
+
+        // larger than the buffer
+        let data: Vec<u8> = (0..BUFFER_SIZE * 2 + 17).map(|i| i as u8).collect();
+
+        let mut builder = DetachedSignatureBuilder::new();
+        builder
+            .sign(&v4.primary_key, Password::empty(), HashAlgorithm::Sha256)
+            .sign_with_key(&v6, Password::empty())
+            .unwrap();
+        let signatures = builder.sign_reader(&mut rng, &data[..]).unwrap();
+        assert_eq!(signatures.signatures.len(), 2);
+
+        let armored = signatures.to_armored_string(Default::default()).unwrap();
+        let (parsed, _) = DetachedSignatures::from_armor_single(armored.as_bytes()).unwrap();
+        assert_eq!(parsed, signatures);
+
+        // each signature is a valid detached signature on its own
+        let (sigs, _) = StandaloneSignature::from_armor_many(armored.as_bytes()).unwrap();
+        let sigs: Vec<_> = sigs.collect::<Result<_>>().unwrap();
+        assert_eq!(sigs.len(), 2);
+        sigs[0].verify(v4.primary_key.public_key(), &data).unwrap();
+        sigs[1].verify(v6.primary_key.public_key(), &data).unwrap();
+        assert!(sigs[1]
+            .verify(v6.primary_key.public_key(), &data[1..])
+            .is_err());
+
+        let bytes = signatures.to_bytes().unwrap();
+        assert_eq!(
+            DetachedSignatures::from_bytes(&bytes[..]).unwrap(),
+            signatures
+        );
+    }
+
+    #[test]
+    fn sign_text() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let key = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519Legacy, &[]);
+
+        let mut builder = DetachedSignatureBuilder::new();
+        builder
+            .sign_text()
+            .sign_with_key(&key, Password::empty())
+            .unwrap();
+        let signatures = builder
+            .sign_reader(&mut rng, &b"hello\nworld\n"[..])
+            .unwrap();
+
+        let signature = &signatures.signatures[0].signature;
+        assert_eq!(signature.typ(), Some(SignatureType::Text));
+        signature
+            .verify(key.primary_key.public_key(), &b"hello\r\nworld\r\n"[..])
+            .unwrap();
+    }
+
+    #[test]
+    fn sign_without_signers() {
+        let rng = ChaCha8Rng::seed_from_u64(2);
+        assert!(DetachedSignatureBuilder::new()
+            .sign_reader(rng, &b"hello"[..])
+            .is_err());
+    }
+
+    #[test]
+    fn verify_multiple() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let v4 = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519Legacy, &[]);
+        let other = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519Legacy, &[]);
+        let v6 = gen_key(&mut rng, KeyVersion::V6, KeyType::Ed25519, &[]);
+
+        let data: Vec<u8> = (0..BUFFER_SIZE + 5).map(|i| i as u8).collect();
+
+        let mut builder = DetachedSignatureBuilder::new();
+        builder
+            .sign(&v4.primary_key, Password::empty(), HashAlgorithm::Sha256)
+            .sign(&other.primary_key, Password::empty(), HashAlgorithm::Sha256)
+            .sign(&v6.primary_key, Password::empty(), HashAlgorithm::Sha256);
+        let signatures = builder.sign_reader(&mut rng, &data[..]).unwrap();
+
+        let keys: [&dyn PublicKeyTrait; 2] =
+            [v4.primary_key.public_key(), v6.primary_key.public_key()];
+        let results = signatures.verify_reader(&keys, &data[..]).unwrap();
+        assert!(matches!(results[0], DetachedVerification::Valid(0)));
+        assert!(matches!(results[1], DetachedVerification::UnknownSigner));
+        assert!(matches!(results[2], DetachedVerification::Valid(1)));
+
+        let results = signatures.verify_reader(&keys, &data[1..]).unwrap();
+        assert!(matches!(results[0], DetachedVerification::Invalid(_)));
+        assert!(matches!(results[1], DetachedVerification::UnknownSigner));
+        assert!(matches!(results[2], DetachedVerification::Invalid(_)));
+    }
+
+    #[test]
+    fn verify_policy() {
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        // EdDSA refuses to sign SHA-1 digests
+        let key = gen_key(&mut rng, KeyVersion::V4, KeyType::Rsa(2048), &[]);
+
+        let mut builder = DetachedSignatureBuilder::new();
+        builder.sign(&key.primary_key, Password::empty(), HashAlgorithm::Sha1);
+        let signatures = builder.sign_reader(&mut rng, &b"hello"[..]).unwrap();
+
+        let keys: [&dyn PublicKeyTrait; 1] = [key.primary_key.public_key()];
+        let results = signatures.verify_reader(&keys, &b"hello"[..]).unwrap();
+        assert!(matches!(
+            results[0],
+            DetachedVerification::Invalid(Error::Policy {
+                source: PolicyViolation::WeakHash { .. }
+            })
+        ));
+
+        let results = signatures
+            .verify_reader_with_policy(&keys, &b"hello"[..], &NullPolicy)
+            .unwrap();
+        assert!(matches!(results[0], DetachedVerification::Valid(0)));
+    }
+
+    #[test]
+    fn verify_text() {
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let key = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519Legacy, &[]);
+
+        let mut builder = DetachedSignatureBuilder::new();
+        builder
+            .sign_text()
+            .sign_with_key(&key, Password::empty())
+            .unwrap();
+        let signatures = builder
+            .sign_reader(&mut rng, &b"hello\nworld\n"[..])
+            .unwrap();
+
+        // line endings are normalized, also across reads
+        let keys: [&dyn PublicKeyTrait; 1] = [key.primary_key.public_key()];
+        let source = (&b"hello\r"[..]).chain(&b"\nworld\r\n"[..]);
+        let results = signatures.verify_reader(&keys, source).unwrap();
+        assert!(results[0].is_valid());
+
+        let results = signatures
+            .verify_reader(&keys, &b"hello world\n"[..])
+            .unwrap();
+        assert!(!results[0].is_valid());
+    }
+}
@@ -0,0 +1,560 @@
+//! Structured reports on the verification of signatures over data.
+//!
+//! Unlike the plain `verify` methods, which only say whether a signature verified against a
+//! particular key, a [`VerificationReport`] looks up the signer in a set of certificates and
+//! tells apart why a signature is not acceptable: an unknown signer, a cryptographically bad
+//! signature, a signing key that was expired, revoked or not capable of signing when the
+//! signature was made, or a signature or key that the [`Policy`] rejects.
+//!
+//! For signed and encrypted messages, [`VerificationReport::check_intended_recipient`]
+//! additionally detects signatures that were made for someone else, and forwarded to the
+//! decrypting key by re-encrypting the message.
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    composed::{ComponentStatus, Revocation, SignedPublicKey},
+    crypto::hash::HashAlgorithm,
+    errors::{Error, Result},
+    packet::{KeyFlags, Notation, Signature, SignatureType},
+    policy::{Policy, PolicyViolation},
+    types::{Fingerprint, KeyDetails, PublicKeyTrait},
+};
+
+/// The component of a certificate that made a signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignerComponent {
+    /// The primary key.
+    Primary,
+    /// The subkey at this index in `public_subkeys`.
+    Subkey(usize),
+}
+
+/// The key that made (or claims to have made) a signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignerInfo {
+    /// The fingerprint of the certificate, i.e. of its primary key.
+    pub certificate: Fingerprint,
+    /// The fingerprint of the component key.
+    pub key: Fingerprint,
+    pub component: SignerComponent,
+}
+
+/// Why a signature is not acceptable.
+#[derive(Debug)]
+pub enum VerificationFailure<'a> {
+    /// None of the certificates contains the issuer of the signature.
+    UnknownSigner,
+    /// The signature does not verify against the issuer's key.
+    BadSignature(Error),
+    /// The signature has no creation time, so the validity of the key can't be determined.
+    MissingCreationTime,
+    /// The signing key had expired at the given time, before the signature was made.
+    KeyExpired(DateTime<Utc>),
+    /// The signing key has been revoked.
+    KeyRevoked(Revocation<'a>),
+    /// The signing key was not validly bound to the certificate when the signature was made,
+    /// see [`VerificationReport::key_validity`] for details.
+    KeyNotValid,
+    /// The signing key is not marked as capable of signing.
+    NotSigningCapable,
+    /// The signature, or the signing key, is rejected by the policy, e.g. because it uses a
+    /// hash algorithm that is no longer considered secure.
+    Policy(PolicyViolation),
+    /// The signature lists its intended recipients, and the decrypting key is not among them.
+    NotIntendedRecipient,
+}
+
+/// The outcome of verifying a single signature against a set of certificates.
+#[derive(Debug)]
+pub struct VerificationReport<'a> {
+    pub signature: &'a Signature,
+    /// The key that issued the signature, if it was found in the certificates.
+    pub signer: Option<SignerInfo>,
+    pub created: Option<DateTime<Utc>>,
+    pub typ: Option<SignatureType>,
+    pub hash_alg: Option<HashAlgorithm>,
+    /// The notations in the hashed area of the signature.
+    pub notations: Vec<&'a Notation>,
+    /// The primary key fingerprints of the recipients the signature was made for.
+    pub intended_recipients: Vec<&'a Fingerprint>,
+    /// The status of the signing key at the creation time of the signature.
+    ///
+    /// If the primary key is not valid, this is its status, otherwise the status of the
+    /// signing component.
+    pub key_validity: Option<ComponentStatus<'a>>,
+    /// `None` if the signature is valid.
+    pub failure: Option<VerificationFailure<'a>>,
+}
+
+impl<'a> VerificationReport<'a> {
+    /// Looks up the issuer of `signature` in `certs`, checks the signature with `verify` against
+    /// each matching component key, and evaluates the signer's certificate at the creation time
+    /// of the signature, under `policy`.
+    pub(crate) fn new<F>(
+        signature: &'a Signature,
+        certs: &[&'a SignedPublicKey],
+        policy: &dyn Policy,
+        verify: F,
+    ) -> Self
+    where
+        F: Fn(&dyn PublicKeyTrait) -> Result<()>,
+    {
+        let mut report = VerificationReport {
+            signature,
+            signer: None,
+            created: signature.created().copied(),
+            typ: signature.typ(),
+            hash_alg: signature.hash_alg(),
+            notations: signature.notations(),
+            intended_recipients: signature.intended_recipients(),
+            key_validity: None,
+            failure: Some(VerificationFailure::UnknownSigner),
+        };
+
+        for cert in certs {
+            let components = std::iter::once((
+                SignerComponent::Primary,
+                &cert.primary_key as &dyn PublicKeyTrait,
+            ))
+            .chain(
+                cert.public_subkeys
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| (SignerComponent::Subkey(i), &s.key as &dyn PublicKeyTrait)),
+            );
+            for (component, key) in components {
+                if !Signature::match_identity(signature, key) {
+                    continue;
+                }
+                report.signer = Some(SignerInfo {
+                    certificate: cert.primary_key.fingerprint(),
+                    key: key.fingerprint(),
+                    component,
+                });
+                match verify(key) {
+                    Ok(()) => {
+                        report.check_signer(cert, component, policy);
+                        return report;
+                    }
+                    Err(err) => report.failure = Some(VerificationFailure::BadSignature(err)),
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Returns true if the signature is valid.
+    pub fn is_valid(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    /// Checks that the signature was made for the recipient whose certificate has the primary
+    /// key fingerprint `recipient`, i.e. the certificate of the key that decrypted the message.
+    ///
+    /// Signatures that don't list any intended recipients are not affected. Otherwise, a valid
+    /// signature that does not list `recipient` fails with
+    /// [`VerificationFailure::NotIntendedRecipient`].
+    pub fn check_intended_recipient(&mut self, recipient: &Fingerprint) {
+        if self.failure.is_none()
+            && !self.intended_recipients.is_empty()
+            && !self.intended_recipients.contains(&recipient)
+        {
+            self.failure = Some(VerificationFailure::NotIntendedRecipient);
+        }
+    }
+
+    /// Checks that the signing component of `cert` was usable for signing when the
+    /// (cryptographically valid) signature was made.
+    fn check_signer(
+        &mut self,
+        cert: &'a SignedPublicKey,
+        component: SignerComponent,
+        policy: &dyn Policy,
+    ) {
+        let Some(created) = self.created else {
+            self.failure = Some(VerificationFailure::MissingCreationTime);
+            return;
+        };
+
+        let validity = cert.validity_at_with_policy(created, policy);
+        let (status, can_sign) = match component {
+            SignerComponent::Primary => {
+                // Keys without any key flags predate them, their primary key may sign.
+                let flags = validity.key_flags();
+                (
+                    validity.status().clone(),
+                    flags.sign() || flags == KeyFlags::default(),
+                )
+            }
+            SignerComponent::Subkey(i) => {
+                let subkey = &validity.subkeys()[i];
+                let status = if validity.is_valid() {
+                    subkey.status.clone()
+                } else {
+                    validity.status().clone()
+                };
+                (status, subkey.key_flags().sign())
+            }
+        };
+
+        self.failure = match &status {
+            ComponentStatus::Valid => None,
+            ComponentStatus::Expired(at) => Some(VerificationFailure::KeyExpired(*at)),
+            ComponentStatus::Revoked(revocation) => {
+                Some(VerificationFailure::KeyRevoked(revocation.clone()))
+            }
+            ComponentStatus::Rejected(violation) => {
+                Some(VerificationFailure::Policy(violation.clone()))
+            }
+            _ => Some(VerificationFailure::KeyNotValid),
+        };
+        self.key_validity = Some(status);
+
+        if self.failure.is_none() && !can_sign {
+            self.failure = Some(VerificationFailure::NotSigningCapable);
+        }
+        if self.failure.is_none() {
+            if let Err(violation) = policy.signature(self.signature) {
+                self.failure = Some(VerificationFailure::Policy(violation));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use chrono::{Duration, SubsecRound};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        composed::{CleartextSignedMessage, KeyType, Message, MessageBuilder, RecipientSelection},
+        crypto::{
+            aead::{AeadAlgorithm, ChunkSize},
+            sym::SymmetricKeyAlgorithm,
+        },
+        packet::{RevocationCode, SignatureConfig, SignatureVersion, Subpacket, SubpacketData},
+        policy::{NullPolicy, StandardPolicy},
+        types::{KeyVersion, Password, SecretKeyTrait},
+        util::test::{alice_key, alice_params, gen_key, generate, TestSubkey},
+    };
+
+    fn sign_text(
+        rng: &mut ChaCha8Rng,
+        text: &str,
+        key: &impl SecretKeyTrait,
+        hash_alg: HashAlgorithm,
+        created: DateTime<Utc>,
+    ) -> CleartextSignedMessage {
+        let mut config = SignatureConfig::from_key(&mut *rng, key, SignatureType::Text).unwrap();
+        config.hash_alg = hash_alg;
+        config.hashed_subpackets = vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(created)).unwrap(),
+            Subpacket::regular(SubpacketData::IssuerFingerprint(key.fingerprint())).unwrap(),
+            Subpacket::regular(SubpacketData::Notation(Notation {
+                readable: true,
+                name: "test@example.org".into(),
+                value: "value".into(),
+            }))
+            .unwrap(),
+        ];
+
+        CleartextSignedMessage::new(text, config, key, &Password::empty()).unwrap()
+    }
+
+    #[test]
+    fn cleartext_reports() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut params = alice_params(KeyVersion::V4);
+        params.can_sign(false);
+        let key = generate(&mut rng, &params, &Password::empty());
+        let public = key.signed_public_key();
+        let signing_key = &key.secret_subkeys[1].key;
+        let now = Utc::now().trunc_subsecs(0);
+
+        let msg = sign_text(&mut rng, "hello\n", signing_key, HashAlgorithm::Sha256, now);
+
+        let reports = msg.verify_reports(&[], &StandardPolicy::default());
+        assert_eq!(reports.len(), 1);
+        assert!(matches!(
+            reports[0].failure,
+            Some(VerificationFailure::UnknownSigner)
+        ));
+        assert!(reports[0].signer.is_none());
+
+        let reports = msg.verify_reports(&[&public], &StandardPolicy::default());
+        let report = &reports[0];
+        assert!(report.is_valid(), "{report:?}");
+        assert_eq!(
+            report.signer,
+            Some(SignerInfo {
+                certificate: public.primary_key.fingerprint(),
+                key: signing_key.fingerprint(),
+                component: SignerComponent::Subkey(1),
+            })
+        );
+        assert_eq!(report.created, Some(now));
+        assert_eq!(report.typ, Some(SignatureType::Text));
+        assert_eq!(report.hash_alg, Some(HashAlgorithm::Sha256));
+        assert_eq!(report.notations.len(), 1);
+        assert_eq!(report.notations[0].name, "test@example.org");
+        assert_eq!(report.key_validity, Some(ComponentStatus::Valid));
+
+        // modified text
+        let armored = msg.to_armored_string(Default::default()).unwrap();
+        let (modified, _) =
+            CleartextSignedMessage::from_string(&armored.replace("hello", "jello")).unwrap();
+        let reports = modified.verify_reports(&[&public], &StandardPolicy::default());
+        assert!(matches!(
+            reports[0].failure,
+            Some(VerificationFailure::BadSignature(_))
+        ));
+        assert_eq!(
+            reports[0].signer.as_ref().unwrap().component,
+            SignerComponent::Subkey(1)
+        );
+
+        // the primary key is only capable of certifying
+        let msg = sign_text(
+            &mut rng,
+            "hello\n",
+            &key.primary_key,
+            HashAlgorithm::Sha256,
+            now,
+        );
+        let reports = msg.verify_reports(&[&public], &StandardPolicy::default());
+        assert!(matches!(
+            reports[0].failure,
+            Some(VerificationFailure::NotSigningCapable)
+        ));
+
+        // made before the key existed
+        let msg = sign_text(
+            &mut rng,
+            "hello\n",
+            signing_key,
+            HashAlgorithm::Sha256,
+            now - Duration::days(2),
+        );
+        let reports = msg.verify_reports(&[&public], &StandardPolicy::default());
+        assert!(matches!(
+            reports[0].failure,
+            Some(VerificationFailure::KeyNotValid)
+        ));
+        assert_eq!(reports[0].key_validity, Some(ComponentStatus::NotYetValid));
+    }
+
+    #[test]
+    fn weak_hash() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        // EdDSA and ECDSA keys can't be used with SHA-1 at all
+        let key = gen_key(&mut rng, KeyVersion::V4, KeyType::Rsa(2048), &[]);
+        let public = key.signed_public_key();
+        let now = Utc::now().trunc_subsecs(0);
+
+        let msg = sign_text(
+            &mut rng,
+            "hello\n",
+            &key.primary_key,
+            HashAlgorithm::Sha1,
+            now,
+        );
+        let reports = msg.verify_reports(&[&public], &StandardPolicy::default());
+        assert!(matches!(
+            reports[0].failure,
+            Some(VerificationFailure::Policy(PolicyViolation::WeakHash {
+                hash_alg: HashAlgorithm::Sha1,
+                ..
+            }))
+        ));
+
+        // a policy can still accept it
+        let reports = msg.verify_reports(&[&public], &NullPolicy);
+        assert!(reports[0].is_valid());
+        assert_eq!(reports[0].key_validity, Some(ComponentStatus::Valid));
+    }
+
+    #[test]
+    fn revoked_key() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let key = alice_key(KeyVersion::V4, 0);
+        let signing_key = &key.secret_subkeys[1].key;
+        let now = Utc::now().trunc_subsecs(0);
+
+        let msg = sign_text(&mut rng, "hello\n", signing_key, HashAlgorithm::Sha256, now);
+
+        // key compromise invalidates earlier signatures, too
+        let mut config =
+            SignatureConfig::from_key(&mut rng, &key.primary_key, SignatureType::KeyRevocation)
+                .unwrap();
+        config.hashed_subpackets = vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(
+                now + Duration::hours(1),
+            ))
+            .unwrap(),
+            Subpacket::regular(SubpacketData::IssuerFingerprint(key.fingerprint())).unwrap(),
+            Subpacket::regular(SubpacketData::RevocationReason(
+                RevocationCode::KeyCompromised,
+                "stolen".into(),
+            ))
+            .unwrap(),
+        ];
+        let revocation = config
+            .sign_key(
+                &key.primary_key,
+                &Password::empty(),
+                key.primary_key.public_key(),
+            )
+            .unwrap();
+        let mut public = key.signed_public_key();
+        public.details.revocation_signatures.push(revocation);
+
+        let reports = msg.verify_reports(&[&public], &StandardPolicy::default());
+        let Some(VerificationFailure::KeyRevoked(revocation)) = &reports[0].failure else {
+            panic!("expected revocation: {:?}", reports[0]);
+        };
+        assert!(revocation.hard);
+        assert_eq!(revocation.code(), Some(RevocationCode::KeyCompromised));
+    }
+
+    #[test]
+    fn message_reports() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let key = alice_key(KeyVersion::V4, 0);
+        let other = alice_key(KeyVersion::V4, 1);
+        let public = key.signed_public_key();
+
+        let mut builder = MessageBuilder::from_bytes("", &b"hello world"[..]);
+        builder
+            .sign(
+                &key.secret_subkeys[1].key,
+                Password::empty(),
+                HashAlgorithm::Sha256,
+            )
+            .sign(
+                &other.secret_subkeys[1].key,
+                Password::empty(),
+                HashAlgorithm::Sha512,
+            );
+        let bytes = builder.to_vec(&mut rng).unwrap();
+
+        let mut msg = Message::from_bytes(&bytes[..]).unwrap();
+        msg.as_data_vec().unwrap();
+
+        let reports = msg
+            .verify_reports(&[&public], &StandardPolicy::default())
+            .unwrap();
+        assert_eq!(reports.len(), 2);
+        let (valid, unknown): (Vec<_>, Vec<_>) = reports.iter().partition(|r| r.is_valid());
+        assert_eq!(valid.len(), 1);
+        assert_eq!(valid[0].hash_alg, Some(HashAlgorithm::Sha256));
+        assert_eq!(
+            valid[0].signer.as_ref().unwrap().key,
+            key.secret_subkeys[1].key.fingerprint()
+        );
+        assert!(matches!(
+            unknown[0].failure,
+            Some(VerificationFailure::UnknownSigner)
+        ));
+    }
+
+    #[test]
+    fn intended_recipient() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let alice = alice_key(KeyVersion::V4, 0);
+        let bob = alice_key(KeyVersion::V4, 1);
+        let carol = alice_key(KeyVersion::V4, 2);
+        let alice_public = alice.signed_public_key();
+        let bob_public = bob.signed_public_key();
+
+        let recipients = RecipientSelection::select([&bob_public]).unwrap();
+        let mut builder = MessageBuilder::from_bytes("", &b"hello world"[..])
+            .seipd_v1(&mut rng, SymmetricKeyAlgorithm::AES128);
+        builder
+            .sign(
+                &alice.secret_subkeys[1].key,
+                Password::empty(),
+                HashAlgorithm::Sha256,
+            )
+            .encrypt_to_recipients(&mut rng, &recipients)
+            .unwrap();
+        let bytes = builder.to_vec(&mut rng).unwrap();
+
+        let msg = Message::from_bytes(&bytes[..]).unwrap();
+        let mut msg = msg.decrypt(&Password::empty(), &bob).unwrap();
+        msg.as_data_vec().unwrap();
+
+        let policy = StandardPolicy::default();
+        let reports = msg
+            .verify_reports_for_recipient(&[&alice_public], &policy, &bob.fingerprint())
+            .unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].is_valid(), "{:?}", reports[0].failure);
+        assert_eq!(reports[0].intended_recipients, vec![&bob.fingerprint()]);
+
+        // bob re-encrypts the signed message to carol
+        let mut reports = msg.verify_reports(&[&alice_public], &policy).unwrap();
+        assert!(reports[0].is_valid());
+        reports[0].check_intended_recipient(&carol.fingerprint());
+        assert!(matches!(
+            reports[0].failure,
+            Some(VerificationFailure::NotIntendedRecipient)
+        ));
+    }
+
+    #[test]
+    fn intended_recipient_mixed_versions() {
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let alice = alice_key(KeyVersion::V4, 0);
+        let bob = gen_key(
+            &mut rng,
+            KeyVersion::V6,
+            KeyType::Ed25519,
+            &[TestSubkey::Encrypt(KeyType::X25519)],
+        );
+        let alice_public = alice.signed_public_key();
+
+        // a v4 signature, carrying the fingerprint of a v6 recipient
+        let mut builder = MessageBuilder::from_bytes("", &b"hello world"[..]).seipd_v2(
+            &mut rng,
+            SymmetricKeyAlgorithm::AES128,
+            AeadAlgorithm::Ocb,
+            ChunkSize::default(),
+        );
+        builder
+            .sign(
+                &alice.secret_subkeys[1].key,
+                Password::empty(),
+                HashAlgorithm::Sha256,
+            )
+            .encrypt_to_key(&mut rng, &bob.secret_subkeys[0].public_key())
+            .unwrap()
+            .add_intended_recipient(bob.fingerprint());
+        let bytes = builder.to_vec(&mut rng).unwrap();
+
+        let msg = Message::from_bytes(&bytes[..]).unwrap();
+        let mut msg = msg.decrypt(&Password::empty(), &bob).unwrap();
+        msg.as_data_vec().unwrap();
+
+        let reports = msg
+            .verify_reports_for_recipient(
+                &[&alice_public],
+                &StandardPolicy::default(),
+                &bob.fingerprint(),
+            )
+            .unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].is_valid(), "{:?}", reports[0].failure);
+        assert_eq!(reports[0].signature.version(), SignatureVersion::V4);
+        assert_eq!(reports[0].intended_recipients, vec![&bob.fingerprint()]);
+        assert_eq!(
+            reports[0].intended_recipients[0].version(),
+            Some(KeyVersion::V6)
+        );
+    }
+}
@@ -103,10 +103,22 @@
 //!     .expect("Verify must succeed");
 //! ```
 
+mod add;
+mod expiration;
+mod export;
 mod key_parser;
+mod lint;
+mod merge;
 mod parse;
+mod protection;
 mod public;
+mod revocation;
 mod secret;
 mod shared;
+mod signing;
+mod validity;
 
-pub use self::{parse::*, public::*, secret::*, shared::*};
+pub use self::{
+    export::*, lint::*, parse::*, public::*, revocation::*, secret::*, shared::*, signing::*,
+    validity::*,
+};
@@ -0,0 +1,284 @@
+//! Binding new components to an existing key.
+
+use chrono::{SubsecRound, Utc};
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    composed::{
+        signed_key::{SignedPublicSubKey, SignedSecretKey},
+        SubkeyParams,
+    },
+    errors::{ensure, ensure_eq, Result},
+    packet::{
+        KeyFlags, PacketTrait, PubKeyInner, PublicSubkey, SignatureConfig, SignatureType,
+        Subpacket, SubpacketData, UserAttribute, UserId,
+    },
+    types::{KeyDetails, KeyVersion, Password, PublicKeyTrait, SignedUser, SignedUserAttribute},
+};
+
+impl SignedSecretKey {
+    /// Adds a new user ID, bound to the primary key with a self-signature.
+    ///
+    /// If the key has no direct key signature (the common case for v4 keys), the metadata of
+    /// the current primary binding (key flags, features, preferences and key expiration) is
+    /// carried over to the new self-signature.
+    pub fn add_user_id<R: CryptoRng + Rng>(
+        &mut self,
+        rng: R,
+        key_pw: &Password,
+        user_id: UserId,
+    ) -> Result<()> {
+        let config = self.certification_config(rng)?;
+        let sig = config.sign_certification(
+            &self.primary_key,
+            self.primary_key.public_key(),
+            key_pw,
+            user_id.tag(),
+            &user_id,
+        )?;
+
+        self.details.users.push(SignedUser::new(user_id, vec![sig]));
+
+        Ok(())
+    }
+
+    /// Adds a new user attribute, bound to the primary key with a self-signature.
+    ///
+    /// The self-signature is produced in the same way as for [`Self::add_user_id`].
+    pub fn add_user_attribute<R: CryptoRng + Rng>(
+        &mut self,
+        rng: R,
+        key_pw: &Password,
+        attr: UserAttribute,
+    ) -> Result<()> {
+        let config = self.certification_config(rng)?;
+        let sig = config.sign_certification(
+            &self.primary_key,
+            self.primary_key.public_key(),
+            key_pw,
+            attr.tag(),
+            &attr,
+        )?;
+
+        self.details
+            .user_attributes
+            .push(SignedUserAttribute::new(attr, vec![sig]));
+
+        Ok(())
+    }
+
+    /// Generates a new subkey from `params` and binds it to the primary key.
+    ///
+    /// Signing capable subkeys get an embedded primary key binding signature.
+    pub fn add_subkey<R: CryptoRng + Rng>(
+        &mut self,
+        mut rng: R,
+        key_pw: &Password,
+        params: SubkeyParams,
+    ) -> Result<()> {
+        let subkey = params.generate(&mut rng, self.primary_key.public_key())?;
+        let signed = subkey.sign(
+            &mut rng,
+            &self.primary_key,
+            self.primary_key.public_key(),
+            key_pw,
+        )?;
+
+        self.secret_subkeys.push(signed);
+
+        Ok(())
+    }
+
+    /// Binds a foreign encryption key as an "Additional Decryption Subkey" (ADSK).
+    ///
+    /// Senders that honor ADSKs encrypt to `adsk` in addition to the regular encryption
+    /// subkeys of this key, see [`crate::composed::RecipientSelection::include_adsks`].
+    /// As the secret key material of `adsk` is held elsewhere, it is added as a public subkey,
+    /// flagged only with [`KeyFlags::adsk`].
+    ///
+    /// See <https://www.gnupg.org/blog/20230321-adsk.html>
+    pub fn add_adsk<R: CryptoRng + Rng>(
+        &mut self,
+        rng: R,
+        key_pw: &Password,
+        adsk: &impl PublicKeyTrait,
+    ) -> Result<()> {
+        ensure!(
+            adsk.is_encryption_key(),
+            "{:?} key {} can not be used for encryption",
+            adsk.algorithm(),
+            adsk.fingerprint()
+        );
+        ensure_eq!(
+            adsk.version(),
+            self.version(),
+            "ADSK version must match the primary key"
+        );
+
+        let key = PublicSubkey::from_inner(PubKeyInner::new(
+            adsk.version(),
+            adsk.algorithm(),
+            *adsk.created_at(),
+            adsk.expiration(),
+            adsk.public_params().clone(),
+        )?)?;
+
+        let mut keyflags = KeyFlags::default();
+        keyflags.set_adsk(true);
+        let sig = key.sign(
+            rng,
+            &self.primary_key,
+            self.primary_key.public_key(),
+            key_pw,
+            keyflags,
+            None,
+        )?;
+
+        self.public_subkeys
+            .push(SignedPublicSubKey::new(key, vec![sig]));
+
+        Ok(())
+    }
+
+    fn certification_config<R: CryptoRng + Rng>(&self, rng: R) -> Result<SignatureConfig> {
+        let mut config =
+            SignatureConfig::from_key(rng, &self.primary_key, SignatureType::CertGeneric)?;
+
+        config.hashed_subpackets = vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(
+                Utc::now().trunc_subsecs(0),
+            ))?,
+            Subpacket::regular(SubpacketData::IssuerFingerprint(self.fingerprint()))?,
+        ];
+
+        // Without a direct key signature, the metadata lives on the user ID self-signatures.
+        let validity = self.validity();
+        if validity.direct_signature().is_none() {
+            if let Some(binding) = validity.binding().and_then(|sig| sig.config()) {
+                config.hashed_subpackets.extend(
+                    binding
+                        .hashed_subpackets()
+                        .filter(|p| {
+                            matches!(
+                                p.data,
+                                SubpacketData::KeyFlags(_)
+                                    | SubpacketData::Features(_)
+                                    | SubpacketData::KeyExpirationTime(_)
+                                    | SubpacketData::PreferredSymmetricAlgorithms(_)
+                                    | SubpacketData::PreferredHashAlgorithms(_)
+                                    | SubpacketData::PreferredCompressionAlgorithms(_)
+                                    | SubpacketData::PreferredAeadAlgorithms(_)
+                                    | SubpacketData::KeyServerPreferences(_)
+                                    | SubpacketData::PreferredKeyServer(_)
+                            )
+                        })
+                        .cloned(),
+                );
+            }
+        }
+
+        if self.version() <= KeyVersion::V4 {
+            config.unhashed_subpackets =
+                vec![Subpacket::regular(SubpacketData::Issuer(self.key_id()))?];
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use bytes::Bytes;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        composed::{KeyType, SubkeyParamsBuilder},
+        types::PacketHeaderVersion,
+        util::test::gen_key,
+    };
+
+    #[test]
+    fn add_user_id_and_attribute() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        for version in [KeyVersion::V4, KeyVersion::V6] {
+            let mut key = gen_key(&mut rng, version, KeyType::Ed25519, &[]);
+
+            let id =
+                UserId::from_str(PacketHeaderVersion::New, "Alice <alice@work.example>").unwrap();
+            key.add_user_id(&mut rng, &Password::empty(), id).unwrap();
+
+            let attr = UserAttribute::new_image(Bytes::from_static(b"not really a jpeg")).unwrap();
+            key.add_user_attribute(&mut rng, &Password::empty(), attr)
+                .unwrap();
+
+            key.verify().unwrap();
+
+            let validity = key.validity();
+            assert_eq!(validity.valid_users().count(), 2);
+            assert!(validity.user_attributes()[0].status.is_valid());
+            // the primary user is unchanged
+            assert_eq!(
+                validity.primary_user().unwrap().user.id.id(),
+                b"Alice <alice@example.org>"
+            );
+
+            if version == KeyVersion::V4 {
+                // metadata is carried over to the new binding
+                let binding = validity.users()[1].binding.unwrap();
+                assert!(binding.key_flags().sign());
+                assert!(binding.features().is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn add_subkeys() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        for version in [KeyVersion::V4, KeyVersion::V6] {
+            let mut key = gen_key(&mut rng, version, KeyType::Ed25519, &[]);
+
+            key.add_subkey(
+                &mut rng,
+                &Password::empty(),
+                SubkeyParamsBuilder::default()
+                    .version(version)
+                    .key_type(KeyType::X25519)
+                    .can_encrypt(true)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+            key.add_subkey(
+                &mut rng,
+                &Password::empty(),
+                SubkeyParamsBuilder::default()
+                    .version(version)
+                    .key_type(KeyType::Ed25519)
+                    .can_sign(true)
+                    .passphrase(Some("sub".into()))
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+
+            key.verify().unwrap();
+            assert_eq!(key.secret_subkeys.len(), 2);
+
+            let validity = key.validity();
+            assert_eq!(validity.valid_subkeys().count(), 2);
+
+            let signing = &validity.subkeys()[1];
+            assert!(signing.key_flags().sign());
+            let backsig = signing.binding.unwrap().embedded_signature().unwrap();
+            backsig
+                .verify_primary_key_binding(signing.key, key.primary_key.public_key())
+                .unwrap();
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Changing the expiration of an existing key.
+
+use chrono::{Duration, SubsecRound, Utc};
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    composed::signed_key::{ComponentStatus, SignedSecretKey},
+    errors::{bail, format_err, Result},
+    packet::{self, Signature, SignatureConfig, Subpacket, SubpacketData},
+    types::{Fingerprint, KeyDetails, Password, Tag},
+};
+
+impl SignedSecretKey {
+    /// Returns a copy of this key, with a new expiration for the primary key.
+    ///
+    /// `expiration` is relative to the creation time of the primary key, `None` means that the
+    /// key does not expire.
+    ///
+    /// This re-issues the current direct key signature (if any), and the current binding
+    /// signatures of all user IDs that are not revoked. All other subpackets (key flags,
+    /// features, preferences, ...) are carried over from the existing signatures.
+    /// The previous signatures are kept, they are superseded by the newer ones.
+    pub fn with_expiration<R: CryptoRng + Rng>(
+        &self,
+        mut rng: R,
+        key_pw: &Password,
+        expiration: Option<Duration>,
+    ) -> Result<Self> {
+        let validity = self.validity();
+
+        let mut direct_signature = None;
+        if let Some(dks) = validity.direct_signature() {
+            let config = reissue(&mut rng, &self.primary_key, dks, expiration)?;
+            direct_signature =
+                Some(config.sign_key(&self.primary_key, key_pw, self.primary_key.public_key())?);
+        }
+
+        let mut user_signatures = Vec::new();
+        for (i, user) in validity.users().iter().enumerate() {
+            let Some(binding) = user.binding else {
+                continue;
+            };
+            if matches!(user.status, ComponentStatus::Revoked(_)) {
+                continue;
+            }
+            // With a direct key signature, only user IDs that carry their own expiration need
+            // to be updated.
+            if direct_signature.is_some() && binding.key_expiration_time().is_none() {
+                continue;
+            }
+
+            let config = reissue(&mut rng, &self.primary_key, binding, expiration)?;
+            let sig = config.sign_certification(
+                &self.primary_key,
+                self.primary_key.public_key(),
+                key_pw,
+                Tag::UserId,
+                &user.user.id,
+            )?;
+            user_signatures.push((i, sig));
+        }
+
+        if direct_signature.is_none() && user_signatures.is_empty() {
+            bail!("no valid self-signature found to update");
+        }
+
+        let mut key = self.clone();
+        key.details.direct_signatures.extend(direct_signature);
+        for (i, sig) in user_signatures {
+            key.details.users[i].signatures.push(sig);
+        }
+
+        Ok(key)
+    }
+
+    /// Returns a copy of this key, with a new expiration for the subkey with the given
+    /// fingerprint.
+    ///
+    /// `expiration` is relative to the creation time of the subkey, `None` means that the
+    /// subkey does not expire.
+    ///
+    /// This re-issues the current binding signature of the subkey, carrying over all other
+    /// subpackets (including the key flags and an embedded primary key binding signature).
+    pub fn with_subkey_expiration<R: CryptoRng + Rng>(
+        &self,
+        mut rng: R,
+        key_pw: &Password,
+        subkey: &Fingerprint,
+        expiration: Option<Duration>,
+    ) -> Result<Self> {
+        let validity = self.validity();
+
+        let subkey_validity = validity
+            .subkeys()
+            .iter()
+            .find(|s| &s.key.fingerprint() == subkey)
+            .ok_or_else(|| format_err!("unknown subkey {}", subkey))?;
+        let Some(binding) = subkey_validity.binding else {
+            bail!("no valid binding signature found for subkey {}", subkey);
+        };
+
+        let config = reissue(&mut rng, &self.primary_key, binding, expiration)?;
+        let sig = config.sign_subkey_binding(
+            &self.primary_key,
+            self.primary_key.public_key(),
+            key_pw,
+            subkey_validity.key,
+        )?;
+
+        let mut key = self.clone();
+        if let Some(k) = key
+            .public_subkeys
+            .iter_mut()
+            .find(|k| &k.key.fingerprint() == subkey)
+        {
+            k.signatures.push(sig);
+        } else if let Some(k) = key
+            .secret_subkeys
+            .iter_mut()
+            .find(|k| &k.key.fingerprint() == subkey)
+        {
+            k.signatures.push(sig);
+        }
+
+        Ok(key)
+    }
+}
+
+/// Builds a new configuration for a signature of the same type as `sig`, with a fresh creation
+/// time and the given key expiration. All other subpackets are carried over.
+fn reissue<R: CryptoRng + Rng>(
+    rng: R,
+    key: &packet::SecretKey,
+    sig: &Signature,
+    expiration: Option<Duration>,
+) -> Result<SignatureConfig> {
+    let Some(old) = sig.config() else {
+        bail!("cannot re-issue unknown signature");
+    };
+
+    let mut config = SignatureConfig::from_key(rng, key, old.typ())?;
+
+    config.hashed_subpackets = vec![Subpacket::regular(SubpacketData::SignatureCreationTime(
+        Utc::now().trunc_subsecs(0),
+    ))?];
+    config.hashed_subpackets.extend(
+        old.hashed_subpackets()
+            .filter(|p| {
+                !matches!(
+                    p.data,
+                    SubpacketData::SignatureCreationTime(_) | SubpacketData::KeyExpirationTime(_)
+                )
+            })
+            .cloned(),
+    );
+    if let Some(expiration) = expiration {
+        config
+            .hashed_subpackets
+            .push(Subpacket::regular(SubpacketData::KeyExpirationTime(
+                expiration,
+            ))?);
+    }
+    config.unhashed_subpackets = old.unhashed_subpackets().cloned().collect();
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        types::{KeyVersion, PublicKeyTrait},
+        util::test::alice_key,
+    };
+
+    #[test]
+    fn change_primary_expiration() {
+        for version in [KeyVersion::V4, KeyVersion::V6] {
+            let key = alice_key(version, 0);
+            assert!(key.validity().expires_at().is_none());
+
+            let expiration = Duration::days(365);
+            let updated = key
+                .with_expiration(
+                    ChaCha8Rng::seed_from_u64(2),
+                    &Password::empty(),
+                    Some(expiration),
+                )
+                .unwrap();
+            updated.verify().unwrap();
+
+            let validity = updated.validity();
+            assert!(validity.is_valid());
+            let expected = *key.primary_key.public_key().created_at() + expiration;
+            assert_eq!(validity.expires_at(), Some(expected));
+            assert!(matches!(
+                updated
+                    .validity_at(expected + Duration::seconds(1))
+                    .status(),
+                ComponentStatus::Expired(_)
+            ));
+
+            // preferences and flags are carried over
+            let binding = validity.binding().unwrap();
+            let original = key.validity();
+            let original = original.binding().unwrap();
+            assert_eq!(binding.key_flags(), original.key_flags());
+            assert_eq!(binding.features(), original.features());
+            assert_eq!(
+                binding.preferred_symmetric_algs(),
+                original.preferred_symmetric_algs()
+            );
+
+            // and removing the expiration again
+            let updated = updated
+                .with_expiration(ChaCha8Rng::seed_from_u64(3), &Password::empty(), None)
+                .unwrap();
+            assert!(updated.validity().expires_at().is_none());
+        }
+    }
+
+    #[test]
+    fn change_subkey_expiration() {
+        for version in [KeyVersion::V4, KeyVersion::V6] {
+            let key = alice_key(version, 0);
+            // the signing subkey
+            let fp = key.secret_subkeys[1].key.fingerprint();
+
+            let expiration = Duration::days(30);
+            let updated = key
+                .with_subkey_expiration(
+                    ChaCha8Rng::seed_from_u64(2),
+                    &Password::empty(),
+                    &fp,
+                    Some(expiration),
+                )
+                .unwrap();
+            updated.verify().unwrap();
+
+            let validity = updated.validity();
+            let subkey = &validity.subkeys()[1];
+            assert!(subkey.status.is_valid());
+            assert_eq!(
+                subkey.expires_at(),
+                Some(*subkey.key.created_at() + expiration)
+            );
+            // the backsig is carried over
+            assert!(subkey.binding.unwrap().embedded_signature().is_some());
+            assert!(updated.validity().expires_at().is_none());
+        }
+    }
+}
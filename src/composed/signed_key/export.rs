@@ -0,0 +1,503 @@
+//! Reduction of keys for export, without re-signing.
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    composed::{
+        signed_key::{
+            CertificateValidity, ComponentStatus, SignedKeyDetails, SignedPublicKey,
+            SignedPublicSubKey, SignedSecretKey, SignedSecretSubKey,
+        },
+        store::normalize_email,
+    },
+    packet::{self, Signature, SignatureType},
+    types::{
+        EncryptedSecretParams, GnuStub, KeyDetails, SecretParams, SignedUser, SignedUserAttribute,
+    },
+};
+
+/// Options to reduce a key on export.
+///
+/// Signatures are only removed, never created, so the result can be exported without
+/// access to the secret key material.
+/// [`ExportOptions::clean`] and [`ExportOptions::minimal`] correspond to the
+/// `export-clean` and `export-minimal` options of GnuPG.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Drop certifications made by other keys.
+    pub drop_third_party: bool,
+    /// Only keep the authoritative self-signature of each component, and self-revocations.
+    pub drop_superseded: bool,
+    /// Drop subkeys that are expired or revoked.
+    pub drop_invalid_subkeys: bool,
+    pub drop_user_attributes: bool,
+    /// Only export user IDs that match one of these, either as a whole or by their email
+    /// address. All user IDs are exported if this is `None`.
+    pub user_ids: Option<Vec<String>>,
+    /// Replace the secret key material of the primary key with a GnuPG `gnu-dummy` stub,
+    /// like `gpg --export-secret-subkeys`. Only applies to secret keys.
+    pub secret_subkeys_only: bool,
+}
+
+impl ExportOptions {
+    /// Drops superseded self-signatures and invalid subkeys.
+    pub fn clean() -> Self {
+        ExportOptions {
+            drop_superseded: true,
+            drop_invalid_subkeys: true,
+            ..Default::default()
+        }
+    }
+
+    /// Additionally to [`Self::clean`], drops third-party certifications and user attributes.
+    pub fn minimal() -> Self {
+        ExportOptions {
+            drop_third_party: true,
+            drop_user_attributes: true,
+            ..Self::clean()
+        }
+    }
+
+    /// Restricts the export to the given user IDs or email addresses.
+    pub fn with_user_ids<S: Into<String>>(mut self, user_ids: impl IntoIterator<Item = S>) -> Self {
+        self.user_ids = Some(user_ids.into_iter().map(Into::into).collect());
+        self
+    }
+
+    fn includes_user_id(&self, user: &SignedUser) -> bool {
+        let Some(user_ids) = &self.user_ids else {
+            return true;
+        };
+        let Some(id) = user.id.as_str() else {
+            return false;
+        };
+        let email = normalize_email(id);
+
+        user_ids
+            .iter()
+            .any(|u| u == id || (email.is_some() && normalize_email(u) == email))
+    }
+}
+
+impl SignedPublicKey {
+    /// Returns a copy of this key, reduced according to `options`.
+    ///
+    /// See [`Self::export_at`].
+    pub fn export(&self, options: &ExportOptions) -> SignedPublicKey {
+        self.export_at(options, Utc::now())
+    }
+
+    /// Returns a copy of this key, reduced according to `options`, evaluating the validity
+    /// of its components at `reference_time`.
+    pub fn export_at(
+        &self,
+        options: &ExportOptions,
+        reference_time: DateTime<Utc>,
+    ) -> SignedPublicKey {
+        let validity = self.validity_at(reference_time);
+        let filter = Filter {
+            options,
+            validity: &validity,
+        };
+
+        let public_subkeys = self
+            .public_subkeys
+            .iter()
+            .enumerate()
+            .filter_map(|(i, subkey)| {
+                let signatures = filter.subkey(i, &subkey.signatures)?;
+                Some(SignedPublicSubKey {
+                    key: subkey.key.clone(),
+                    signatures,
+                })
+            })
+            .collect();
+
+        SignedPublicKey::new(
+            self.primary_key.clone(),
+            filter.details(&self.details),
+            public_subkeys,
+        )
+    }
+}
+
+impl SignedSecretKey {
+    /// Returns a copy of this key, reduced according to `options`.
+    ///
+    /// See [`Self::export_at`].
+    pub fn export(&self, options: &ExportOptions) -> SignedSecretKey {
+        self.export_at(options, Utc::now())
+    }
+
+    /// Returns a copy of this key, reduced according to `options`, evaluating the validity
+    /// of its components at `reference_time`.
+    pub fn export_at(
+        &self,
+        options: &ExportOptions,
+        reference_time: DateTime<Utc>,
+    ) -> SignedSecretKey {
+        let validity = self.validity_at(reference_time);
+        let filter = Filter {
+            options,
+            validity: &validity,
+        };
+
+        // The validity lists public subkeys first, then secret subkeys.
+        let public_subkeys = self
+            .public_subkeys
+            .iter()
+            .enumerate()
+            .filter_map(|(i, subkey)| {
+                let signatures = filter.subkey(i, &subkey.signatures)?;
+                Some(SignedPublicSubKey {
+                    key: subkey.key.clone(),
+                    signatures,
+                })
+            })
+            .collect();
+
+        let offset = self.public_subkeys.len();
+        let secret_subkeys = self
+            .secret_subkeys
+            .iter()
+            .enumerate()
+            .filter_map(|(i, subkey)| {
+                let signatures = filter.subkey(offset + i, &subkey.signatures)?;
+                Some(SignedSecretSubKey {
+                    key: subkey.key.clone(),
+                    signatures,
+                })
+            })
+            .collect();
+
+        let primary_key = if options.secret_subkeys_only {
+            let public_key = self.primary_key.public_key().clone();
+            let stub = EncryptedSecretParams::new_gnu_stub(GnuStub::Dummy, public_key.version());
+            packet::SecretKey::new(public_key, SecretParams::Encrypted(stub))
+                .expect("stub is smaller than the original secret key")
+        } else {
+            self.primary_key.clone()
+        };
+
+        SignedSecretKey::new(
+            primary_key,
+            filter.details(&self.details),
+            public_subkeys,
+            secret_subkeys,
+        )
+    }
+
+    /// Returns the public part of this key, reduced according to `options`.
+    ///
+    /// All secret key material is removed.
+    pub fn export_public(&self, options: &ExportOptions) -> SignedPublicKey {
+        self.export(options).signed_public_key()
+    }
+
+    /// Returns the public part of this key, reduced according to `options`, evaluating the
+    /// validity of its components at `reference_time`.
+    pub fn export_public_at(
+        &self,
+        options: &ExportOptions,
+        reference_time: DateTime<Utc>,
+    ) -> SignedPublicKey {
+        self.export_at(options, reference_time).signed_public_key()
+    }
+}
+
+struct Filter<'a> {
+    options: &'a ExportOptions,
+    validity: &'a CertificateValidity<'a>,
+}
+
+impl Filter<'_> {
+    fn details(&self, details: &SignedKeyDetails) -> SignedKeyDetails {
+        // Key revocations are always kept, including those by designated revokers.
+        let revocation_signatures = details.revocation_signatures.clone();
+        let direct_signatures =
+            self.signatures(&details.direct_signatures, self.validity.direct_signature());
+
+        let users = details
+            .users
+            .iter()
+            .zip(self.validity.users())
+            .filter(|(user, _)| self.options.includes_user_id(user))
+            .map(|(user, validity)| SignedUser {
+                id: user.id.clone(),
+                signatures: self.signatures(&user.signatures, validity.binding),
+            })
+            .collect();
+
+        let user_attributes = if self.options.drop_user_attributes {
+            Vec::new()
+        } else {
+            details
+                .user_attributes
+                .iter()
+                .zip(self.validity.user_attributes())
+                .map(|(attr, validity)| SignedUserAttribute {
+                    attr: attr.attr.clone(),
+                    signatures: self.signatures(&attr.signatures, validity.binding),
+                })
+                .collect()
+        };
+
+        // Components without any signatures left are dropped here.
+        SignedKeyDetails::new(
+            revocation_signatures,
+            direct_signatures,
+            users,
+            user_attributes,
+        )
+    }
+
+    /// Filters the signatures of the subkey at `index` in the validity, returns `None` if
+    /// the subkey should be dropped.
+    fn subkey(&self, index: usize, signatures: &[Signature]) -> Option<Vec<Signature>> {
+        let validity = self.validity.subkeys().get(index)?;
+        if self.options.drop_invalid_subkeys
+            && matches!(
+                validity.status,
+                ComponentStatus::Expired(_) | ComponentStatus::Revoked(_)
+            )
+        {
+            return None;
+        }
+
+        let signatures = self.signatures(signatures, validity.binding);
+        (!signatures.is_empty()).then_some(signatures)
+    }
+
+    /// Filters the signatures of a single component, with `binding` being its authoritative
+    /// self-signature, which must point into `signatures`.
+    fn signatures(&self, signatures: &[Signature], binding: Option<&Signature>) -> Vec<Signature> {
+        let primary = self.validity.primary_key();
+
+        // Without an authoritative binding, keep the newest self-signature.
+        let binding = binding.or_else(|| {
+            signatures
+                .iter()
+                .filter(|sig| is_self_signature(sig, primary) && !is_revocation(sig))
+                .max_by_key(|sig| sig.created().copied())
+        });
+
+        signatures
+            .iter()
+            .filter(|sig| {
+                if !is_self_signature(sig, primary) {
+                    return !self.options.drop_third_party;
+                }
+                !self.options.drop_superseded
+                    || is_revocation(sig)
+                    || binding.is_some_and(|b| std::ptr::eq(b, *sig))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Returns true if `sig` was issued by `primary`, according to its issuer subpackets.
+fn is_self_signature(sig: &Signature, primary: &packet::PublicKey) -> bool {
+    let fingerprints = sig.issuer_fingerprint();
+    if !fingerprints.is_empty() {
+        let fingerprint = primary.fingerprint();
+        return fingerprints.iter().any(|fp| **fp == fingerprint);
+    }
+
+    let key_id = primary.key_id();
+    sig.issuer().iter().any(|id| **id == key_id)
+}
+
+fn is_revocation(sig: &Signature) -> bool {
+    matches!(
+        sig.typ(),
+        Some(
+            SignatureType::KeyRevocation
+                | SignatureType::CertRevocation
+                | SignatureType::SubkeyRevocation
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use chrono::Duration;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        composed::{Deserializable, KeyType},
+        packet::SubpacketData,
+        packet::{RevocationCode, SignatureConfig, UserAttribute},
+        ser::Serialize,
+        types::{KeyVersion, Password, Tag},
+        util::test::{alice_key, gen_key},
+    };
+
+    /// The newer self-signature is created in the future, evaluate after it.
+    fn later() -> DateTime<Utc> {
+        Utc::now() + Duration::hours(1)
+    }
+
+    /// Alice's key, with a revoked subkey, a superseded self-signature, a third-party
+    /// certification and a user attribute.
+    fn alice() -> SignedSecretKey {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut key = alice_key(KeyVersion::V4, 0);
+        let pw = Password::empty();
+
+        let fp = key.secret_subkeys[1].key.fingerprint();
+        let revocation = key
+            .revoke_subkey(&mut rng, &pw, &fp, RevocationCode::KeyRetired, "")
+            .unwrap();
+        key.secret_subkeys[1].signatures.push(revocation);
+
+        // a newer self-signature on the first user ID
+        let user = key.details.users[0].clone();
+        let mut config =
+            SignatureConfig::from_key(&mut rng, &key.primary_key, SignatureType::CertPositive)
+                .unwrap();
+        config.hashed_subpackets = key.details.users[0].signatures[0]
+            .config()
+            .unwrap()
+            .hashed_subpackets
+            .clone();
+        for subpacket in &mut config.hashed_subpackets {
+            if let SubpacketData::SignatureCreationTime(t) = &mut subpacket.data {
+                *t += Duration::seconds(1);
+            }
+        }
+        let newer = config
+            .sign_certification(
+                &key.primary_key,
+                key.primary_key.public_key(),
+                &pw,
+                Tag::UserId,
+                &user.id,
+            )
+            .unwrap();
+        key.details.users[0].signatures.push(newer);
+
+        // a certification by Bob
+        let bob = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519, &[]);
+        let certification = user
+            .id
+            .sign_third_party(
+                &mut rng,
+                &bob.primary_key,
+                &pw,
+                key.primary_key.public_key(),
+            )
+            .unwrap();
+        key.details.users[0]
+            .signatures
+            .extend(certification.signatures);
+
+        let attr = UserAttribute::new_image(b"\xff\xd8\xff\xe0"[..].into()).unwrap();
+        key.add_user_attribute(&mut rng, &pw, attr).unwrap();
+
+        key
+    }
+
+    #[test]
+    fn export_minimal() {
+        let key = alice();
+
+        let all = key.export_at(&ExportOptions::default(), later());
+        assert_eq!(all, key);
+
+        let minimal = key.export_at(&ExportOptions::minimal(), later());
+        assert_eq!(minimal.details.users.len(), 2);
+        assert_eq!(minimal.details.users[0].signatures.len(), 1);
+        assert_eq!(
+            minimal.details.users[0].signatures[0],
+            key.details.users[0].signatures[1]
+        );
+        assert!(minimal.details.user_attributes.is_empty());
+        assert_eq!(minimal.secret_subkeys.len(), 1);
+        assert_eq!(
+            minimal.secret_subkeys[0].key.fingerprint(),
+            key.secret_subkeys[0].key.fingerprint()
+        );
+        minimal.verify().unwrap();
+
+        // clean keeps third-party certifications and user attributes
+        let clean = key.export_at(&ExportOptions::clean(), later());
+        assert_eq!(clean.details.users[0].signatures.len(), 2);
+        assert_eq!(
+            clean.details.users[0].signatures[1],
+            key.details.users[0].signatures[2]
+        );
+        assert_eq!(clean.details.user_attributes.len(), 1);
+        assert_eq!(clean.secret_subkeys.len(), 1);
+
+        // without dropping invalid subkeys, the revocation is kept
+        let options = ExportOptions {
+            drop_superseded: true,
+            ..Default::default()
+        };
+        let revoked = &key.export_at(&options, later()).secret_subkeys[1];
+        assert_eq!(revoked.signatures, key.secret_subkeys[1].signatures);
+    }
+
+    #[test]
+    fn export_user_ids() {
+        let key = alice();
+
+        let options = ExportOptions::minimal().with_user_ids(["ALICE@work.example"]);
+        let public = key.export_public_at(&options, later());
+        assert_eq!(public.details.users.len(), 1);
+        assert_eq!(
+            public.details.users[0].id.id(),
+            b"Alice <alice@work.example>"
+        );
+        assert_eq!(public.public_subkeys.len(), 1);
+        public.verify().unwrap();
+
+        let options = ExportOptions::default().with_user_ids(["Alice <alice@example.org>"]);
+        let public = key.export_public_at(&options, later());
+        assert_eq!(public.details.users.len(), 1);
+        assert_eq!(public.details.users[0].signatures.len(), 3);
+        assert_eq!(public.public_subkeys.len(), 2);
+        assert_eq!(public, key.signed_public_key().export_at(&options, later()));
+
+        let options = ExportOptions::default().with_user_ids(["nobody@example.org"]);
+        assert!(key
+            .export_public_at(&options, later())
+            .details
+            .users
+            .is_empty());
+    }
+
+    #[test]
+    fn export_secret_subkeys_only() {
+        let key = alice_key(KeyVersion::V4, 0);
+
+        let options = ExportOptions {
+            secret_subkeys_only: true,
+            ..Default::default()
+        };
+        let exported = key.export_at(&options, later());
+        exported.verify().unwrap();
+
+        assert_eq!(
+            exported.primary_key.public_key(),
+            key.primary_key.public_key()
+        );
+        assert_eq!(
+            exported.primary_key.secret_params().gnu_stub(),
+            Some(&GnuStub::Dummy)
+        );
+        assert!(exported
+            .primary_key
+            .unlock(&Password::empty(), |_, _| Ok(()))
+            .is_err());
+        assert_eq!(exported.secret_subkeys, key.secret_subkeys);
+
+        let bytes = exported.to_bytes().unwrap();
+        assert_eq!(SignedSecretKey::from_bytes(&bytes[..]).unwrap(), exported);
+    }
+}
@@ -0,0 +1,688 @@
+//! Checking keys for outdated self-signatures and weak key material, and repairing them.
+
+use chrono::{DateTime, SubsecRound, Utc};
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    composed::signed_key::{
+        CertificateValidity, ComponentStatus, SignedPublicKey, SignedSecretKey,
+    },
+    crypto::{hash::HashAlgorithm, public_key::PublicKeyAlgorithm},
+    errors::{bail, format_err, Result},
+    packet::{self, Features, Signature, SignatureConfig, SignatureType, Subpacket, SubpacketData},
+    policy::{Policy, PolicyViolation, StandardPolicy},
+    types::{
+        KeyDetails, KeyVersion, Password, PublicKeyTrait, SignedUser, SignedUserAttribute, Tag,
+    },
+};
+
+/// An issue found by [`SignedPublicKey::lint`] or [`SignedSecretKey::lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint<'a> {
+    /// The component the issue was found on.
+    pub component: LintComponent<'a>,
+    /// The offending signature, if the issue is with a signature.
+    pub signature: Option<&'a Signature>,
+    pub issue: LintIssue,
+}
+
+/// A component of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintComponent<'a> {
+    PrimaryKey(&'a packet::PublicKey),
+    User(&'a SignedUser),
+    UserAttribute(&'a SignedUserAttribute),
+    /// Secret subkeys are reported via their public part.
+    Subkey(&'a packet::PublicSubkey),
+}
+
+/// The kinds of issues reported by the linter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintIssue {
+    /// A self-signature or back signature uses a hash algorithm that is not collision resistant.
+    WeakHash(HashAlgorithm),
+    /// A signing-capable subkey is bound without a valid primary key binding ("back")
+    /// signature.
+    MissingBacksig,
+    /// The self-signatures of the primary key don't advertise any features.
+    MissingFeatures,
+    /// The key material is too small.
+    WeakKey {
+        algorithm: PublicKeyAlgorithm,
+        bits: usize,
+    },
+}
+
+impl LintIssue {
+    /// Returns true if [`SignedSecretKey::repair`] can fix this issue.
+    pub fn is_repairable(&self) -> bool {
+        !matches!(self, LintIssue::WeakKey { .. })
+    }
+}
+
+impl SignedPublicKey {
+    /// Checks the authoritative self-signatures and the key material of this key.
+    ///
+    /// See [`Self::lint_at`].
+    pub fn lint(&self) -> Vec<Lint<'_>> {
+        self.lint_at(Utc::now())
+    }
+
+    /// Checks the self-signatures that are authoritative at `reference_time`, and the key
+    /// material of all components that are not revoked.
+    ///
+    /// Superseded signatures and third-party certifications are not checked.
+    /// Key material is checked against the [`StandardPolicy`], see [`Self::lint_with_policy`].
+    pub fn lint_at(&self, reference_time: DateTime<Utc>) -> Vec<Lint<'_>> {
+        self.lint_with_policy(reference_time, &StandardPolicy::default())
+    }
+
+    /// Like [`Self::lint_at`], but checks the key material against `policy`.
+    ///
+    /// The policy is not used to select the authoritative self-signatures, otherwise weak
+    /// ones could not be reported.
+    pub fn lint_with_policy(
+        &self,
+        reference_time: DateTime<Utc>,
+        policy: &dyn Policy,
+    ) -> Vec<Lint<'_>> {
+        lint(
+            &self.validity_at(reference_time),
+            self.public_subkeys.iter().map(|s| s.signatures.as_slice()),
+            policy,
+        )
+    }
+}
+
+impl SignedSecretKey {
+    /// Checks the authoritative self-signatures and the key material of this key.
+    ///
+    /// See [`SignedPublicKey::lint_at`].
+    pub fn lint(&self) -> Vec<Lint<'_>> {
+        self.lint_at(Utc::now())
+    }
+
+    /// Checks the self-signatures that are authoritative at `reference_time`, and the key
+    /// material of all components that are not revoked.
+    ///
+    /// See [`SignedPublicKey::lint_at`].
+    pub fn lint_at(&self, reference_time: DateTime<Utc>) -> Vec<Lint<'_>> {
+        self.lint_with_policy(reference_time, &StandardPolicy::default())
+    }
+
+    /// Like [`Self::lint_at`], but checks the key material against `policy`.
+    ///
+    /// See [`SignedPublicKey::lint_with_policy`].
+    pub fn lint_with_policy(
+        &self,
+        reference_time: DateTime<Utc>,
+        policy: &dyn Policy,
+    ) -> Vec<Lint<'_>> {
+        lint(
+            &self.validity_at(reference_time),
+            self.public_subkeys
+                .iter()
+                .map(|s| s.signatures.as_slice())
+                .chain(self.secret_subkeys.iter().map(|s| s.signatures.as_slice())),
+            policy,
+        )
+    }
+
+    /// Returns a copy of this key, with all repairable issues reported by [`Self::lint`]
+    /// fixed.
+    ///
+    /// The affected self-signatures are re-issued with the preferred hash algorithm of the
+    /// primary key and a fresh creation time, all other subpackets are carried over.
+    /// Missing features are added, and missing or weak back signatures are re-created.
+    /// This requires the secret key material of the subkey, which is expected to be
+    /// protected by `key_pw` as well.
+    ///
+    /// The previous signatures are kept, they are superseded by the newer ones.
+    /// Issues that can't be fixed by re-issuing a self-signature, such as weak key material,
+    /// are left in place.
+    pub fn repair<R: CryptoRng + Rng>(&self, mut rng: R, key_pw: &Password) -> Result<Self> {
+        let validity = self.validity();
+        let lints = self.lint();
+
+        let mut repairs: Vec<Repair<'_>> = Vec::new();
+        for lint in &lints {
+            let Some(signature) = lint.signature else {
+                continue;
+            };
+
+            let (target, binding) = self.repair_target(&validity, lint, signature)?;
+
+            let repair = match repairs.iter_mut().find(|r| r.target == target) {
+                Some(repair) => repair,
+                None => {
+                    repairs.push(Repair {
+                        target,
+                        binding,
+                        hash: false,
+                        features: false,
+                        backsig: false,
+                    });
+                    repairs.last_mut().expect("just pushed")
+                }
+            };
+            match lint.issue {
+                LintIssue::MissingFeatures => repair.features = true,
+                LintIssue::MissingBacksig => repair.backsig = true,
+                LintIssue::WeakHash(_) if signature.typ() == Some(SignatureType::KeyBinding) => {
+                    repair.backsig = true
+                }
+                LintIssue::WeakHash(_) => repair.hash = true,
+                LintIssue::WeakKey { .. } => {}
+            }
+        }
+
+        let mut key = self.clone();
+        for repair in repairs {
+            match repair.target {
+                Target::DirectKey => {
+                    let config = reissue(&mut rng, &self.primary_key, repair.binding, repair)?;
+                    let sig = config.sign_key(
+                        &self.primary_key,
+                        key_pw,
+                        self.primary_key.public_key(),
+                    )?;
+                    key.details.direct_signatures.push(sig);
+                }
+                Target::User(i) => {
+                    let config = reissue(&mut rng, &self.primary_key, repair.binding, repair)?;
+                    let sig = config.sign_certification(
+                        &self.primary_key,
+                        self.primary_key.public_key(),
+                        key_pw,
+                        Tag::UserId,
+                        &self.details.users[i].id,
+                    )?;
+                    key.details.users[i].signatures.push(sig);
+                }
+                Target::UserAttribute(i) => {
+                    let config = reissue(&mut rng, &self.primary_key, repair.binding, repair)?;
+                    let sig = config.sign_certification(
+                        &self.primary_key,
+                        self.primary_key.public_key(),
+                        key_pw,
+                        Tag::UserAttribute,
+                        &self.details.user_attributes[i].attr,
+                    )?;
+                    key.details.user_attributes[i].signatures.push(sig);
+                }
+                Target::Subkey(i) => {
+                    let subkey = validity.subkeys()[i].key;
+                    let secret = i
+                        .checked_sub(self.public_subkeys.len())
+                        .map(|i| &self.secret_subkeys[i].key);
+
+                    let mut config = reissue(&mut rng, &self.primary_key, repair.binding, repair)?;
+                    if repair.backsig {
+                        match secret {
+                            Some(secret) => {
+                                let backsig = secret.sign_primary_key_binding(
+                                    &mut rng,
+                                    self.primary_key.public_key(),
+                                    key_pw,
+                                )?;
+                                config.hashed_subpackets.retain(|p| {
+                                    !matches!(p.data, SubpacketData::EmbeddedSignature(_))
+                                });
+                                config.hashed_subpackets.push(Subpacket::regular(
+                                    SubpacketData::EmbeddedSignature(Box::new(backsig)),
+                                )?);
+                            }
+                            // Without the secret subkey, the back signature can't be re-created.
+                            None if repair.hash => {}
+                            None => continue,
+                        }
+                    }
+
+                    let sig = config.sign_subkey_binding(
+                        &self.primary_key,
+                        self.primary_key.public_key(),
+                        key_pw,
+                        subkey,
+                    )?;
+                    match i.checked_sub(self.public_subkeys.len()) {
+                        Some(i) => key.secret_subkeys[i].signatures.push(sig),
+                        None => key.public_subkeys[i].signatures.push(sig),
+                    }
+                }
+            }
+        }
+
+        Ok(key)
+    }
+
+    /// Finds where the re-issued signature for `lint` belongs, and which self-signature to
+    /// re-issue.
+    fn repair_target<'a>(
+        &self,
+        validity: &CertificateValidity<'a>,
+        lint: &Lint<'_>,
+        signature: &'a Signature,
+    ) -> Result<(Target, &'a Signature)> {
+        let target = match lint.component {
+            LintComponent::PrimaryKey(_) => {
+                if self
+                    .details
+                    .direct_signatures
+                    .iter()
+                    .any(|sig| std::ptr::eq(sig, signature))
+                {
+                    Target::DirectKey
+                } else if let Some(i) = self
+                    .details
+                    .users
+                    .iter()
+                    .position(|u| u.signatures.iter().any(|sig| std::ptr::eq(sig, signature)))
+                {
+                    Target::User(i)
+                } else {
+                    bail!("unknown signature {:?}", signature);
+                }
+            }
+            LintComponent::User(user) => validity
+                .users()
+                .iter()
+                .position(|u| std::ptr::eq(u.user, user))
+                .map(Target::User)
+                .ok_or_else(|| format_err!("unknown user {:?}", user.id))?,
+            LintComponent::UserAttribute(attr) => validity
+                .user_attributes()
+                .iter()
+                .position(|a| std::ptr::eq(a.attr, attr))
+                .map(Target::UserAttribute)
+                .ok_or_else(|| format_err!("unknown user attribute"))?,
+            LintComponent::Subkey(key) => {
+                let i = validity
+                    .subkeys()
+                    .iter()
+                    .position(|s| std::ptr::eq(s.key, key))
+                    .ok_or_else(|| format_err!("unknown subkey {}", key.fingerprint()))?;
+
+                // Issues with back signatures are fixed by re-issuing the binding.
+                if let Some(binding) = validity.subkeys()[i].binding {
+                    if signature.typ() == Some(SignatureType::KeyBinding) {
+                        return Ok((Target::Subkey(i), binding));
+                    }
+                }
+                Target::Subkey(i)
+            }
+        };
+
+        Ok((target, signature))
+    }
+}
+
+/// Where a re-issued signature belongs, indices are as in [`CertificateValidity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    DirectKey,
+    User(usize),
+    UserAttribute(usize),
+    Subkey(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Repair<'a> {
+    target: Target,
+    /// The self-signature to re-issue.
+    binding: &'a Signature,
+    /// The self-signature itself is weak.
+    hash: bool,
+    /// Add the features subpacket.
+    features: bool,
+    /// Re-create the back signature.
+    backsig: bool,
+}
+
+fn lint<'a>(
+    validity: &CertificateValidity<'a>,
+    subkey_signatures: impl Iterator<Item = &'a [Signature]>,
+    policy: &dyn Policy,
+) -> Vec<Lint<'a>> {
+    let mut lints = Vec::new();
+
+    if matches!(validity.status(), ComponentStatus::Revoked(_)) {
+        return lints;
+    }
+
+    let primary_key = validity.primary_key();
+    if let Some(issue) = weak_key(primary_key, policy) {
+        lints.push(Lint {
+            component: LintComponent::PrimaryKey(primary_key),
+            signature: None,
+            issue,
+        });
+    }
+
+    if let Some(dks) = validity.direct_signature() {
+        if let Some(issue) = weak_hash(dks) {
+            lints.push(Lint {
+                component: LintComponent::PrimaryKey(primary_key),
+                signature: Some(dks),
+                issue,
+            });
+        }
+    }
+
+    if let Some(binding) = validity.binding() {
+        if validity
+            .preference_signatures()
+            .iter()
+            .all(|sig| sig.features().is_none())
+        {
+            lints.push(Lint {
+                component: LintComponent::PrimaryKey(primary_key),
+                signature: Some(binding),
+                issue: LintIssue::MissingFeatures,
+            });
+        }
+    }
+
+    for user in validity.users() {
+        if let (Some(binding), false) = (
+            user.binding,
+            matches!(user.status, ComponentStatus::Revoked(_)),
+        ) {
+            if let Some(issue) = weak_hash(binding) {
+                lints.push(Lint {
+                    component: LintComponent::User(user.user),
+                    signature: Some(binding),
+                    issue,
+                });
+            }
+        }
+    }
+
+    for attr in validity.user_attributes() {
+        if let (Some(binding), false) = (
+            attr.binding,
+            matches!(attr.status, ComponentStatus::Revoked(_)),
+        ) {
+            if let Some(issue) = weak_hash(binding) {
+                lints.push(Lint {
+                    component: LintComponent::UserAttribute(attr.attr),
+                    signature: Some(binding),
+                    issue,
+                });
+            }
+        }
+    }
+
+    for (subkey, signatures) in validity.subkeys().iter().zip(subkey_signatures) {
+        let component = LintComponent::Subkey(subkey.key);
+        match subkey.status {
+            ComponentStatus::Revoked(_) => continue,
+            ComponentStatus::MissingBacksig => {
+                // The newest binding that is only rejected for its missing back signature.
+                let binding = signatures
+                    .iter()
+                    .filter(|sig| sig.typ() == Some(SignatureType::SubkeyBinding))
+                    .filter(|sig| {
+                        sig.created()
+                            .is_some_and(|c| c <= validity.reference_time())
+                    })
+                    .filter(|sig| sig.verify_subkey_binding(primary_key, subkey.key).is_ok())
+                    .max_by_key(|sig| sig.created().copied());
+                lints.push(Lint {
+                    component: component.clone(),
+                    signature: binding,
+                    issue: LintIssue::MissingBacksig,
+                });
+            }
+            _ => {}
+        }
+
+        if let Some(issue) = weak_key(subkey.key, policy) {
+            lints.push(Lint {
+                component: component.clone(),
+                signature: None,
+                issue,
+            });
+        }
+
+        let Some(binding) = subkey.binding else {
+            continue;
+        };
+        if let Some(issue) = weak_hash(binding) {
+            lints.push(Lint {
+                component: component.clone(),
+                signature: Some(binding),
+                issue,
+            });
+        }
+        if let Some(backsig) = binding.embedded_signature() {
+            if let Some(issue) = weak_hash(backsig) {
+                lints.push(Lint {
+                    component,
+                    signature: Some(backsig),
+                    issue,
+                });
+            }
+        }
+    }
+
+    lints
+}
+
+fn weak_hash(sig: &Signature) -> Option<LintIssue> {
+    match sig.hash_alg()? {
+        alg @ (HashAlgorithm::Md5 | HashAlgorithm::Sha1 | HashAlgorithm::Ripemd160) => {
+            Some(LintIssue::WeakHash(alg))
+        }
+        _ => None,
+    }
+}
+
+fn weak_key(key: &dyn PublicKeyTrait, policy: &dyn Policy) -> Option<LintIssue> {
+    match policy.public_key(key) {
+        Err(PolicyViolation::WeakKey { algorithm, bits }) => {
+            Some(LintIssue::WeakKey { algorithm, bits })
+        }
+        _ => None,
+    }
+}
+
+/// Builds a new configuration for a signature of the same type as `sig`, with a fresh creation
+/// time and the preferred hash algorithm of `key`. All other subpackets are carried over.
+fn reissue<R: CryptoRng + Rng>(
+    rng: R,
+    key: &packet::SecretKey,
+    sig: &Signature,
+    repair: Repair<'_>,
+) -> Result<SignatureConfig> {
+    let Some(old) = sig.config() else {
+        bail!("cannot re-issue unknown signature");
+    };
+
+    let mut config = SignatureConfig::from_key(rng, key, old.typ())?;
+
+    config.hashed_subpackets = vec![Subpacket::regular(SubpacketData::SignatureCreationTime(
+        Utc::now().trunc_subsecs(0),
+    ))?];
+    config.hashed_subpackets.extend(
+        old.hashed_subpackets()
+            .filter(|p| !matches!(p.data, SubpacketData::SignatureCreationTime(_)))
+            .cloned(),
+    );
+    if repair.features && sig.features().is_none() {
+        let mut features = Features::new();
+        features.set_seipd_v1(true);
+        if key.version() == KeyVersion::V6 {
+            features.set_seipd_v2(true);
+        }
+        config
+            .hashed_subpackets
+            .push(Subpacket::regular(SubpacketData::Features(features))?);
+    }
+    config.unhashed_subpackets = old.unhashed_subpackets().cloned().collect();
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use chrono::Duration;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        composed::{Deserializable, KeyType, SecretKeyParamsBuilder, SubkeyParamsBuilder},
+        policy::NullPolicy,
+    };
+
+    #[test]
+    fn lint_legacy_key() {
+        let (key, _) =
+            SignedPublicKey::from_armor_file("./tests/openpgp/bug1223-good.asc").unwrap();
+        let lints = key.lint();
+
+        assert!(lints.contains(&Lint {
+            component: LintComponent::PrimaryKey(&key.primary_key),
+            signature: None,
+            issue: LintIssue::WeakKey {
+                algorithm: PublicKeyAlgorithm::RSA,
+                bits: 1024
+            },
+        }));
+        assert!(lints
+            .iter()
+            .any(|l| matches!(l.component, LintComponent::User(_))
+                && l.issue == LintIssue::WeakHash(HashAlgorithm::Sha1)));
+
+        // the key size threshold is taken from the policy
+        let lints = key.lint_with_policy(Utc::now(), &NullPolicy);
+        assert!(!lints
+            .iter()
+            .any(|l| matches!(l.issue, LintIssue::WeakKey { .. })));
+    }
+
+    /// A key as produced by older implementations: SHA-1 self-signatures without features, and
+    /// a signing subkey without a back signature.
+    fn legacy_key() -> SignedSecretKey {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let created = Utc::now().trunc_subsecs(0) - Duration::days(1);
+
+        let key = SecretKeyParamsBuilder::default()
+            .key_type(KeyType::Rsa(2048))
+            .can_certify(true)
+            .can_sign(true)
+            .created_at(created)
+            .primary_user_id("Alice <alice@example.org>".into())
+            .subkey(
+                SubkeyParamsBuilder::default()
+                    .key_type(KeyType::Ed25519Legacy)
+                    .can_sign(true)
+                    .created_at(created)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+            .generate(&mut rng)
+            .unwrap()
+            .sign(&mut rng, &Password::empty())
+            .unwrap();
+
+        let sha1 = |typ, sig: &Signature, drop: fn(&SubpacketData) -> bool| {
+            let mut config = SignatureConfig::v4(typ, key.algorithm(), HashAlgorithm::Sha1);
+            config.hashed_subpackets = sig
+                .config()
+                .unwrap()
+                .hashed_subpackets()
+                .filter(|p| !drop(&p.data))
+                .cloned()
+                .collect();
+            config.unhashed_subpackets = sig
+                .config()
+                .unwrap()
+                .unhashed_subpackets()
+                .cloned()
+                .collect();
+            config
+        };
+
+        let mut legacy = key.clone();
+        let user = &key.details.users[0];
+        legacy.details.users[0].signatures =
+            vec![sha1(SignatureType::CertPositive, &user.signatures[0], |p| {
+                matches!(p, SubpacketData::Features(_))
+            })
+            .sign_certification(
+                &key.primary_key,
+                key.primary_key.public_key(),
+                &Password::empty(),
+                Tag::UserId,
+                &user.id,
+            )
+            .unwrap()];
+
+        let subkey = &key.secret_subkeys[0];
+        legacy.secret_subkeys[0].signatures =
+            vec![
+                sha1(SignatureType::SubkeyBinding, &subkey.signatures[0], |p| {
+                    matches!(p, SubpacketData::EmbeddedSignature(_))
+                })
+                .sign_subkey_binding(
+                    &key.primary_key,
+                    key.primary_key.public_key(),
+                    &Password::empty(),
+                    subkey.key.public_key(),
+                )
+                .unwrap(),
+            ];
+
+        legacy
+    }
+
+    #[test]
+    fn lint_and_repair() {
+        let key = legacy_key();
+        assert!(key.verify().is_ok());
+
+        let lints = key.lint();
+        let issues: Vec<_> = lints.iter().map(|l| &l.issue).collect();
+        assert_eq!(
+            issues,
+            [
+                &LintIssue::MissingFeatures,
+                &LintIssue::WeakHash(HashAlgorithm::Sha1),
+                &LintIssue::MissingBacksig,
+            ]
+        );
+        assert_eq!(
+            lints[2].component,
+            LintComponent::Subkey(key.secret_subkeys[0].key.public_key())
+        );
+        assert_eq!(
+            lints[2].signature,
+            Some(&key.secret_subkeys[0].signatures[0])
+        );
+
+        let repaired = key
+            .repair(ChaCha8Rng::seed_from_u64(1), &Password::empty())
+            .unwrap();
+        repaired.verify().unwrap();
+        assert_eq!(repaired.lint(), Vec::new());
+
+        let validity = repaired.validity();
+        let binding = validity.binding().unwrap();
+        assert_eq!(binding.hash_alg(), Some(HashAlgorithm::Sha256));
+        assert!(binding.features().unwrap().seipd_v1());
+        assert!(binding.is_primary());
+
+        let subkey = &validity.subkeys()[0];
+        assert!(subkey.status.is_valid());
+        assert!(subkey.key_flags().sign());
+
+        // the superseded signatures are kept
+        assert_eq!(repaired.details.users[0].signatures.len(), 2);
+        assert_eq!(repaired.secret_subkeys[0].signatures.len(), 2);
+    }
+}
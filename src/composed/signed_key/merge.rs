@@ -0,0 +1,327 @@
+//! Merging of two copies of the same certificate.
+//!
+//! This implements the usual keyring update semantics: components are unioned by identity,
+//! signatures are deduplicated byte-for-byte, and secret key material is retained.
+//!
+//! Merging does not verify any signatures. Use [`SignedPublicKey::validity`] or
+//! [`SignedPublicKey::verify`] on the result to determine which components are actually bound.
+
+use std::collections::HashSet;
+
+use crate::{
+    composed::signed_key::{
+        SignedKeyDetails, SignedPublicKey, SignedPublicSubKey, SignedSecretKey, SignedSecretSubKey,
+    },
+    errors::{ensure_eq, Result},
+    packet::Signature,
+    ser::Serialize,
+    types::KeyDetails,
+};
+
+impl SignedKeyDetails {
+    /// Merges the components and signatures of `other` into `self`.
+    ///
+    /// User IDs are matched by their raw value, user attributes by their serialized content.
+    /// Signatures that are already present (byte-for-byte) are skipped.
+    pub fn merge(&mut self, other: SignedKeyDetails) -> Result<()> {
+        let SignedKeyDetails {
+            revocation_signatures,
+            direct_signatures,
+            users,
+            user_attributes,
+        } = other;
+
+        merge_signatures(&mut self.revocation_signatures, revocation_signatures)?;
+        merge_signatures(&mut self.direct_signatures, direct_signatures)?;
+
+        for user in users {
+            match self.users.iter_mut().find(|u| u.id.id() == user.id.id()) {
+                Some(existing) => merge_signatures(&mut existing.signatures, user.signatures)?,
+                None => self.users.push(user),
+            }
+        }
+
+        for attr in user_attributes {
+            let content = attr.attr.to_bytes()?;
+            let mut existing = None;
+            for a in self.user_attributes.iter_mut() {
+                if a.attr.to_bytes()? == content {
+                    existing = Some(a);
+                    break;
+                }
+            }
+
+            match existing {
+                Some(existing) => merge_signatures(&mut existing.signatures, attr.signatures)?,
+                None => self.user_attributes.push(attr),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SignedPublicKey {
+    /// Merges another copy of the same certificate into this one.
+    ///
+    /// Users, user attributes and subkeys are unioned by identity, signatures are deduplicated.
+    /// Fails if `other` has a different primary key.
+    pub fn merge(mut self, other: SignedPublicKey) -> Result<Self> {
+        ensure_eq!(
+            self.fingerprint(),
+            other.fingerprint(),
+            "cannot merge different certificates"
+        );
+
+        self.details.merge(other.details)?;
+        for subkey in other.public_subkeys {
+            merge_public_subkey(&mut self.public_subkeys, subkey)?;
+        }
+
+        Ok(self)
+    }
+}
+
+impl SignedSecretKey {
+    /// Merges another copy of the same key into this one.
+    ///
+    /// Users, user attributes and subkeys are unioned by identity, signatures are deduplicated.
+    /// Secret key material is kept: subkeys that are only public here, but secret in `other`
+    /// are upgraded to secret subkeys.
+    /// Fails if `other` has a different primary key.
+    pub fn merge(mut self, other: SignedSecretKey) -> Result<Self> {
+        ensure_eq!(
+            self.fingerprint(),
+            other.fingerprint(),
+            "cannot merge different keys"
+        );
+
+        self.details.merge(other.details)?;
+        for subkey in other.secret_subkeys {
+            self.merge_secret_subkey(subkey)?;
+        }
+        for subkey in other.public_subkeys {
+            self.merge_public_subkey(subkey)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Merges an updated copy of the public certificate into this key.
+    ///
+    /// This is typically used to apply a certificate refreshed from a keyserver to a local
+    /// secret key. All secret key material is kept.
+    /// Fails if `other` has a different primary key.
+    pub fn merge_public(mut self, other: SignedPublicKey) -> Result<Self> {
+        ensure_eq!(
+            self.fingerprint(),
+            other.fingerprint(),
+            "cannot merge different keys"
+        );
+
+        self.details.merge(other.details)?;
+        for subkey in other.public_subkeys {
+            self.merge_public_subkey(subkey)?;
+        }
+
+        Ok(self)
+    }
+
+    fn merge_secret_subkey(&mut self, subkey: SignedSecretSubKey) -> Result<()> {
+        let fingerprint = subkey.key.fingerprint();
+
+        if let Some(existing) = self
+            .secret_subkeys
+            .iter_mut()
+            .find(|k| k.key.fingerprint() == fingerprint)
+        {
+            return merge_signatures(&mut existing.signatures, subkey.signatures);
+        }
+
+        match self
+            .public_subkeys
+            .iter()
+            .position(|k| k.key.fingerprint() == fingerprint)
+        {
+            Some(pos) => {
+                // we learned the secret part of a subkey that we only had as a public key so far
+                let public = self.public_subkeys.remove(pos);
+                let mut signatures = public.signatures;
+                merge_signatures(&mut signatures, subkey.signatures)?;
+                self.secret_subkeys
+                    .push(SignedSecretSubKey::new(subkey.key, signatures));
+            }
+            None => self.secret_subkeys.push(subkey),
+        }
+
+        Ok(())
+    }
+
+    fn merge_public_subkey(&mut self, subkey: SignedPublicSubKey) -> Result<()> {
+        let fingerprint = subkey.key.fingerprint();
+
+        match self
+            .secret_subkeys
+            .iter_mut()
+            .find(|k| k.key.fingerprint() == fingerprint)
+        {
+            Some(existing) => merge_signatures(&mut existing.signatures, subkey.signatures),
+            None => merge_public_subkey(&mut self.public_subkeys, subkey),
+        }
+    }
+}
+
+fn merge_public_subkey(
+    subkeys: &mut Vec<SignedPublicSubKey>,
+    subkey: SignedPublicSubKey,
+) -> Result<()> {
+    let fingerprint = subkey.key.fingerprint();
+
+    match subkeys
+        .iter_mut()
+        .find(|k| k.key.fingerprint() == fingerprint)
+    {
+        Some(existing) => merge_signatures(&mut existing.signatures, subkey.signatures),
+        None => {
+            subkeys.push(subkey);
+            Ok(())
+        }
+    }
+}
+
+/// Appends all signatures from `other` to `signatures`, that are not already contained in it.
+fn merge_signatures(signatures: &mut Vec<Signature>, other: Vec<Signature>) -> Result<()> {
+    let mut seen = signatures
+        .iter()
+        .map(Serialize::to_bytes)
+        .collect::<Result<HashSet<_>>>()?;
+
+    for sig in other {
+        if seen.insert(sig.to_bytes()?) {
+            signatures.push(sig);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::{
+        packet::UserId,
+        types::{KeyVersion, PacketHeaderVersion, Password},
+        util::test::alice_key,
+    };
+
+    #[test]
+    fn merge_public_union() {
+        for version in [KeyVersion::V4, KeyVersion::V6] {
+            let full = alice_key(version, 0).signed_public_key();
+
+            // merging with itself changes nothing
+            let merged = full.clone().merge(full.clone()).unwrap();
+            assert_eq!(merged, full);
+
+            // two partial copies add up to the full certificate
+            let mut a = full.clone();
+            a.details.users.truncate(1);
+            a.public_subkeys.truncate(1);
+
+            let mut b = full.clone();
+            b.details.users.remove(0);
+            b.public_subkeys.remove(0);
+
+            let merged = a.merge(b).unwrap();
+            assert_eq!(merged.details.users.len(), 2);
+            assert_eq!(merged.public_subkeys.len(), 2);
+            for (user, orig) in merged.details.users.iter().zip(&full.details.users) {
+                assert_eq!(user.id, orig.id);
+                assert_eq!(user.signatures, orig.signatures);
+            }
+            merged.verify().unwrap();
+        }
+    }
+
+    #[test]
+    fn merge_new_signatures() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let key = alice_key(KeyVersion::V4, 0);
+        let public = key.signed_public_key();
+
+        // a third party certification on an existing user id
+        let other = alice_key(KeyVersion::V4, 1);
+        let certified = public.details.users[0]
+            .id
+            .sign_third_party(
+                &mut rng,
+                &other.primary_key,
+                &Password::empty(),
+                &public.primary_key,
+            )
+            .unwrap();
+
+        // and a new user id
+        let new_user = UserId::from_str(PacketHeaderVersion::New, "Alice <alice@new.example>")
+            .unwrap()
+            .sign(
+                &mut rng,
+                &key.primary_key,
+                key.primary_key.public_key(),
+                &Password::empty(),
+            )
+            .unwrap();
+
+        let mut update = public.clone();
+        update.details.users[0]
+            .signatures
+            .extend(certified.signatures.clone());
+        update.details.users.push(new_user.clone());
+
+        let merged = public.clone().merge(update.clone()).unwrap();
+        assert_eq!(merged.details.users.len(), 3);
+        assert_eq!(merged.details.users[0].signatures.len(), 2);
+        assert_eq!(merged.details.users[2], new_user);
+
+        // merging again is a no-op
+        let again = merged.clone().merge(update).unwrap();
+        assert_eq!(again, merged);
+    }
+
+    #[test]
+    fn merge_different_keys() {
+        let a = alice_key(KeyVersion::V4, 0);
+        let b = alice_key(KeyVersion::V4, 1);
+
+        assert!(a.signed_public_key().merge(b.signed_public_key()).is_err());
+        assert!(a.clone().merge(b.clone()).is_err());
+        assert!(a.merge_public(b.signed_public_key()).is_err());
+    }
+
+    #[test]
+    fn merge_secret_keeps_secrets() {
+        let key = alice_key(KeyVersion::V6, 0);
+
+        // a secret key that only has the public part of its encryption subkey
+        let mut stripped = key.clone();
+        let subkey = stripped.secret_subkeys.remove(0);
+        stripped.public_subkeys.push(subkey.signed_public_key());
+
+        let merged = stripped.clone().merge(key.clone()).unwrap();
+        assert!(merged.public_subkeys.is_empty());
+        assert_eq!(merged.secret_subkeys.len(), 2);
+        merged.verify().unwrap();
+
+        // merging the public certificate into the secret key keeps all secrets
+        let merged = key.clone().merge_public(key.signed_public_key()).unwrap();
+        assert_eq!(merged, key);
+
+        let merged = stripped.merge_public(key.signed_public_key()).unwrap();
+        assert_eq!(merged.public_subkeys.len(), 1);
+        assert_eq!(merged.secret_subkeys.len(), 1);
+    }
+}
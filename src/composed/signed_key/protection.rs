@@ -0,0 +1,206 @@
+//! Changing the password protection of all secret key material of a key at once.
+
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    composed::SignedSecretKey,
+    errors::Result,
+    types::{KeyDetails, KeyVersion, Password, S2kParams},
+};
+
+/// Re-protects the secret key material of a single secret key packet, leaving GnuPG stubs
+/// untouched. Works on both primary keys and subkeys, which share these methods.
+macro_rules! reprotect {
+    ($key:expr, $old_pw:expr, $new_pw:expr, $s2k_params:expr) => {
+        if $key.secret_params().gnu_stub().is_none() {
+            $key.remove_password($old_pw)?;
+            $key.set_password_with_s2k($new_pw, $s2k_params($key.version()))?;
+            $key.unlock($new_pw, |_, _| Ok(()))??;
+        }
+    };
+}
+
+impl SignedSecretKey {
+    /// Changes the password of the primary key and all secret subkeys, protecting them with
+    /// the default mechanism for their version (see [`S2kParams::new_default`]).
+    ///
+    /// See [`Self::change_password_with_s2k`].
+    pub fn change_password<R: Rng + CryptoRng>(
+        &mut self,
+        mut rng: R,
+        old_pw: &Password,
+        new_pw: &Password,
+    ) -> Result<()> {
+        self.change_password_with_s2k(old_pw, new_pw, |version| {
+            S2kParams::new_default(&mut rng, version)
+        })
+    }
+
+    /// Changes the password of the primary key and all secret subkeys.
+    ///
+    /// `s2k_params` is called once per component with its key version, and must return fresh
+    /// parameters (salt, IV or nonce) each time. This can be used to upgrade the protection,
+    /// e.g. from CFB with a SHA-1 checksum to AEAD with Argon2.
+    ///
+    /// Components that are currently unprotected are protected with `new_pw`, components
+    /// without secret key material (GnuPG stubs) are skipped.
+    ///
+    /// The key is only modified if every component can be unlocked with `old_pw`, and again
+    /// with `new_pw` after it has been re-protected.
+    pub fn change_password_with_s2k<F>(
+        &mut self,
+        old_pw: &Password,
+        new_pw: &Password,
+        mut s2k_params: F,
+    ) -> Result<()>
+    where
+        F: FnMut(KeyVersion) -> S2kParams,
+    {
+        let mut key = self.clone();
+
+        reprotect!(key.primary_key, old_pw, new_pw, s2k_params);
+        for subkey in &mut key.secret_subkeys {
+            reprotect!(subkey.key, old_pw, new_pw, s2k_params);
+        }
+
+        *self = key;
+        Ok(())
+    }
+
+    /// Removes the password protection of the primary key and all secret subkeys.
+    ///
+    /// The key is only modified if every component can be unlocked with `password`.
+    pub fn remove_password(&mut self, password: &Password) -> Result<()> {
+        let mut key = self.clone();
+
+        if key.primary_key.secret_params().gnu_stub().is_none() {
+            key.primary_key.remove_password(password)?;
+        }
+        for subkey in &mut key.secret_subkeys {
+            if subkey.key.secret_params().gnu_stub().is_none() {
+                subkey.key.remove_password(password)?;
+            }
+        }
+
+        *self = key;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        crypto::{aead::AeadAlgorithm, sym::SymmetricKeyAlgorithm},
+        types::{SecretParams, StringToKey},
+        util::test::alice_key,
+    };
+
+    fn s2k_params(key: &SignedSecretKey) -> Vec<&S2kParams> {
+        std::iter::once(key.primary_key.secret_params())
+            .chain(key.secret_subkeys.iter().map(|s| s.key.secret_params()))
+            .map(|params| match params {
+                SecretParams::Encrypted(params) => params.string_to_key_params(),
+                SecretParams::Plain(_) => panic!("unprotected secret key"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn change_password() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut key = alice_key(KeyVersion::V4, 0);
+        key.change_password(&mut rng, &Password::empty(), &"old".into())
+            .unwrap();
+        let old = key.clone();
+
+        // a wrong password leaves the key unchanged
+        assert!(key
+            .change_password(&mut rng, &"wrong".into(), &"new".into())
+            .is_err());
+        assert_eq!(key, old);
+
+        // upgrade to AEAD and Argon2, with cheap parameters
+        key.change_password_with_s2k(&"old".into(), &"new".into(), |_| S2kParams::Aead {
+            sym_alg: SymmetricKeyAlgorithm::AES256,
+            aead_mode: AeadAlgorithm::Ocb,
+            s2k: StringToKey::Argon2 {
+                salt: rng.gen(),
+                t: 1,
+                p: 1,
+                m_enc: 8,
+            },
+            nonce: rng.gen::<[u8; 15]>().to_vec().into(),
+        })
+        .unwrap();
+        key.verify().unwrap();
+        assert_eq!(key.public_key(), old.public_key());
+
+        let params = s2k_params(&key);
+        assert_eq!(params.len(), 3);
+        for p in &params {
+            let S2kParams::Aead {
+                sym_alg,
+                aead_mode,
+                s2k,
+                ..
+            } = p
+            else {
+                panic!("unexpected {p:?}");
+            };
+            assert_eq!(*sym_alg, SymmetricKeyAlgorithm::AES256);
+            assert_eq!(*aead_mode, AeadAlgorithm::Ocb);
+            assert!(matches!(s2k, StringToKey::Argon2 { .. }));
+        }
+        // every component got its own salt and nonce
+        assert_ne!(params[0], params[1]);
+        assert_ne!(params[1], params[2]);
+
+        assert!(key
+            .primary_key
+            .unlock(&"old".into(), |_, _| Ok(()))
+            .is_err());
+        for subkey in &key.secret_subkeys {
+            subkey
+                .key
+                .unlock(&"new".into(), |_, _| Ok(()))
+                .unwrap()
+                .unwrap();
+        }
+
+        key.remove_password(&"new".into()).unwrap();
+        assert!(!key.primary_key.secret_params().is_encrypted());
+        assert!(key
+            .secret_subkeys
+            .iter()
+            .all(|s| !s.key.secret_params().is_encrypted()));
+    }
+
+    #[test]
+    fn change_password_invalid_s2k() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut key = alice_key(KeyVersion::V4, 0);
+        key.change_password(&mut rng, &Password::empty(), &"old".into())
+            .unwrap();
+        let old = key.clone();
+
+        // Argon2 is only allowed with AEAD, the components can't be unlocked afterwards
+        let res = key.change_password_with_s2k(&"old".into(), &"new".into(), |_| S2kParams::Cfb {
+            sym_alg: SymmetricKeyAlgorithm::AES256,
+            s2k: StringToKey::Argon2 {
+                salt: [0; 16],
+                t: 1,
+                p: 1,
+                m_enc: 8,
+            },
+            iv: vec![0; 16].into(),
+        });
+        assert!(res.is_err());
+        assert_eq!(key, old);
+    }
+}
@@ -0,0 +1,398 @@
+//! Creation of revocation signatures and standalone revocation certificates.
+
+use std::io;
+
+use bytes::Bytes;
+use chrono::{SubsecRound, Utc};
+use rand::{CryptoRng, Rng};
+
+use crate::{
+    armor,
+    composed::{
+        signed_key::{SignedKeyDetails, SignedPublicKey, SignedPublicKeyParser, SignedSecretKey},
+        ArmorOptions, Deserializable,
+    },
+    errors::{bail, ensure, format_err, Result},
+    packet::{
+        self, Packet, PacketTrait, RevocationCode, Signature, SignatureConfig, SignatureType,
+        Subpacket, SubpacketData, UserAttribute, UserId,
+    },
+    ser::Serialize,
+    types::{Fingerprint, KeyDetails, KeyVersion, Password},
+};
+
+impl SignedSecretKey {
+    /// Creates a revocation signature for the primary key.
+    ///
+    /// The resulting signature can be added to `details.revocation_signatures`, or exported
+    /// via [`SignedSecretKey::revocation_certificate`].
+    pub fn revoke<R: CryptoRng + Rng>(
+        &self,
+        rng: R,
+        key_pw: &Password,
+        code: RevocationCode,
+        reason: &str,
+    ) -> Result<Signature> {
+        let config = self.revocation_config(rng, SignatureType::KeyRevocation, code, reason)?;
+
+        config.sign_key(&self.primary_key, key_pw, self.primary_key.public_key())
+    }
+
+    /// Creates a standalone revocation certificate for the primary key.
+    ///
+    /// This is intended to be generated ahead of time and stored safely, so that the key can
+    /// be revoked even if the secret key material or its passphrase is lost.
+    pub fn revocation_certificate<R: CryptoRng + Rng>(
+        &self,
+        rng: R,
+        key_pw: &Password,
+        code: RevocationCode,
+        reason: &str,
+    ) -> Result<RevocationCertificate> {
+        let signature = self.revoke(rng, key_pw, code, reason)?;
+
+        Ok(RevocationCertificate {
+            primary_key: self.primary_key.public_key().clone(),
+            signature,
+        })
+    }
+
+    /// Creates a revocation signature for the subkey with the given fingerprint.
+    ///
+    /// The resulting signature belongs into the `signatures` of that subkey.
+    pub fn revoke_subkey<R: CryptoRng + Rng>(
+        &self,
+        rng: R,
+        key_pw: &Password,
+        subkey: &Fingerprint,
+        code: RevocationCode,
+        reason: &str,
+    ) -> Result<Signature> {
+        let signee = self
+            .public_subkeys
+            .iter()
+            .map(|k| &k.key)
+            .chain(self.secret_subkeys.iter().map(|k| k.key.public_key()))
+            .find(|k| &k.fingerprint() == subkey)
+            .ok_or_else(|| format_err!("unknown subkey {}", subkey))?;
+
+        let config = self.revocation_config(rng, SignatureType::SubkeyRevocation, code, reason)?;
+
+        config.sign_subkey_binding(
+            &self.primary_key,
+            self.primary_key.public_key(),
+            key_pw,
+            signee,
+        )
+    }
+
+    /// Creates a certification revocation signature for the given user ID.
+    ///
+    /// The resulting signature belongs into the `signatures` of that user.
+    pub fn revoke_user_id<R: CryptoRng + Rng>(
+        &self,
+        rng: R,
+        key_pw: &Password,
+        user_id: &UserId,
+        code: RevocationCode,
+        reason: &str,
+    ) -> Result<Signature> {
+        ensure!(
+            self.details.users.iter().any(|u| u.id.id() == user_id.id()),
+            "unknown user id"
+        );
+
+        let config = self.revocation_config(rng, SignatureType::CertRevocation, code, reason)?;
+
+        config.sign_certification(
+            &self.primary_key,
+            self.primary_key.public_key(),
+            key_pw,
+            user_id.tag(),
+            user_id,
+        )
+    }
+
+    /// Creates a certification revocation signature for the given user attribute.
+    ///
+    /// The resulting signature belongs into the `signatures` of that user attribute.
+    pub fn revoke_user_attribute<R: CryptoRng + Rng>(
+        &self,
+        rng: R,
+        key_pw: &Password,
+        attr: &UserAttribute,
+        code: RevocationCode,
+        reason: &str,
+    ) -> Result<Signature> {
+        let content = attr.to_bytes()?;
+        let mut known = false;
+        for a in &self.details.user_attributes {
+            if a.attr.to_bytes()? == content {
+                known = true;
+                break;
+            }
+        }
+        ensure!(known, "unknown user attribute");
+
+        let config = self.revocation_config(rng, SignatureType::CertRevocation, code, reason)?;
+
+        config.sign_certification(
+            &self.primary_key,
+            self.primary_key.public_key(),
+            key_pw,
+            attr.tag(),
+            attr,
+        )
+    }
+
+    fn revocation_config<R: CryptoRng + Rng>(
+        &self,
+        rng: R,
+        typ: SignatureType,
+        code: RevocationCode,
+        reason: &str,
+    ) -> Result<SignatureConfig> {
+        let mut config = SignatureConfig::from_key(rng, &self.primary_key, typ)?;
+
+        config.hashed_subpackets = vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(
+                Utc::now().trunc_subsecs(0),
+            ))?,
+            Subpacket::regular(SubpacketData::IssuerFingerprint(self.fingerprint()))?,
+            Subpacket::regular(SubpacketData::RevocationReason(
+                code,
+                Bytes::copy_from_slice(reason.as_bytes()),
+            ))?,
+        ];
+        if self.version() <= KeyVersion::V4 {
+            config.unhashed_subpackets =
+                vec![Subpacket::regular(SubpacketData::Issuer(self.key_id()))?];
+        }
+
+        Ok(config)
+    }
+}
+
+/// A standalone revocation certificate for a primary key.
+///
+/// It is serialized as the primary key packet, followed by the key revocation signature,
+/// and armored as a public key block.
+/// Because it is a (minimal) transferable public key, it can be applied to a stored
+/// certificate with [`SignedPublicKey::merge`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevocationCertificate {
+    primary_key: packet::PublicKey,
+    signature: Signature,
+}
+
+impl RevocationCertificate {
+    /// The primary key that is revoked by this certificate.
+    pub fn primary_key(&self) -> &packet::PublicKey {
+        &self.primary_key
+    }
+
+    /// The key revocation signature.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// Verifies the revocation signature against the primary key.
+    pub fn verify(&self) -> Result<()> {
+        self.signature.verify_key(&self.primary_key)
+    }
+
+    pub fn to_armored_writer(
+        &self,
+        writer: &mut impl io::Write,
+        opts: ArmorOptions<'_>,
+    ) -> Result<()> {
+        armor::write(
+            self,
+            armor::BlockType::PublicKey,
+            writer,
+            opts.headers,
+            opts.include_checksum,
+        )
+    }
+
+    pub fn to_armored_bytes(&self, opts: ArmorOptions<'_>) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+
+        self.to_armored_writer(&mut buf, opts)?;
+
+        Ok(buf)
+    }
+
+    pub fn to_armored_string(&self, opts: ArmorOptions<'_>) -> Result<String> {
+        let res = String::from_utf8(self.to_armored_bytes(opts)?).map_err(|e| e.utf8_error())?;
+        Ok(res)
+    }
+}
+
+impl From<RevocationCertificate> for SignedPublicKey {
+    fn from(value: RevocationCertificate) -> Self {
+        SignedPublicKey::new(
+            value.primary_key,
+            SignedKeyDetails::new(vec![value.signature], vec![], vec![], vec![]),
+            vec![],
+        )
+    }
+}
+
+impl TryFrom<SignedPublicKey> for RevocationCertificate {
+    type Error = crate::errors::Error;
+
+    fn try_from(value: SignedPublicKey) -> Result<Self> {
+        let Some(signature) = value.details.revocation_signatures.into_iter().next() else {
+            bail!("missing key revocation signature");
+        };
+
+        Ok(RevocationCertificate {
+            primary_key: value.primary_key,
+            signature,
+        })
+    }
+}
+
+impl Serialize for RevocationCertificate {
+    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        self.primary_key.to_writer_with_header(writer)?;
+        self.signature.to_writer_with_header(writer)?;
+
+        Ok(())
+    }
+
+    fn write_len(&self) -> usize {
+        self.primary_key.write_len_with_header() + self.signature.write_len_with_header()
+    }
+}
+
+impl Deserializable for RevocationCertificate {
+    /// Parse a revocation certificate from packets.
+    fn from_packets<'a, I: Iterator<Item = Result<Packet>> + 'a>(
+        packets: std::iter::Peekable<I>,
+    ) -> Box<dyn Iterator<Item = Result<Self>> + 'a> {
+        Box::new(
+            SignedPublicKeyParser::from_packets(packets)
+                .map(|key| key.and_then(RevocationCertificate::try_from)),
+        )
+    }
+
+    fn matches_block_type(typ: armor::BlockType) -> bool {
+        matches!(typ, armor::BlockType::PublicKey | armor::BlockType::File)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use chrono::Duration;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{composed::ComponentStatus, types::PacketHeaderVersion, util::test::alice_key};
+
+    #[test]
+    fn revoke_components() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        for version in [KeyVersion::V4, KeyVersion::V6] {
+            let mut key = alice_key(version, 0);
+            let pw = Password::empty();
+
+            let user = key.details.users[1].id.clone();
+            let sig = key
+                .revoke_user_id(&mut rng, &pw, &user, RevocationCode::CertUserIdInvalid, "")
+                .unwrap();
+            assert_eq!(sig.typ(), Some(SignatureType::CertRevocation));
+            key.details.users[1].signatures.push(sig);
+
+            let subkey = key.secret_subkeys[0].key.fingerprint();
+            let sig = key
+                .revoke_subkey(
+                    &mut rng,
+                    &pw,
+                    &subkey,
+                    RevocationCode::KeyRetired,
+                    "retired",
+                )
+                .unwrap();
+            assert_eq!(sig.typ(), Some(SignatureType::SubkeyRevocation));
+            assert_eq!(sig.revocation_reason_string().unwrap(), &b"retired"[..]);
+            key.secret_subkeys[0].signatures.push(sig);
+
+            let sig = key
+                .revoke(&mut rng, &pw, RevocationCode::KeyCompromised, "leaked")
+                .unwrap();
+            assert_eq!(sig.typ(), Some(SignatureType::KeyRevocation));
+            key.details.revocation_signatures.push(sig);
+
+            key.verify().unwrap();
+
+            let at = Utc::now() + Duration::hours(1);
+            let validity = key.validity_at(at);
+            assert!(matches!(validity.status(), ComponentStatus::Revoked(r) if r.hard));
+            assert!(matches!(
+                validity.users()[1].status,
+                ComponentStatus::Revoked(_)
+            ));
+            assert!(matches!(
+                validity.subkeys()[0].status,
+                ComponentStatus::Revoked(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn revoke_unknown_components() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let key = alice_key(KeyVersion::V4, 0);
+        let pw = Password::empty();
+
+        let user =
+            UserId::from_str(PacketHeaderVersion::New, "Mallory <mallory@example.org>").unwrap();
+        assert!(key
+            .revoke_user_id(&mut rng, &pw, &user, RevocationCode::NoReason, "")
+            .is_err());
+
+        let other = alice_key(KeyVersion::V6, 0);
+        assert!(key
+            .revoke_subkey(
+                &mut rng,
+                &pw,
+                &other.fingerprint(),
+                RevocationCode::NoReason,
+                ""
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn revocation_certificate_roundtrip() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        for version in [KeyVersion::V4, KeyVersion::V6] {
+            let key = alice_key(version, 0);
+            let cert = key
+                .revocation_certificate(&mut rng, &Password::empty(), RevocationCode::NoReason, "")
+                .unwrap();
+            cert.verify().unwrap();
+
+            let armored = cert.to_armored_string(ArmorOptions::default()).unwrap();
+            assert!(armored.starts_with("-----BEGIN PGP PUBLIC KEY BLOCK-----"));
+
+            let (parsed, _) = RevocationCertificate::from_string(&armored).unwrap();
+            assert_eq!(parsed, cert);
+
+            // applying the certificate revokes the key
+            let public = key
+                .signed_public_key()
+                .merge(SignedPublicKey::from(parsed))
+                .unwrap();
+            assert_eq!(public.details.revocation_signatures.len(), 1);
+            assert!(!public.validity().is_valid());
+        }
+    }
+}
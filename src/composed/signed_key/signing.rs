@@ -0,0 +1,372 @@
+//! Selection of the signing key and hash algorithm of a secret key.
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    composed::signed_key::{SignedPublicKey, SignedSecretKey},
+    crypto::{hash::HashAlgorithm, public_key::PublicKeyAlgorithm},
+    errors::{bail, ensure, Result},
+    policy::{Policy, StandardPolicy},
+    types::{
+        Fingerprint, KeyDetails, KeyId, KeyVersion, Password, PublicKeyTrait, SecretKeyTrait,
+        SignatureBytes,
+    },
+};
+
+/// Hash algorithms that may be negotiated, in order of preference of this implementation.
+const HASH_ALGORITHMS: &[HashAlgorithm] = &[
+    HashAlgorithm::Sha512,
+    HashAlgorithm::Sha384,
+    HashAlgorithm::Sha256,
+    HashAlgorithm::Sha3_512,
+    HashAlgorithm::Sha3_256,
+];
+
+/// The hash algorithm that every implementation must support
+/// (<https://www.rfc-editor.org/rfc/rfc9580.html#name-hash-algorithms>).
+const MANDATORY_HASH: HashAlgorithm = HashAlgorithm::Sha256;
+
+/// The (primary or sub-) key of a [`SignedSecretKey`] that was selected for signing, together
+/// with the hash algorithm to use.
+///
+/// This implements [`SecretKeyTrait`], with [`SecretKeyTrait::hash_alg`] returning the
+/// selected hash algorithm, so it can be used wherever a signer is expected.
+#[derive(Debug, Clone, Copy)]
+pub struct SigningKey<'a> {
+    key: &'a dyn SecretKeyTrait,
+    is_primary: bool,
+    hash_alg: HashAlgorithm,
+}
+
+impl SignedSecretKey {
+    /// Selects the key to sign data with.
+    ///
+    /// See [`Self::signing_key_at`].
+    pub fn signing_key(&self) -> Result<SigningKey<'_>> {
+        self.signing_key_at(Utc::now())
+    }
+
+    /// Selects the key to sign data with, evaluating the key at `reference_time`.
+    ///
+    /// This picks the newest valid (bound, not expired, not revoked) subkey that is flagged for
+    /// signing and has secret key material, falling back to the primary key if it is flagged
+    /// for signing and has secret key material.
+    /// GnuPG stubs (`gnu-dummy` and divert-to-card keys) are never selected.
+    /// The hash algorithm is the default for the key algorithm, use
+    /// [`SigningKey::with_recipients`] to take the preferences of recipients into account.
+    ///
+    /// The key is evaluated with the [`StandardPolicy`], see [`Self::signing_key_with_policy`].
+    pub fn signing_key_at(&self, reference_time: DateTime<Utc>) -> Result<SigningKey<'_>> {
+        self.signing_key_with_policy(reference_time, &StandardPolicy::default())
+    }
+
+    /// Like [`Self::signing_key_at`], but the key is evaluated with `policy`.
+    ///
+    /// Keys and binding signatures that `policy` rejects are not selected.
+    pub fn signing_key_with_policy(
+        &self,
+        reference_time: DateTime<Utc>,
+        policy: &dyn Policy,
+    ) -> Result<SigningKey<'_>> {
+        let validity = self.validity_at_with_policy(reference_time, policy);
+        ensure!(
+            validity.is_valid(),
+            "key {} is not valid: {:?}",
+            self.fingerprint(),
+            validity.status()
+        );
+
+        let subkey = validity
+            .valid_subkeys()
+            .filter(|s| s.key_flags().sign() && s.key.is_signing_key())
+            .filter_map(|s| {
+                self.secret_subkeys
+                    .iter()
+                    .find(|k| k.key.fingerprint() == s.key.fingerprint())
+            })
+            .filter(|k| k.key.secret_params().gnu_stub().is_none())
+            .max_by_key(|k| *k.key.public_key().created_at());
+
+        if let Some(subkey) = subkey {
+            return Ok(SigningKey::new(&subkey.key, false));
+        }
+
+        if validity.key_flags().sign()
+            && self.primary_key.public_key().is_signing_key()
+            && self.primary_key.secret_params().gnu_stub().is_none()
+        {
+            return Ok(SigningKey::new(&self.primary_key, true));
+        }
+
+        bail!("key {} has no valid signing key", self.fingerprint());
+    }
+}
+
+impl<'a> SigningKey<'a> {
+    fn new(key: &'a dyn SecretKeyTrait, is_primary: bool) -> Self {
+        SigningKey {
+            key,
+            is_primary,
+            hash_alg: key.hash_alg(),
+        }
+    }
+
+    /// The selected component key.
+    pub fn key(&self) -> &'a dyn SecretKeyTrait {
+        self.key
+    }
+
+    /// Returns true if the primary key was selected, false for a subkey.
+    pub fn is_primary(&self) -> bool {
+        self.is_primary
+    }
+
+    /// Selects the hash algorithm based on the preferences of the given recipients.
+    ///
+    /// This picks the most preferred hash algorithm that all recipients accept, and that is
+    /// strong enough for the key algorithm. If there is none, the default hash algorithm of
+    /// the key is kept.
+    pub fn with_recipients<'b>(
+        mut self,
+        recipients: impl IntoIterator<Item = &'b SignedPublicKey>,
+    ) -> Self {
+        let preferences: Vec<Vec<HashAlgorithm>> = recipients
+            .into_iter()
+            .map(|cert| {
+                cert.validity()
+                    .preference_signatures()
+                    .iter()
+                    .map(|s| s.preferred_hash_algs())
+                    .find(|p| !p.is_empty())
+                    .unwrap_or_default()
+                    .to_vec()
+            })
+            .collect();
+
+        let accepts = |prefs: &Vec<HashAlgorithm>, hash: HashAlgorithm| {
+            hash == MANDATORY_HASH || prefs.is_empty() || prefs.contains(&hash)
+        };
+
+        let candidates = preferences
+            .iter()
+            .flat_map(|p| p.iter().copied())
+            .chain(std::iter::once(self.key.hash_alg()))
+            .chain(HASH_ALGORITHMS.iter().copied());
+
+        if let Some(hash_alg) = candidates
+            .filter(|hash| self.is_compatible(*hash))
+            .find(|hash| preferences.iter().all(|p| accepts(p, *hash)))
+        {
+            self.hash_alg = hash_alg;
+        }
+
+        self
+    }
+
+    /// Returns true if `hash` can be used to sign with this key.
+    fn is_compatible(&self, hash: HashAlgorithm) -> bool {
+        let default = self.key.hash_alg();
+        if hash == default {
+            return true;
+        }
+
+        // These algorithms mandate a specific hash algorithm.
+        #[cfg(feature = "draft-pqc")]
+        if matches!(
+            self.key.algorithm(),
+            PublicKeyAlgorithm::MlDsa65Ed25519
+                | PublicKeyAlgorithm::MlDsa87Ed448
+                | PublicKeyAlgorithm::SlhDsaShake128s
+                | PublicKeyAlgorithm::SlhDsaShake128f
+                | PublicKeyAlgorithm::SlhDsaShake256s
+        ) {
+            return false;
+        }
+
+        // The default is the weakest hash algorithm that is appropriate for the key.
+        HASH_ALGORITHMS.contains(&hash) && hash.digest_size() >= default.digest_size()
+    }
+}
+
+impl KeyDetails for SigningKey<'_> {
+    fn version(&self) -> KeyVersion {
+        self.key.version()
+    }
+
+    fn fingerprint(&self) -> Fingerprint {
+        self.key.fingerprint()
+    }
+
+    fn key_id(&self) -> KeyId {
+        self.key.key_id()
+    }
+
+    fn algorithm(&self) -> PublicKeyAlgorithm {
+        self.key.algorithm()
+    }
+}
+
+impl SecretKeyTrait for SigningKey<'_> {
+    fn create_signature(
+        &self,
+        key_pw: &Password,
+        hash: HashAlgorithm,
+        data: &[u8],
+    ) -> Result<SignatureBytes> {
+        self.key.create_signature(key_pw, hash, data)
+    }
+
+    fn hash_alg(&self) -> HashAlgorithm {
+        self.hash_alg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use chrono::Duration;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use smallvec::smallvec;
+
+    use super::*;
+    use crate::{
+        composed::{Deserializable, KeyType},
+        crypto::ecc_curve::ECCCurve,
+        packet::{RevocationCode, SecretSubkey},
+        types::{EncryptedSecretParams, GnuStub, SecretParams},
+        util::test::{gen_key, generate, key_params, TestSubkey},
+    };
+
+    #[test]
+    fn select_signing_subkey() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let key = gen_key(
+            &mut rng,
+            KeyVersion::V4,
+            KeyType::Ed25519Legacy,
+            &[TestSubkey::Sign(KeyType::Ed25519Legacy)],
+        );
+
+        let signer = key.signing_key().unwrap();
+        assert!(!signer.is_primary());
+        assert_eq!(
+            signer.fingerprint(),
+            key.secret_subkeys[0].key.fingerprint()
+        );
+
+        // a revoked subkey is not used
+        let fp = key.secret_subkeys[0].key.fingerprint();
+        let sig = key
+            .revoke_subkey(
+                &mut rng,
+                &Password::empty(),
+                &fp,
+                RevocationCode::KeyRetired,
+                "",
+            )
+            .unwrap();
+        let mut revoked = key.clone();
+        revoked.secret_subkeys[0].signatures.push(sig);
+
+        let signer = revoked.signing_key().unwrap();
+        assert!(signer.is_primary());
+        assert_eq!(signer.fingerprint(), key.fingerprint());
+
+        // and neither is a subkey without secret key material
+        let mut public_only = key.clone();
+        let subkey = public_only.secret_subkeys.remove(0);
+        public_only.public_subkeys.push(subkey.signed_public_key());
+        assert!(public_only.signing_key().unwrap().is_primary());
+
+        // nor is anything before the key was created
+        let before = *key.primary_key.public_key().created_at() - Duration::days(1);
+        assert!(key.signing_key_at(before).is_err());
+    }
+
+    #[test]
+    fn select_with_policy() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let key = gen_key(
+            &mut rng,
+            KeyVersion::V4,
+            KeyType::Ed25519Legacy,
+            &[TestSubkey::Sign(KeyType::Rsa(2048))],
+        );
+        assert!(!key.signing_key().unwrap().is_primary());
+
+        // a subkey that the policy rejects is not used
+        let policy = StandardPolicy {
+            min_rsa_bits: 3072,
+            ..Default::default()
+        };
+        let signer = key.signing_key_with_policy(Utc::now(), &policy).unwrap();
+        assert!(signer.is_primary());
+    }
+
+    #[test]
+    fn skip_gnu_stubs() {
+        // a signing subkey that was moved to a smartcard is not used
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut on_card = gen_key(
+            &mut rng,
+            KeyVersion::V4,
+            KeyType::Ed25519Legacy,
+            &[TestSubkey::Sign(KeyType::Ed25519Legacy)],
+        );
+        let subkey = &mut on_card.secret_subkeys[0];
+        let secret_params = SecretParams::Encrypted(EncryptedSecretParams::new_gnu_stub(
+            GnuStub::DivertToCard {
+                serial: vec![0xd2, 0x76, 0x00, 0x01].into(),
+            },
+            KeyVersion::V4,
+        ));
+        subkey.key = SecretSubkey::new(subkey.key.public_key().clone(), secret_params).unwrap();
+        assert!(on_card.signing_key().unwrap().is_primary());
+
+        // the primary key is a gnu-dummy stub, the only subkey is for encryption
+        let (key, _) = SignedSecretKey::from_armor_file("tests/gnupg/stub.sec.asc").unwrap();
+        assert!(key.validity().is_valid());
+        assert!(key.validity().key_flags().sign());
+        assert!(key.signing_key().is_err());
+    }
+
+    #[test]
+    fn select_hash() {
+        use HashAlgorithm::*;
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let key = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519Legacy, &[]);
+        let p384 = gen_key(
+            &mut rng,
+            KeyVersion::V4,
+            KeyType::ECDSA(ECCCurve::P384),
+            &[],
+        );
+        let mut gen_cert = |hashes| {
+            let mut params = key_params(KeyVersion::V4, KeyType::Ed25519Legacy, &[]);
+            params.preferred_hash_algorithms(hashes);
+            generate(&mut rng, &params, &Password::empty()).signed_public_key()
+        };
+
+        let signer = key.signing_key().unwrap();
+        assert_eq!(signer.hash_alg(), Sha256);
+
+        let a = gen_cert(smallvec![Sha384, Sha512]);
+        let b = gen_cert(smallvec![Sha512, Sha384]);
+        assert_eq!(signer.with_recipients([&a, &b]).hash_alg(), Sha384);
+
+        // unsupported or weak preferences are ignored
+        let c = gen_cert(smallvec![Sha1, Sha224]);
+        assert_eq!(signer.with_recipients([&c]).hash_alg(), Sha256);
+
+        // P-384 needs at least SHA384
+        let signer = p384.signing_key().unwrap();
+        assert!(signer.is_primary());
+        assert_eq!(signer.hash_alg(), Sha384);
+        let d = gen_cert(smallvec![Sha256, Sha512]);
+        assert_eq!(signer.with_recipients([&d]).hash_alg(), Sha512);
+        assert_eq!(signer.with_recipients([&c]).hash_alg(), Sha384);
+    }
+}
@@ -0,0 +1,892 @@
+//! Time-aware validity evaluation for certificates.
+//!
+//! [`SignedKeyDetails::verify`] and [`SignedPublicKey::verify`] check every signature on a
+//! certificate, but don't say which of the (possibly many) self-signatures is authoritative,
+//! or whether a component is usable at a particular point in time.
+//!
+//! [`CertificateValidity`] answers these questions: for each component it selects the newest
+//! valid binding self-signature at the reference time, applies hard and soft revocations,
+//! key and signature expiration, and (for signing-capable subkeys) requires a valid primary key
+//! binding ("back") signature.
+//!
+//! See <https://www.rfc-editor.org/rfc/rfc9580.html#name-key-revocation-and-expirati>
+
+use bytes::Bytes;
+use chrono::{DateTime, Duration, Utc};
+use log::debug;
+
+use crate::{
+    composed::{SignedKeyDetails, SignedPublicKey, SignedSecretKey},
+    errors::Result,
+    packet::{self, KeyFlags, RevocationCode, Signature, SignatureType},
+    policy::{NullPolicy, Policy, PolicyViolation},
+    types::{PublicKeyTrait, SignedUser, SignedUserAttribute, Tag},
+};
+
+/// The state of a certificate component at a reference time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComponentStatus<'a> {
+    /// The component is bound, not revoked and not expired.
+    Valid,
+    /// The component was created after the reference time.
+    NotYetValid,
+    /// No valid binding self-signature exists at the reference time.
+    Unbound,
+    /// The binding self-signature marks a signing-capable subkey, but it has no valid
+    /// primary key binding ("back") signature.
+    MissingBacksig,
+    /// The component expired at the given time.
+    Expired(DateTime<Utc>),
+    /// The component has been revoked.
+    Revoked(Revocation<'a>),
+    /// The key material is rejected by the policy the certificate was evaluated with.
+    Rejected(PolicyViolation),
+}
+
+impl ComponentStatus<'_> {
+    /// Returns true if the component is valid.
+    pub fn is_valid(&self) -> bool {
+        matches!(self, ComponentStatus::Valid)
+    }
+}
+
+/// A revocation that is in effect at the reference time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revocation<'a> {
+    /// The revocation signature.
+    pub signature: &'a Signature,
+    /// Hard revocations invalidate the key at all times, soft revocations only from their
+    /// creation time on.
+    pub hard: bool,
+}
+
+impl Revocation<'_> {
+    /// The reason code of the revocation, if any.
+    pub fn code(&self) -> Option<RevocationCode> {
+        self.signature.revocation_reason_code().copied()
+    }
+
+    /// The human readable reason of the revocation, if any.
+    pub fn reason(&self) -> Option<&Bytes> {
+        self.signature.revocation_reason_string()
+    }
+
+    /// The creation time of the revocation signature.
+    pub fn created(&self) -> Option<&DateTime<Utc>> {
+        self.signature.created()
+    }
+}
+
+/// Returns true if a key revocation signature is a "hard" revocation.
+///
+/// Revocations without a reason, or with a reason that indicates key compromise (or any reason
+/// we don't understand) are hard. "Superseded" and "retired" are soft.
+///
+/// See <https://www.rfc-editor.org/rfc/rfc9580.html#name-reason-for-revocation>
+pub fn is_hard_revocation(sig: &Signature) -> bool {
+    !matches!(
+        sig.revocation_reason_code(),
+        Some(
+            RevocationCode::KeySuperseded
+                | RevocationCode::KeyRetired
+                | RevocationCode::CertUserIdInvalid
+        )
+    )
+}
+
+/// Validity of a user ID at the reference time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserValidity<'a> {
+    pub user: &'a SignedUser,
+    /// The authoritative self-signature, if any.
+    pub binding: Option<&'a Signature>,
+    pub status: ComponentStatus<'a>,
+}
+
+impl UserValidity<'_> {
+    /// Returns true if the authoritative self-signature marks this as the primary user ID.
+    pub fn is_primary(&self) -> bool {
+        self.binding.is_some_and(Signature::is_primary)
+    }
+}
+
+/// Validity of a user attribute at the reference time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserAttributeValidity<'a> {
+    pub attr: &'a SignedUserAttribute,
+    /// The authoritative self-signature, if any.
+    pub binding: Option<&'a Signature>,
+    pub status: ComponentStatus<'a>,
+}
+
+/// Validity of a subkey at the reference time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubkeyValidity<'a> {
+    pub key: &'a packet::PublicSubkey,
+    /// The authoritative subkey binding signature, if any.
+    pub binding: Option<&'a Signature>,
+    pub status: ComponentStatus<'a>,
+}
+
+impl SubkeyValidity<'_> {
+    /// The key flags of the authoritative binding signature.
+    pub fn key_flags(&self) -> KeyFlags {
+        self.binding.map(Signature::key_flags).unwrap_or_default()
+    }
+
+    /// The expiration time of this subkey, according to the authoritative binding signature.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        expires_at(self.key.created_at(), self.binding)
+    }
+}
+
+/// A structured view of a certificate, evaluated at a reference time.
+///
+/// Construct via [`SignedPublicKey::validity_at`] or [`SignedSecretKey::validity_at`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateValidity<'a> {
+    reference_time: DateTime<Utc>,
+    primary_key: &'a packet::PublicKey,
+    binding: Option<&'a Signature>,
+    direct_signature: Option<&'a Signature>,
+    status: ComponentStatus<'a>,
+    users: Vec<UserValidity<'a>>,
+    user_attributes: Vec<UserAttributeValidity<'a>>,
+    subkeys: Vec<SubkeyValidity<'a>>,
+}
+
+impl<'a> CertificateValidity<'a> {
+    /// Evaluate the components of a certificate at `reference_time`.
+    ///
+    /// Self-signatures and revocations that `policy` rejects are ignored, as if they were
+    /// invalid. Keys that `policy` rejects are [`ComponentStatus::Rejected`].
+    pub fn evaluate(
+        primary_key: &'a packet::PublicKey,
+        details: &'a SignedKeyDetails,
+        subkeys: impl IntoIterator<Item = (&'a packet::PublicSubkey, &'a [Signature])>,
+        reference_time: DateTime<Utc>,
+        policy: &dyn Policy,
+    ) -> Self {
+        let t = reference_time;
+
+        let users: Vec<_> = details
+            .users
+            .iter()
+            .map(|user| {
+                let (binding, status) = evaluate_certifications(&user.signatures, t, |sig| {
+                    policy.signature(sig)?;
+                    sig.verify_certification(primary_key, Tag::UserId, &user.id)
+                });
+                UserValidity {
+                    user,
+                    binding,
+                    status,
+                }
+            })
+            .collect();
+
+        let user_attributes = details
+            .user_attributes
+            .iter()
+            .map(|attr| {
+                let (binding, status) = evaluate_certifications(&attr.signatures, t, |sig| {
+                    policy.signature(sig)?;
+                    sig.verify_certification(primary_key, Tag::UserAttribute, &attr.attr)
+                });
+                UserAttributeValidity {
+                    attr,
+                    binding,
+                    status,
+                }
+            })
+            .collect();
+
+        let direct_signature = newest_valid(
+            details
+                .direct_signatures
+                .iter()
+                .filter(|sig| sig.typ() == Some(SignatureType::Key)),
+            t,
+            |sig| {
+                policy.signature(sig)?;
+                sig.verify_key(primary_key)
+            },
+        );
+
+        // The primary user ID: prefer user IDs flagged as primary, then the newest binding.
+        let primary_user_binding = users
+            .iter()
+            .filter(|u| u.status.is_valid())
+            .filter_map(|u| u.binding)
+            .max_by_key(|sig| (sig.is_primary(), sig.created().copied()));
+
+        let binding = direct_signature.or(primary_user_binding);
+
+        let status = if primary_key.created_at() > &t {
+            ComponentStatus::NotYetValid
+        } else if let Some(revocation) = find_key_revocation(
+            details
+                .revocation_signatures
+                .iter()
+                .filter(|sig| sig.typ() == Some(SignatureType::KeyRevocation)),
+            t,
+            |sig| {
+                policy.signature(sig)?;
+                sig.verify_key(primary_key)
+            },
+        ) {
+            ComponentStatus::Revoked(revocation)
+        } else if let Err(violation) = policy.public_key(primary_key) {
+            ComponentStatus::Rejected(violation)
+        } else if binding.is_none() {
+            ComponentStatus::Unbound
+        } else {
+            // A key expiration time on the direct key signature takes precedence.
+            let expiration_sig = match direct_signature {
+                Some(dks) if dks.key_expiration_time().is_some() => Some(dks),
+                _ => primary_user_binding.or(direct_signature),
+            };
+            match expires_at(primary_key.created_at(), expiration_sig)
+                .or_else(|| legacy_expires_at(primary_key))
+            {
+                Some(exp) if exp <= t => ComponentStatus::Expired(exp),
+                _ => ComponentStatus::Valid,
+            }
+        };
+
+        let subkeys = subkeys
+            .into_iter()
+            .map(|(key, signatures)| evaluate_subkey(primary_key, key, signatures, t, policy))
+            .collect();
+
+        CertificateValidity {
+            reference_time,
+            primary_key,
+            binding,
+            direct_signature,
+            status,
+            users,
+            user_attributes,
+            subkeys,
+        }
+    }
+
+    /// The time at which this view was evaluated.
+    pub fn reference_time(&self) -> &DateTime<Utc> {
+        &self.reference_time
+    }
+
+    pub fn primary_key(&self) -> &'a packet::PublicKey {
+        self.primary_key
+    }
+
+    /// The status of the primary key (and hence of the certificate as a whole).
+    pub fn status(&self) -> &ComponentStatus<'a> {
+        &self.status
+    }
+
+    /// Returns true if the primary key is valid at the reference time.
+    pub fn is_valid(&self) -> bool {
+        self.status.is_valid()
+    }
+
+    /// The authoritative self-signature for the primary key: the newest valid direct key
+    /// signature, or the binding signature of the primary user ID.
+    pub fn binding(&self) -> Option<&'a Signature> {
+        self.binding
+    }
+
+    /// The newest valid direct key signature, if any.
+    pub fn direct_signature(&self) -> Option<&'a Signature> {
+        self.direct_signature
+    }
+
+    /// The expiration time of the primary key.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        let expiration_sig = match self.direct_signature {
+            Some(dks) if dks.key_expiration_time().is_some() => Some(dks),
+            _ => self.primary_user().and_then(|u| u.binding).or(self.binding),
+        };
+
+        expires_at(self.primary_key.created_at(), expiration_sig)
+            .or_else(|| legacy_expires_at(self.primary_key))
+    }
+
+    /// The primary user ID, if one is valid.
+    pub fn primary_user(&self) -> Option<&UserValidity<'a>> {
+        self.users
+            .iter()
+            .filter(|u| u.status.is_valid())
+            .max_by_key(|u| (u.is_primary(), u.binding.and_then(|s| s.created().copied())))
+    }
+
+    /// All user IDs, with their individual status.
+    pub fn users(&self) -> &[UserValidity<'a>] {
+        &self.users
+    }
+
+    /// All user attributes, with their individual status.
+    pub fn user_attributes(&self) -> &[UserAttributeValidity<'a>] {
+        &self.user_attributes
+    }
+
+    /// All subkeys, with their individual status.
+    pub fn subkeys(&self) -> &[SubkeyValidity<'a>] {
+        &self.subkeys
+    }
+
+    /// User IDs that are usable: the user ID and the primary key are valid.
+    pub fn valid_users(&self) -> impl Iterator<Item = &UserValidity<'a>> {
+        let primary_valid = self.is_valid();
+        self.users
+            .iter()
+            .filter(move |u| primary_valid && u.status.is_valid())
+    }
+
+    /// Subkeys that are usable: the subkey and the primary key are valid.
+    pub fn valid_subkeys(&self) -> impl Iterator<Item = &SubkeyValidity<'a>> {
+        let primary_valid = self.is_valid();
+        self.subkeys
+            .iter()
+            .filter(move |s| primary_valid && s.status.is_valid())
+    }
+
+    /// Additional decryption subkeys (ADSKs) that are usable: valid, encryption capable subkeys
+    /// that are flagged with [`KeyFlags::adsk`].
+    pub fn adsks(&self) -> impl Iterator<Item = &SubkeyValidity<'a>> {
+        self.valid_subkeys()
+            .filter(|s| s.key_flags().adsk() && s.key.is_encryption_key())
+    }
+
+    /// The key flags that apply to the primary key.
+    pub fn key_flags(&self) -> KeyFlags {
+        self.binding.map(Signature::key_flags).unwrap_or_default()
+    }
+
+    /// The self-signatures that carry the certificate wide preferences, in order of precedence:
+    /// the direct key signature, then the binding of the primary user ID.
+    pub(crate) fn preference_signatures(&self) -> Vec<&'a Signature> {
+        self.direct_signature
+            .into_iter()
+            .chain(self.primary_user().and_then(|u| u.binding))
+            .collect()
+    }
+}
+
+impl SignedPublicKey {
+    /// Evaluate the validity of this certificate and all its components at `reference_time`.
+    ///
+    /// No policy is applied, see [`Self::validity_at_with_policy`].
+    pub fn validity_at(&self, reference_time: DateTime<Utc>) -> CertificateValidity<'_> {
+        self.validity_at_with_policy(reference_time, &NullPolicy)
+    }
+
+    /// Evaluate the validity of this certificate and all its components at `reference_time`,
+    /// only accepting signatures and keys that `policy` accepts.
+    pub fn validity_at_with_policy(
+        &self,
+        reference_time: DateTime<Utc>,
+        policy: &dyn Policy,
+    ) -> CertificateValidity<'_> {
+        CertificateValidity::evaluate(
+            &self.primary_key,
+            &self.details,
+            self.public_subkeys
+                .iter()
+                .map(|s| (&s.key, s.signatures.as_slice())),
+            reference_time,
+            policy,
+        )
+    }
+
+    /// Evaluate the validity of this certificate and all its components now.
+    pub fn validity(&self) -> CertificateValidity<'_> {
+        self.validity_at(Utc::now())
+    }
+}
+
+impl SignedSecretKey {
+    /// Evaluate the validity of this key and all its components at `reference_time`.
+    ///
+    /// Secret subkeys are reported via their public part. No policy is applied, see
+    /// [`Self::validity_at_with_policy`].
+    pub fn validity_at(&self, reference_time: DateTime<Utc>) -> CertificateValidity<'_> {
+        self.validity_at_with_policy(reference_time, &NullPolicy)
+    }
+
+    /// Evaluate the validity of this key and all its components at `reference_time`, only
+    /// accepting signatures and keys that `policy` accepts.
+    pub fn validity_at_with_policy(
+        &self,
+        reference_time: DateTime<Utc>,
+        policy: &dyn Policy,
+    ) -> CertificateValidity<'_> {
+        CertificateValidity::evaluate(
+            self.primary_key.public_key(),
+            &self.details,
+            self.public_subkeys
+                .iter()
+                .map(|s| (&s.key, s.signatures.as_slice()))
+                .chain(
+                    self.secret_subkeys
+                        .iter()
+                        .map(|s| (s.key.public_key(), s.signatures.as_slice())),
+                ),
+            reference_time,
+            policy,
+        )
+    }
+
+    /// Evaluate the validity of this key and all its components now.
+    pub fn validity(&self) -> CertificateValidity<'_> {
+        self.validity_at(Utc::now())
+    }
+}
+
+fn evaluate_subkey<'a>(
+    primary_key: &'a packet::PublicKey,
+    key: &'a packet::PublicSubkey,
+    signatures: &'a [Signature],
+    t: DateTime<Utc>,
+    policy: &dyn Policy,
+) -> SubkeyValidity<'a> {
+    if key.created_at() > &t {
+        return SubkeyValidity {
+            key,
+            binding: None,
+            status: ComponentStatus::NotYetValid,
+        };
+    }
+
+    let mut missing_backsig = false;
+    let binding = newest_valid(
+        signatures
+            .iter()
+            .filter(|sig| sig.typ() == Some(SignatureType::SubkeyBinding)),
+        t,
+        |sig| {
+            policy.signature(sig)?;
+            sig.verify_subkey_binding(primary_key, key)?;
+
+            if sig.key_flags().sign() {
+                let backsig = sig.embedded_signature();
+                let valid = backsig.is_some_and(|backsig| {
+                    backsig.typ() == Some(SignatureType::KeyBinding)
+                        && policy.signature(backsig).is_ok()
+                        && backsig.verify_primary_key_binding(key, primary_key).is_ok()
+                });
+                if !valid {
+                    missing_backsig = true;
+                    crate::errors::bail!("missing valid back signature for signing subkey");
+                }
+            }
+
+            Ok(())
+        },
+    );
+
+    let revocation = find_key_revocation(
+        signatures
+            .iter()
+            .filter(|sig| sig.typ() == Some(SignatureType::SubkeyRevocation)),
+        t,
+        |sig| {
+            policy.signature(sig)?;
+            sig.verify_subkey_binding(primary_key, key)
+        },
+    );
+
+    let status = if let Some(revocation) = revocation {
+        ComponentStatus::Revoked(revocation)
+    } else if let Err(violation) = policy.public_key(key) {
+        ComponentStatus::Rejected(violation)
+    } else if binding.is_none() {
+        if missing_backsig {
+            ComponentStatus::MissingBacksig
+        } else {
+            ComponentStatus::Unbound
+        }
+    } else {
+        match expires_at(key.created_at(), binding).or_else(|| legacy_expires_at(key)) {
+            Some(exp) if exp <= t => ComponentStatus::Expired(exp),
+            _ => ComponentStatus::Valid,
+        }
+    };
+
+    SubkeyValidity {
+        key,
+        binding,
+        status,
+    }
+}
+
+/// Evaluate the certifications of a user ID or user attribute.
+///
+/// Revocations of user IDs are always soft: they take effect if they were issued at, or
+/// after, the authoritative binding signature, and before the reference time.
+fn evaluate_certifications<'a>(
+    signatures: &'a [Signature],
+    t: DateTime<Utc>,
+    verify: impl Fn(&Signature) -> Result<()>,
+) -> (Option<&'a Signature>, ComponentStatus<'a>) {
+    let binding = newest_valid(
+        signatures.iter().filter(|sig| {
+            matches!(
+                sig.typ(),
+                Some(
+                    SignatureType::CertGeneric
+                        | SignatureType::CertPersona
+                        | SignatureType::CertCasual
+                        | SignatureType::CertPositive
+                )
+            )
+        }),
+        t,
+        &verify,
+    );
+
+    let Some(binding) = binding else {
+        return (None, ComponentStatus::Unbound);
+    };
+
+    let revocation = signatures
+        .iter()
+        .filter(|sig| sig.typ() == Some(SignatureType::CertRevocation))
+        .filter(|sig| {
+            sig.created()
+                .is_some_and(|c| c <= &t && Some(c) >= binding.created())
+        })
+        .find(|sig| verify(sig).is_ok());
+
+    if let Some(signature) = revocation {
+        return (
+            Some(binding),
+            ComponentStatus::Revoked(Revocation {
+                signature,
+                hard: false,
+            }),
+        );
+    }
+
+    (Some(binding), ComponentStatus::Valid)
+}
+
+/// Find a revocation of a (sub)key that is in effect at `t`.
+fn find_key_revocation<'a>(
+    signatures: impl Iterator<Item = &'a Signature>,
+    t: DateTime<Utc>,
+    verify: impl Fn(&Signature) -> Result<()>,
+) -> Option<Revocation<'a>> {
+    signatures
+        .filter_map(|signature| {
+            let hard = is_hard_revocation(signature);
+            // Soft revocations only take effect from their creation time on.
+            if !hard && signature.created().is_none_or(|c| c > &t) {
+                return None;
+            }
+
+            match verify(signature) {
+                Ok(()) => Some(Revocation { signature, hard }),
+                Err(err) => {
+                    debug!("ignoring invalid revocation signature: {err:?}");
+                    None
+                }
+            }
+        })
+        .max_by_key(|r| r.hard)
+}
+
+/// Select the newest signature that is alive at `t` and passes `verify`.
+fn newest_valid<'a>(
+    signatures: impl Iterator<Item = &'a Signature>,
+    t: DateTime<Utc>,
+    mut verify: impl FnMut(&Signature) -> Result<()>,
+) -> Option<&'a Signature> {
+    let mut candidates: Vec<_> = signatures.filter(|sig| is_alive_at(sig, t)).collect();
+    // Newest first. Of signatures created in the same second, the one that comes later wins.
+    candidates.reverse();
+    candidates.sort_by_key(|sig| std::cmp::Reverse(sig.created().copied()));
+
+    candidates.into_iter().find(|sig| match verify(sig) {
+        Ok(()) => true,
+        Err(err) => {
+            debug!("ignoring invalid binding signature: {err:?}");
+            false
+        }
+    })
+}
+
+/// Is `sig` created at or before `t`, and not expired at `t`?
+fn is_alive_at(sig: &Signature, t: DateTime<Utc>) -> bool {
+    let Some(created) = sig.created() else {
+        return false;
+    };
+    if created > &t {
+        return false;
+    }
+
+    match sig.signature_expiration_time() {
+        Some(exp) if !exp.is_zero() => *created + *exp > t,
+        _ => true,
+    }
+}
+
+/// Absolute expiration time of a key, based on the Key Expiration Time subpacket in `sig`.
+fn expires_at(created: &DateTime<Utc>, sig: Option<&Signature>) -> Option<DateTime<Utc>> {
+    let exp = sig?.key_expiration_time()?;
+    if exp.is_zero() {
+        return None;
+    }
+
+    Some(*created + *exp)
+}
+
+/// Absolute expiration time of a legacy (v2/v3) key, which encodes its validity period in
+/// days in the key packet itself.
+fn legacy_expires_at(key: &impl PublicKeyTrait) -> Option<DateTime<Utc>> {
+    match key.expiration() {
+        Some(days) if days > 0 => Some(*key.created_at() + Duration::days(days.into())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use chrono::SubsecRound;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        crypto::hash::HashAlgorithm,
+        packet::{SignatureConfig, Subpacket, SubpacketData},
+        types::{KeyDetails, KeyVersion, Password},
+        util::test::alice_key,
+    };
+
+    fn revocation(
+        key: &SignedSecretKey,
+        typ: SignatureType,
+        code: RevocationCode,
+        created: DateTime<Utc>,
+    ) -> SignatureConfig {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut config = SignatureConfig::from_key(&mut rng, &key.primary_key, typ).unwrap();
+        config.hashed_subpackets = vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(created)).unwrap(),
+            Subpacket::regular(SubpacketData::IssuerFingerprint(key.fingerprint())).unwrap(),
+            Subpacket::regular(SubpacketData::RevocationReason(code, "test".into())).unwrap(),
+        ];
+        config
+    }
+
+    #[test]
+    fn valid_key() {
+        for version in [KeyVersion::V4, KeyVersion::V6] {
+            let key = alice_key(version, 0);
+            let public = key.signed_public_key();
+            let view = public.validity();
+
+            assert!(view.is_valid());
+            assert!(view.binding().is_some());
+            assert_eq!(view.expires_at(), None);
+            assert_eq!(view.valid_users().count(), 2);
+            assert_eq!(view.valid_subkeys().count(), 2);
+            assert_eq!(
+                view.primary_user().unwrap().user.id.id(),
+                b"Alice <alice@example.org>"
+            );
+            if version == KeyVersion::V6 {
+                assert!(view.direct_signature().is_some());
+            }
+
+            // the secret key yields the same view
+            assert_eq!(key.validity().subkeys().len(), 2);
+            assert!(key.validity().is_valid());
+
+            // before the key was created, nothing is valid
+            let before = *public.primary_key.created_at() - Duration::days(1);
+            let view = public.validity_at(before);
+            assert_eq!(view.status(), &ComponentStatus::NotYetValid);
+            assert_eq!(view.valid_subkeys().count(), 0);
+        }
+    }
+
+    #[test]
+    fn hard_and_soft_key_revocation() {
+        let key = alice_key(KeyVersion::V4, 0);
+        let now = Utc::now().trunc_subsecs(0);
+        let pw = Password::empty();
+
+        let later = now + Duration::hours(1);
+
+        // a soft revocation only applies from its creation on
+        let sig = revocation(
+            &key,
+            SignatureType::KeyRevocation,
+            RevocationCode::KeyRetired,
+            later,
+        )
+        .sign_key(&key.primary_key, &pw, key.primary_key.public_key())
+        .unwrap();
+
+        let mut soft = key.signed_public_key();
+        soft.details.revocation_signatures.push(sig);
+
+        let view = soft.validity_at(now);
+        assert!(view.is_valid());
+        let view = soft.validity_at(later);
+        let ComponentStatus::Revoked(rev) = view.status() else {
+            panic!("expected revocation: {:?}", view.status());
+        };
+        assert!(!rev.hard);
+        assert_eq!(rev.code(), Some(RevocationCode::KeyRetired));
+        assert_eq!(view.valid_subkeys().count(), 0);
+
+        // a hard revocation applies retroactively
+        let sig = revocation(
+            &key,
+            SignatureType::KeyRevocation,
+            RevocationCode::KeyCompromised,
+            later,
+        )
+        .sign_key(&key.primary_key, &pw, key.primary_key.public_key())
+        .unwrap();
+
+        let mut hard = key.signed_public_key();
+        hard.details.revocation_signatures.push(sig);
+
+        let view = hard.validity_at(now);
+        let ComponentStatus::Revoked(rev) = view.status() else {
+            panic!("expected revocation: {:?}", view.status());
+        };
+        assert!(rev.hard);
+    }
+
+    #[test]
+    fn subkey_revocation_and_expiration() {
+        let key = alice_key(KeyVersion::V4, 0);
+        let now = Utc::now().trunc_subsecs(0);
+        let pw = Password::empty();
+        let mut public = key.signed_public_key();
+
+        let sig = revocation(
+            &key,
+            SignatureType::SubkeyRevocation,
+            RevocationCode::KeySuperseded,
+            now,
+        )
+        .sign_subkey_binding(
+            &key.primary_key,
+            key.primary_key.public_key(),
+            &pw,
+            &public.public_subkeys[0].key,
+        )
+        .unwrap();
+        public.public_subkeys[0].signatures.push(sig);
+
+        let view = public.validity_at(now);
+        assert!(view.is_valid());
+        assert!(matches!(
+            view.subkeys()[0].status,
+            ComponentStatus::Revoked(_)
+        ));
+        assert_eq!(view.valid_subkeys().count(), 1);
+
+        // a newer binding with an expiration time supersedes the original binding
+        let later = now + Duration::seconds(1);
+        let subkey = &public.public_subkeys[1];
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let mut config = SignatureConfig::v4(
+            SignatureType::SubkeyBinding,
+            key.algorithm(),
+            HashAlgorithm::Sha256,
+        );
+        config.hashed_subpackets = vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(later)).unwrap(),
+            Subpacket::regular(SubpacketData::IssuerFingerprint(key.fingerprint())).unwrap(),
+            Subpacket::regular(SubpacketData::KeyFlags(subkey.signatures[0].key_flags())).unwrap(),
+            Subpacket::regular(SubpacketData::KeyExpirationTime(Duration::days(2))).unwrap(),
+        ];
+
+        // without a back signature, the new binding is not acceptable for a signing subkey
+        let sig = config
+            .clone()
+            .sign_subkey_binding(
+                &key.primary_key,
+                key.primary_key.public_key(),
+                &pw,
+                &subkey.key,
+            )
+            .unwrap();
+        let mut no_backsig = public.clone();
+        no_backsig.public_subkeys[1].signatures = vec![sig];
+        let view = no_backsig.validity_at(later);
+        assert_eq!(view.subkeys()[1].status, ComponentStatus::MissingBacksig);
+
+        let backsig = key.secret_subkeys[1]
+            .sign_primary_key_binding(&mut rng, key.primary_key.public_key(), &pw)
+            .unwrap();
+        config
+            .hashed_subpackets
+            .push(Subpacket::regular(SubpacketData::EmbeddedSignature(Box::new(backsig))).unwrap());
+        let sig = config
+            .sign_subkey_binding(
+                &key.primary_key,
+                key.primary_key.public_key(),
+                &pw,
+                &subkey.key,
+            )
+            .unwrap();
+        public.public_subkeys[1].signatures.push(sig);
+
+        let view = public.validity_at(later);
+        let subkey = &view.subkeys()[1];
+        assert!(subkey.status.is_valid());
+        assert_eq!(subkey.binding.unwrap().created(), Some(&later));
+        assert!(subkey.key_flags().sign());
+
+        let expiration = subkey.expires_at().unwrap();
+        assert_eq!(expiration, *subkey.key.created_at() + Duration::days(2));
+
+        let view = public.validity_at(expiration);
+        assert_eq!(
+            view.subkeys()[1].status,
+            ComponentStatus::Expired(expiration)
+        );
+    }
+
+    #[test]
+    fn user_id_revocation() {
+        let key = alice_key(KeyVersion::V4, 0);
+        let now = Utc::now().trunc_subsecs(0);
+        let mut public = key.signed_public_key();
+
+        let user = &public.details.users[1];
+        let sig = revocation(
+            &key,
+            SignatureType::CertRevocation,
+            RevocationCode::CertUserIdInvalid,
+            now,
+        )
+        .sign_certification(
+            &key.primary_key,
+            key.primary_key.public_key(),
+            &Password::empty(),
+            Tag::UserId,
+            &user.id,
+        )
+        .unwrap();
+        public.details.users[1].signatures.push(sig);
+
+        let view = public.validity_at(now);
+        assert!(view.is_valid());
+        assert_eq!(view.valid_users().count(), 1);
+        assert!(matches!(
+            view.users()[1].status,
+            ComponentStatus::Revoked(Revocation { hard: false, .. })
+        ));
+    }
+}
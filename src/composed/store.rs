@@ -0,0 +1,582 @@
+//! In-memory storage of certificates, indexed for lookups.
+
+#[cfg(feature = "cert-d")]
+mod cert_d;
+mod gnupg;
+
+use std::collections::{HashMap, HashSet};
+
+use log::warn;
+
+use crate::{
+    composed::{Esk, SignedPublicKey, SignedSecretKey},
+    errors::Result,
+    packet::{Signature, UserId},
+    types::{Fingerprint, KeyId, PublicKeyTrait},
+};
+
+#[cfg(feature = "cert-d")]
+pub use self::cert_d::{CertD, CertDTag};
+pub use self::gnupg::{Keybox, KeyboxMetadata, KeyboxUserId, Keyring, KeyringEntry};
+
+/// A certificate (or secret key) that can be kept in a [`CertStore`].
+pub trait StoreEntry: Clone {
+    /// The primary key.
+    fn primary_key(&self) -> &dyn PublicKeyTrait;
+
+    /// The public parts of all subkeys.
+    fn subkeys(&self) -> Vec<&dyn PublicKeyTrait>;
+
+    /// All user IDs.
+    fn user_ids(&self) -> Vec<&UserId>;
+
+    /// Merges another copy of the same certificate into this one.
+    fn merge_entry(self, other: Self) -> Result<Self>;
+}
+
+impl StoreEntry for SignedPublicKey {
+    fn primary_key(&self) -> &dyn PublicKeyTrait {
+        &self.primary_key
+    }
+
+    fn subkeys(&self) -> Vec<&dyn PublicKeyTrait> {
+        self.public_subkeys
+            .iter()
+            .map(|k| &k.key as &dyn PublicKeyTrait)
+            .collect()
+    }
+
+    fn user_ids(&self) -> Vec<&UserId> {
+        self.details.users.iter().map(|u| &u.id).collect()
+    }
+
+    fn merge_entry(self, other: Self) -> Result<Self> {
+        self.merge(other)
+    }
+}
+
+impl StoreEntry for SignedSecretKey {
+    fn primary_key(&self) -> &dyn PublicKeyTrait {
+        self.primary_key.public_key()
+    }
+
+    fn subkeys(&self) -> Vec<&dyn PublicKeyTrait> {
+        self.secret_subkeys
+            .iter()
+            .map(|k| k.key.public_key() as &dyn PublicKeyTrait)
+            .chain(
+                self.public_subkeys
+                    .iter()
+                    .map(|k| &k.key as &dyn PublicKeyTrait),
+            )
+            .collect()
+    }
+
+    fn user_ids(&self) -> Vec<&UserId> {
+        self.details.users.iter().map(|u| &u.id).collect()
+    }
+
+    fn merge_entry(self, other: Self) -> Result<Self> {
+        self.merge(other)
+    }
+}
+
+/// The outcome of [`CertStore::insert`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inserted {
+    /// The certificate was not in the store yet.
+    New,
+    /// The certificate was already in the store, and the new copy was merged into it.
+    Merged,
+}
+
+/// A set of components with the same key ID, but different fingerprints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyIdCollision {
+    pub key_id: KeyId,
+    pub fingerprints: Vec<Fingerprint>,
+}
+
+/// An in-memory store of certificates (or secret keys).
+///
+/// Certificates are identified by the fingerprint of their primary key, inserting a certificate
+/// that is already in the store merges the two copies.
+///
+/// Lookups are indexed by the fingerprints and key IDs of primary keys and subkeys, by user ID
+/// and by the (normalized) email address in a user ID.
+/// Lookups do not check the validity of certificates or components, use
+/// [`SignedPublicKey::validity`] on the results where this matters.
+#[derive(Debug, Clone)]
+pub struct CertStore<C = SignedPublicKey> {
+    certs: Vec<C>,
+    by_primary: HashMap<Fingerprint, usize>,
+    by_fingerprint: HashMap<Fingerprint, Vec<usize>>,
+    by_key_id: HashMap<KeyId, Vec<(usize, Fingerprint)>>,
+    by_user_id: HashMap<Vec<u8>, Vec<usize>>,
+    by_email: HashMap<String, Vec<usize>>,
+}
+
+impl<C> Default for CertStore<C> {
+    fn default() -> Self {
+        CertStore {
+            certs: Vec::new(),
+            by_primary: HashMap::new(),
+            by_fingerprint: HashMap::new(),
+            by_key_id: HashMap::new(),
+            by_user_id: HashMap::new(),
+            by_email: HashMap::new(),
+        }
+    }
+}
+
+impl<C: StoreEntry> CertStore<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a certificate, merging it with an existing copy of the same certificate.
+    pub fn insert(&mut self, cert: C) -> Result<Inserted> {
+        let fingerprint = cert.primary_key().fingerprint();
+
+        match self.by_primary.get(&fingerprint) {
+            Some(&i) => {
+                let existing = self.certs[i].clone();
+                let merged = existing.merge_entry(cert)?;
+                self.unindex(i);
+                self.certs[i] = merged;
+                self.index(i);
+
+                Ok(Inserted::Merged)
+            }
+            None => {
+                self.certs.push(cert);
+                self.index(self.certs.len() - 1);
+
+                Ok(Inserted::New)
+            }
+        }
+    }
+
+    /// Removes the certificate with the given primary key fingerprint.
+    pub fn remove(&mut self, fingerprint: &Fingerprint) -> Option<C> {
+        let i = *self.by_primary.get(fingerprint)?;
+        let last = self.certs.len() - 1;
+
+        self.unindex(i);
+        if i != last {
+            self.unindex(last);
+        }
+        let cert = self.certs.swap_remove(i);
+        if i != last {
+            self.index(i);
+        }
+
+        Some(cert)
+    }
+
+    /// The number of certificates in the store.
+    pub fn len(&self) -> usize {
+        self.certs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.certs.is_empty()
+    }
+
+    /// Iterates over all certificates, in order of insertion (unless some were removed).
+    pub fn iter(&self) -> impl Iterator<Item = &C> {
+        self.certs.iter()
+    }
+
+    /// Returns the certificate with the given primary key fingerprint.
+    pub fn get(&self, fingerprint: &Fingerprint) -> Option<&C> {
+        self.by_primary.get(fingerprint).map(|&i| &self.certs[i])
+    }
+
+    /// Returns all certificates that have a primary key or subkey with the given fingerprint.
+    pub fn lookup_by_fingerprint(&self, fingerprint: &Fingerprint) -> Vec<&C> {
+        self.resolve(self.by_fingerprint.get(fingerprint).into_iter().flatten())
+    }
+
+    /// Returns all certificates that have a primary key or subkey with the given key ID.
+    ///
+    /// Key IDs are not unique, see [`Self::key_id_collisions`].
+    pub fn lookup_by_key_id(&self, key_id: &KeyId) -> Vec<&C> {
+        self.resolve(
+            self.by_key_id
+                .get(key_id)
+                .into_iter()
+                .flatten()
+                .map(|(i, _)| i),
+        )
+    }
+
+    /// Returns all certificates with exactly the given user ID.
+    pub fn lookup_by_user_id(&self, user_id: &[u8]) -> Vec<&C> {
+        self.resolve(self.by_user_id.get(user_id).into_iter().flatten())
+    }
+
+    /// Returns all certificates with a user ID that contains the given email address.
+    ///
+    /// Email addresses are compared case insensitively. `email` may also be a full user ID,
+    /// in which case its email address is used.
+    pub fn lookup_by_email(&self, email: &str) -> Vec<&C> {
+        let Some(email) = normalize_email(email) else {
+            return Vec::new();
+        };
+        self.resolve(self.by_email.get(&email).into_iter().flatten())
+    }
+
+    /// Returns the certificates and component keys that may have issued the given signature.
+    ///
+    /// The issuer fingerprint subpackets are used if present, falling back to the issuer key IDs.
+    /// The signature itself is not verified.
+    pub fn lookup_by_issuer(&self, signature: &Signature) -> Vec<(&C, &dyn PublicKeyTrait)> {
+        let fingerprints = signature.issuer_fingerprint();
+        let key_ids = signature.issuer();
+
+        let certs = if fingerprints.is_empty() {
+            key_ids
+                .iter()
+                .flat_map(|id| self.lookup_by_key_id(id))
+                .collect::<Vec<_>>()
+        } else {
+            fingerprints
+                .iter()
+                .flat_map(|fp| self.lookup_by_fingerprint(fp))
+                .collect::<Vec<_>>()
+        };
+
+        let mut seen = HashSet::new();
+        let mut out = Vec::new();
+        for cert in certs {
+            for key in std::iter::once(cert.primary_key()).chain(cert.subkeys()) {
+                let matches = if fingerprints.is_empty() {
+                    key_ids.contains(&&key.key_id())
+                } else {
+                    fingerprints.contains(&&key.fingerprint())
+                };
+                if matches && seen.insert(key.fingerprint()) {
+                    out.push((cert, key));
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Returns all key IDs that are shared by components with different fingerprints.
+    pub fn key_id_collisions(&self) -> Vec<KeyIdCollision> {
+        let mut collisions: Vec<_> = self
+            .by_key_id
+            .iter()
+            .filter_map(|(key_id, entries)| {
+                let mut fingerprints: Vec<Fingerprint> = Vec::new();
+                for (_, fp) in entries {
+                    if !fingerprints.contains(fp) {
+                        fingerprints.push(fp.clone());
+                    }
+                }
+                (fingerprints.len() > 1).then_some(KeyIdCollision {
+                    key_id: *key_id,
+                    fingerprints,
+                })
+            })
+            .collect();
+        collisions.sort_by_key(|c| c.key_id.to_string());
+
+        collisions
+    }
+
+    /// Maps certificate indices to certificates, without duplicates and in store order.
+    fn resolve<'a>(&self, indices: impl Iterator<Item = &'a usize>) -> Vec<&C> {
+        let mut indices: Vec<usize> = indices.copied().collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        indices.into_iter().map(|i| &self.certs[i]).collect()
+    }
+
+    fn index(&mut self, i: usize) {
+        let cert = &self.certs[i];
+
+        self.by_primary.insert(cert.primary_key().fingerprint(), i);
+
+        for key in std::iter::once(cert.primary_key()).chain(cert.subkeys()) {
+            let fingerprint = key.fingerprint();
+            let key_id = key.key_id();
+
+            let entries = self.by_key_id.entry(key_id).or_default();
+            if entries.iter().any(|(_, fp)| fp != &fingerprint) {
+                warn!("key ID collision for {key_id}: {fingerprint}");
+            }
+            entries.push((i, fingerprint.clone()));
+
+            self.by_fingerprint.entry(fingerprint).or_default().push(i);
+        }
+
+        for user_id in cert.user_ids() {
+            self.by_user_id
+                .entry(user_id.id().to_vec())
+                .or_default()
+                .push(i);
+
+            if let Some(email) = user_id.as_str().and_then(normalize_email) {
+                self.by_email.entry(email).or_default().push(i);
+            }
+        }
+    }
+
+    fn unindex(&mut self, i: usize) {
+        let cert = &self.certs[i];
+
+        self.by_primary.remove(&cert.primary_key().fingerprint());
+
+        for key in std::iter::once(cert.primary_key()).chain(cert.subkeys()) {
+            remove_index(&mut self.by_fingerprint, &key.fingerprint(), |&j| j == i);
+            remove_index(&mut self.by_key_id, &key.key_id(), |(j, _)| *j == i);
+        }
+
+        for user_id in cert.user_ids() {
+            remove_index(&mut self.by_user_id, user_id.id(), |&j| j == i);
+            if let Some(email) = user_id.as_str().and_then(normalize_email) {
+                remove_index(&mut self.by_email, &email, |&j| j == i);
+            }
+        }
+    }
+}
+
+impl CertStore<SignedSecretKey> {
+    /// Returns the secret keys that the given ESKs may be encrypted to, to be used
+    /// in [`TheRing::secret_keys`](crate::composed::TheRing::secret_keys).
+    ///
+    /// For anonymous recipients, all secret keys are returned.
+    pub fn decryption_keys(&self, esk: &[Esk]) -> Vec<&SignedSecretKey> {
+        let mut indices = Vec::new();
+        for esk in esk {
+            let Esk::PublicKeyEncryptedSessionKey(pkesk) = esk else {
+                continue;
+            };
+
+            match (pkesk.id(), pkesk.fingerprint()) {
+                (Ok(id), _) if !id.is_wildcard() => {
+                    indices.extend(self.by_key_id.get(id).into_iter().flatten().map(|(i, _)| i))
+                }
+                (_, Ok(Some(fp))) => {
+                    indices.extend(self.by_fingerprint.get(fp).into_iter().flatten())
+                }
+                // anonymous recipient
+                _ => return self.certs.iter().collect(),
+            }
+        }
+
+        self.resolve(indices.iter())
+    }
+}
+
+fn remove_index<K, Q, V>(map: &mut HashMap<K, Vec<V>>, key: &Q, matches: impl Fn(&V) -> bool)
+where
+    K: std::borrow::Borrow<Q> + std::hash::Hash + Eq,
+    Q: std::hash::Hash + Eq + ?Sized,
+{
+    if let Some(entries) = map.get_mut(key) {
+        entries.retain(|v| !matches(v));
+        if entries.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+/// Extracts the email address from a user ID, and normalizes it to lower case.
+///
+/// Handles both the conventional `Name <email>` form and bare email addresses.
+pub(crate) fn normalize_email(user_id: &str) -> Option<String> {
+    let email = match (user_id.rfind('<'), user_id.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &user_id[start + 1..end],
+        _ => user_id,
+    }
+    .trim();
+
+    let (local, domain) = email.split_once('@')?;
+    if local.is_empty()
+        || domain.is_empty()
+        || domain.contains('@')
+        || email.contains(char::is_whitespace)
+    {
+        return None;
+    }
+
+    Some(email.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        composed::{KeyType, Message, MessageBuilder, RecipientSelection, TheRing},
+        crypto::sym::SymmetricKeyAlgorithm,
+        types::{KeyDetails, KeyVersion, Password},
+        util::test::{gen_key, generate, key_params, TestSubkey},
+    };
+
+    #[test]
+    fn lookups() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let subkeys = [TestSubkey::Encrypt(KeyType::X25519)];
+        let mut params = key_params(KeyVersion::V4, KeyType::Ed25519, &subkeys);
+        params.primary_user_id("Alice <Alice@Example.org>".into());
+        let alice = generate(&mut rng, &params, &Password::empty()).signed_public_key();
+        let mut params = key_params(KeyVersion::V6, KeyType::Ed25519, &subkeys);
+        params.primary_user_id("bob@example.org".into());
+        let bob = generate(&mut rng, &params, &Password::empty()).signed_public_key();
+
+        let mut store = CertStore::new();
+        assert_eq!(store.insert(alice.clone()).unwrap(), Inserted::New);
+        assert_eq!(store.insert(bob.clone()).unwrap(), Inserted::New);
+        assert_eq!(store.len(), 2);
+
+        assert_eq!(store.get(&alice.fingerprint()), Some(&alice));
+        assert_eq!(store.get(&alice.public_subkeys[0].fingerprint()), None);
+
+        let subkey = &bob.public_subkeys[0];
+        assert_eq!(store.lookup_by_fingerprint(&subkey.fingerprint()), [&bob]);
+        assert_eq!(store.lookup_by_key_id(&subkey.key_id()), [&bob]);
+        assert_eq!(store.lookup_by_key_id(&alice.key_id()), [&alice]);
+
+        assert_eq!(store.lookup_by_email("alice@example.org"), [&alice]);
+        assert_eq!(store.lookup_by_email("Bob <BOB@example.org>"), [&bob]);
+        assert!(store.lookup_by_email("carol@example.org").is_empty());
+        assert_eq!(store.lookup_by_user_id(b"bob@example.org"), [&bob]);
+        assert!(store.lookup_by_user_id(b"Bob@example.org").is_empty());
+
+        assert!(store.key_id_collisions().is_empty());
+    }
+
+    #[test]
+    fn insert_merges_and_remove() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let subkeys = [TestSubkey::Encrypt(KeyType::X25519)];
+        let mut key = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519, &subkeys);
+        let alice = key.signed_public_key();
+        let mut params = key_params(KeyVersion::V6, KeyType::Ed25519, &subkeys);
+        params.primary_user_id("Bob <bob@example.org>".into());
+        let bob = generate(&mut rng, &params, &Password::empty()).signed_public_key();
+
+        let mut store = CertStore::new();
+        store.insert(alice.clone()).unwrap();
+        store.insert(bob.clone()).unwrap();
+
+        // an update with a new user ID
+        key.add_user_id(
+            &mut rng,
+            &Password::empty(),
+            UserId::from_str(Default::default(), "Alice <alice@work.example>").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            store.insert(key.signed_public_key()).unwrap(),
+            Inserted::Merged
+        );
+        assert_eq!(store.len(), 2);
+        assert_eq!(
+            store.lookup_by_email("alice@work.example")[0]
+                .details
+                .users
+                .len(),
+            2
+        );
+
+        assert_eq!(
+            store.remove(&alice.fingerprint()).unwrap().fingerprint(),
+            alice.fingerprint()
+        );
+        assert!(store.remove(&alice.fingerprint()).is_none());
+        assert!(store.lookup_by_email("alice@work.example").is_empty());
+        assert!(store.lookup_by_key_id(&alice.key_id()).is_empty());
+        assert_eq!(store.lookup_by_email("bob@example.org"), [&bob]);
+        assert_eq!(store.iter().collect::<Vec<_>>(), [&bob]);
+    }
+
+    #[test]
+    fn key_id_collisions() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let alice = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519, &[]).signed_public_key();
+        let bob = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519, &[]).signed_public_key();
+
+        let mut store = CertStore::new();
+        store.insert(alice.clone()).unwrap();
+        store.insert(bob.clone()).unwrap();
+
+        // key IDs are too short to produce real collisions in a test, so fake one
+        store
+            .by_key_id
+            .get_mut(&alice.key_id())
+            .unwrap()
+            .push((1, bob.fingerprint()));
+
+        assert_eq!(
+            store.key_id_collisions(),
+            [KeyIdCollision {
+                key_id: alice.key_id(),
+                fingerprints: vec![alice.fingerprint(), bob.fingerprint()],
+            }]
+        );
+        assert_eq!(store.lookup_by_key_id(&alice.key_id()), [&alice, &bob]);
+    }
+
+    #[test]
+    fn verify_and_decrypt_with_store() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let alice = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519, &[]);
+        let bob = gen_key(
+            &mut rng,
+            KeyVersion::V6,
+            KeyType::Ed25519,
+            &[TestSubkey::Encrypt(KeyType::X25519)],
+        );
+
+        let mut certs = CertStore::new();
+        certs.insert(alice.signed_public_key()).unwrap();
+        let mut keys = CertStore::new();
+        keys.insert(alice.clone()).unwrap();
+        keys.insert(bob.clone()).unwrap();
+
+        // signed by alice, encrypted to bob
+        let bob_cert = bob.signed_public_key();
+        let recipients = RecipientSelection::select([&bob_cert]).unwrap();
+        let mut builder = MessageBuilder::from_bytes("", &b"hello"[..])
+            .seipd_v1(&mut rng, SymmetricKeyAlgorithm::AES128);
+        builder.sign_with_key(&alice, Password::empty()).unwrap();
+        builder
+            .encrypt_to_recipients(&mut rng, &recipients)
+            .unwrap();
+        let encrypted = builder.to_vec(&mut rng).unwrap();
+
+        let message = Message::from_bytes(&encrypted[..]).unwrap();
+        let Message::Encrypted { esk, .. } = &message else {
+            panic!("not encrypted");
+        };
+        let secret_keys = keys.decryption_keys(esk);
+        assert_eq!(secret_keys, [&bob]);
+
+        let key_pw = Password::empty();
+        let ring = TheRing {
+            secret_keys,
+            key_passwords: vec![&key_pw],
+            ..Default::default()
+        };
+        let (mut decrypted, _) = message.decrypt_the_ring(ring, true).unwrap();
+        assert_eq!(decrypted.as_data_vec().unwrap(), b"hello");
+
+        let (signer, _) = decrypted.verify_with_store(&certs).unwrap();
+        assert_eq!(signer.fingerprint(), alice.fingerprint());
+        assert!(decrypted
+            .verify_with_store(&CertStore::<SignedPublicKey>::new())
+            .is_err());
+    }
+}
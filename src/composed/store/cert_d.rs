@@ -0,0 +1,452 @@
+//! The shared OpenPGP certificate directory.
+//!
+//! See <https://datatracker.ietf.org/doc/draft-nwjw-openpgp-cert-d/>
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    composed::{CertStore, Deserializable, SignedPublicKey, SignedSecretKey},
+    errors::{bail, ensure, Result},
+    ser::Serialize,
+    types::{Fingerprint, KeyDetails, KeyVersion},
+};
+
+/// The special name of the trust root.
+const TRUST_ROOT: &str = "trust-root";
+
+/// The file that writers lock.
+const WRITE_LOCK: &str = "writelock";
+
+/// Identifies the state of a file in the certificate directory.
+///
+/// Tags are cheap to compute (they are derived from file metadata), and change whenever the
+/// file is updated. Compare a stored tag with [`CertD::tag`] to check if a cached copy of a
+/// certificate is still current.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CertDTag {
+    len: u64,
+    modified: Option<SystemTime>,
+    #[cfg(unix)]
+    inode: u64,
+}
+
+impl CertDTag {
+    fn from_metadata(metadata: &fs::Metadata) -> Self {
+        #[cfg(unix)]
+        use std::os::unix::fs::MetadataExt;
+
+        CertDTag {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            #[cfg(unix)]
+            inode: metadata.ino(),
+        }
+    }
+}
+
+/// A certificate directory in the shared `pgp.cert.d` format.
+///
+/// Certificates are stored in binary form, in files named after their fingerprint, sharded
+/// by the first byte.
+/// Reads don't need any coordination, writers take an exclusive lock on the store, merge the
+/// new certificate with the stored one, and atomically replace the file.
+#[derive(Debug, Clone)]
+pub struct CertD {
+    base: PathBuf,
+}
+
+impl CertD {
+    /// Opens the certificate directory at `base`.
+    ///
+    /// The directory is created on the first write, if it does not exist.
+    pub fn open(base: impl Into<PathBuf>) -> Self {
+        CertD { base: base.into() }
+    }
+
+    /// The location of the default certificate directory.
+    ///
+    /// This is `$PGP_CERT_D` if set, otherwise the platform specific default location.
+    pub fn default_location() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("PGP_CERT_D") {
+            return Some(path.into());
+        }
+
+        let data_dir = if cfg!(windows) {
+            std::env::var_os("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join("Library/Application Support"))
+        } else {
+            std::env::var_os("XDG_DATA_HOME")
+                .map(PathBuf::from)
+                .or_else(|| {
+                    std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share"))
+                })
+        };
+
+        data_dir.map(|dir| dir.join("pgp.cert.d"))
+    }
+
+    /// The base directory of this store.
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// The path of the file for the certificate with the given fingerprint.
+    pub fn path(&self, fingerprint: &Fingerprint) -> PathBuf {
+        let hex = hex::encode(fingerprint.as_bytes());
+        self.base.join(&hex[..2]).join(&hex[2..])
+    }
+
+    /// The current tag of the certificate with the given fingerprint, if it is stored.
+    pub fn tag(&self, fingerprint: &Fingerprint) -> Result<Option<CertDTag>> {
+        file_tag(&self.path(fingerprint))
+    }
+
+    /// Reads the certificate with the given fingerprint.
+    pub fn get(&self, fingerprint: &Fingerprint) -> Result<Option<(CertDTag, SignedPublicKey)>> {
+        let Some((tag, bytes)) = read(&self.path(fingerprint))? else {
+            return Ok(None);
+        };
+        let cert = SignedPublicKey::from_bytes(&bytes[..])?;
+        ensure!(
+            &cert.fingerprint() == fingerprint,
+            "stored certificate has fingerprint {}, expected {}",
+            cert.fingerprint(),
+            fingerprint
+        );
+
+        Ok(Some((tag, cert)))
+    }
+
+    /// Reads the certificate with the given fingerprint, unless its tag is still `tag`.
+    pub fn get_if_changed(
+        &self,
+        fingerprint: &Fingerprint,
+        tag: &CertDTag,
+    ) -> Result<Option<(CertDTag, SignedPublicKey)>> {
+        if self.tag(fingerprint)?.as_ref() == Some(tag) {
+            return Ok(None);
+        }
+        self.get(fingerprint)
+    }
+
+    /// Inserts a certificate, merging it with the stored copy (if any).
+    ///
+    /// Returns the tag of the updated file.
+    pub fn insert(&self, cert: &SignedPublicKey) -> Result<CertDTag> {
+        let path = self.path(&cert.fingerprint());
+
+        self.with_lock(|| {
+            let merged = match self.get(&cert.fingerprint())? {
+                Some((_, stored)) => stored.merge(cert.clone())?,
+                None => cert.clone(),
+            };
+            write_atomic(&path, &merged.to_bytes()?)
+        })
+    }
+
+    /// The current tag of the trust root, if there is one.
+    pub fn trust_root_tag(&self) -> Result<Option<CertDTag>> {
+        file_tag(&self.base.join(TRUST_ROOT))
+    }
+
+    /// Reads the trust root.
+    ///
+    /// If the trust root is stored with its secret key material, only the public certificate
+    /// is returned, see [`Self::trust_root_secret_key`].
+    pub fn trust_root(&self) -> Result<Option<(CertDTag, SignedPublicKey)>> {
+        let Some((tag, bytes)) = read(&self.base.join(TRUST_ROOT))? else {
+            return Ok(None);
+        };
+        let cert = match SignedPublicKey::from_bytes(&bytes[..]) {
+            Ok(cert) => cert,
+            Err(_) => SignedSecretKey::from_bytes(&bytes[..])?.signed_public_key(),
+        };
+
+        Ok(Some((tag, cert)))
+    }
+
+    /// Reads the trust root, including its secret key material.
+    ///
+    /// Fails if the trust root is stored without secret key material.
+    pub fn trust_root_secret_key(&self) -> Result<Option<(CertDTag, SignedSecretKey)>> {
+        let Some((tag, bytes)) = read(&self.base.join(TRUST_ROOT))? else {
+            return Ok(None);
+        };
+
+        Ok(Some((tag, SignedSecretKey::from_bytes(&bytes[..])?)))
+    }
+
+    /// Replaces the trust root with the given certificate.
+    pub fn set_trust_root(&self, cert: &SignedPublicKey) -> Result<CertDTag> {
+        let bytes = cert.to_bytes()?;
+        self.with_lock(|| write_atomic(&self.base.join(TRUST_ROOT), &bytes))
+    }
+
+    /// Replaces the trust root with the given key, including its secret key material.
+    pub fn set_trust_root_secret_key(&self, key: &SignedSecretKey) -> Result<CertDTag> {
+        let bytes = key.to_bytes()?;
+        self.with_lock(|| write_atomic(&self.base.join(TRUST_ROOT), &bytes))
+    }
+
+    /// Lists the fingerprints of all stored certificates.
+    ///
+    /// Files that are not named like certificates are ignored.
+    pub fn fingerprints(&self) -> Result<Vec<Fingerprint>> {
+        let mut fingerprints = Vec::new();
+
+        let dirs = match fs::read_dir(&self.base) {
+            Ok(dirs) => dirs,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(fingerprints),
+            Err(err) => return Err(err.into()),
+        };
+        for dir in dirs {
+            let dir = dir?;
+            let prefix = dir.file_name();
+            let Some(prefix) = prefix.to_str().filter(|p| is_lower_hex(p, 2)) else {
+                continue;
+            };
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+
+            for file in fs::read_dir(dir.path())? {
+                let name = file?.file_name();
+                let Some(rest) = name.to_str() else {
+                    continue;
+                };
+                let version = match rest.len() {
+                    38 => KeyVersion::V4,
+                    62 => KeyVersion::V6,
+                    _ => continue,
+                };
+                if !is_lower_hex(rest, rest.len()) {
+                    continue;
+                }
+
+                let Ok(bytes) = hex::decode(format!("{prefix}{rest}")) else {
+                    continue;
+                };
+                fingerprints.push(Fingerprint::new(version, &bytes)?);
+            }
+        }
+
+        Ok(fingerprints)
+    }
+
+    /// Reads all certificates into an in-memory store.
+    pub fn load(&self) -> Result<CertStore> {
+        let mut store = CertStore::new();
+        for fingerprint in self.fingerprints()? {
+            if let Some((_, cert)) = self.get(&fingerprint)? {
+                store.insert(cert)?;
+            }
+        }
+
+        Ok(store)
+    }
+
+    /// Runs `f` while holding the write lock of the store.
+    fn with_lock<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        fs::create_dir_all(&self.base)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.base.join(WRITE_LOCK))?;
+        let mut lock = fd_lock::RwLock::new(file);
+        let _guard = lock.write()?;
+
+        f()
+    }
+}
+
+fn is_lower_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn file_tag(path: &Path) -> Result<Option<CertDTag>> {
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(Some(CertDTag::from_metadata(&metadata))),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Reads a file, together with its tag.
+fn read(path: &Path) -> Result<Option<(CertDTag, Vec<u8>)>> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    // The tag is taken from the opened file, files are only ever replaced, not modified.
+    let tag = CertDTag::from_metadata(&file.metadata()?);
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    Ok(Some((tag, bytes)))
+}
+
+/// Replaces the file at `path` with `bytes`, by writing to a temporary file and renaming it.
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<CertDTag> {
+    let Some(dir) = path.parent() else {
+        bail!("invalid path {}", path.display());
+    };
+    fs::create_dir_all(dir)?;
+
+    let Some(name) = path.file_name() else {
+        bail!("invalid path {}", path.display());
+    };
+    let mut tmp_name = name.to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = dir.join(tmp_name);
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp, path)?;
+
+    file_tag(path)?.ok_or_else(|| io::Error::from(io::ErrorKind::NotFound).into())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        composed::KeyType,
+        packet::UserId,
+        types::{PacketHeaderVersion, Password},
+        util::test::gen_key,
+    };
+
+    fn with_user_id(key: &SignedSecretKey, seed: u64, id: &str) -> SignedPublicKey {
+        let mut key = key.clone();
+        key.add_user_id(
+            ChaCha8Rng::seed_from_u64(seed),
+            &Password::empty(),
+            UserId::from_str(PacketHeaderVersion::New, id).unwrap(),
+        )
+        .unwrap();
+        key.signed_public_key()
+    }
+
+    #[test]
+    fn insert_and_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let certd = CertD::open(dir.path().join("pgp.cert.d"));
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let v4 = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519, &[]);
+        let v6 = gen_key(&mut rng, KeyVersion::V6, KeyType::Ed25519, &[]);
+        assert!(certd.get(&v4.fingerprint()).unwrap().is_none());
+        assert!(certd.fingerprints().unwrap().is_empty());
+
+        let tag = certd.insert(&v4.signed_public_key()).unwrap();
+        certd.insert(&v6.signed_public_key()).unwrap();
+
+        // the file layout is shared with other implementations
+        let hex = hex::encode(v4.fingerprint().as_bytes());
+        let path = dir
+            .path()
+            .join("pgp.cert.d")
+            .join(&hex[..2])
+            .join(&hex[2..]);
+        assert_eq!(certd.path(&v4.fingerprint()), path);
+        assert_eq!(
+            fs::read(&path).unwrap(),
+            v4.signed_public_key().to_bytes().unwrap()
+        );
+
+        let (read_tag, cert) = certd.get(&v4.fingerprint()).unwrap().unwrap();
+        assert_eq!(read_tag, tag);
+        assert_eq!(cert, v4.signed_public_key());
+        assert!(certd
+            .get_if_changed(&v4.fingerprint(), &tag)
+            .unwrap()
+            .is_none());
+
+        // updates are merged, and change the tag
+        let update = with_user_id(&v4, 2, "Alice <alice@work.example>");
+        let new_tag = certd.insert(&update).unwrap();
+        assert_ne!(new_tag, tag);
+        let (_, cert) = certd
+            .get_if_changed(&v4.fingerprint(), &tag)
+            .unwrap()
+            .unwrap();
+        assert_eq!(cert.details.users.len(), 2);
+
+        let mut fingerprints = certd.fingerprints().unwrap();
+        fingerprints.sort_by_key(|fp| fp.to_string());
+        let mut expected = vec![v4.fingerprint(), v6.fingerprint()];
+        expected.sort_by_key(|fp| fp.to_string());
+        assert_eq!(fingerprints, expected);
+
+        let store = certd.load().unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.lookup_by_email("alice@work.example"), [&cert]);
+    }
+
+    #[test]
+    fn trust_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let certd = CertD::open(dir.path());
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let key = gen_key(&mut rng, KeyVersion::V6, KeyType::Ed25519, &[]);
+
+        assert!(certd.trust_root().unwrap().is_none());
+
+        let tag = certd.set_trust_root(&key.signed_public_key()).unwrap();
+        let (read_tag, cert) = certd.trust_root().unwrap().unwrap();
+        assert_eq!(read_tag, tag);
+        assert_eq!(cert, key.signed_public_key());
+        assert!(certd.trust_root_secret_key().is_err());
+
+        certd.set_trust_root_secret_key(&key).unwrap();
+        assert_eq!(
+            certd.trust_root().unwrap().unwrap().1,
+            key.signed_public_key()
+        );
+        assert_eq!(certd.trust_root_secret_key().unwrap().unwrap().1, key);
+
+        // the trust root is not a regular certificate
+        assert!(certd.fingerprints().unwrap().is_empty());
+    }
+
+    #[test]
+    fn concurrent_inserts() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let key = gen_key(&mut rng, KeyVersion::V4, KeyType::Ed25519, &[]);
+        let updates: Vec<_> = (0..4)
+            .map(|i| with_user_id(&key, i, &format!("Alice <alice{i}@example.org>")))
+            .collect();
+
+        std::thread::scope(|s| {
+            for update in &updates {
+                let certd = CertD::open(dir.path());
+                s.spawn(move || certd.insert(update).unwrap());
+            }
+        });
+
+        let (_, cert) = CertD::open(dir.path())
+            .get(&key.fingerprint())
+            .unwrap()
+            .unwrap();
+        assert_eq!(cert.details.users.len(), 5);
+        cert.verify().unwrap();
+    }
+}
@@ -0,0 +1,541 @@
+//! Reading of GnuPG keyrings: keybox files (`pubring.kbx`) and legacy keyrings
+//! (`pubring.gpg`, `secring.gpg`).
+//!
+//! Ref: <https://github.com/gpg/gnupg/blob/master/kbx/keybox-blob.c>
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    marker::PhantomData,
+    path::Path,
+};
+
+use bytes::Bytes;
+use chrono::{DateTime, TimeZone, Utc};
+use log::warn;
+use sha1::{Digest, Sha1};
+
+use crate::{
+    composed::{Deserializable, SignedPublicKey},
+    errors::{ensure, ensure_eq, format_err, Result},
+    packet::{GnupgTrust, Packet, PacketParser, TrustValue, UserId},
+    parsing_reader::BufReadParsing,
+    types::{Fingerprint, KeyDetails},
+};
+
+/// Magic of the keybox header blob.
+const KEYBOX_MAGIC: &[u8; 4] = b"KBXf";
+
+/// Keybox blob types.
+const BLOB_TYPE_EMPTY: u8 = 0;
+const BLOB_TYPE_HEADER: u8 = 1;
+const BLOB_TYPE_OPENPGP: u8 = 2;
+const BLOB_TYPE_X509: u8 = 3;
+
+/// Size of the SHA-1 checksum at the end of each blob.
+const BLOB_CHECKSUM_LEN: usize = 20;
+
+/// Upper limit on the size of a single blob, as enforced by GnuPG.
+const MAX_BLOB_LEN: usize = 5 * 1024 * 1024;
+
+/// A key read from a GnuPG keyring, together with the information GnuPG stored alongside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyringEntry<K> {
+    pub key: K,
+    /// The trust packet following the primary key.
+    pub key_trust: Option<GnupgTrust>,
+    /// The trust packets following the user IDs.
+    pub user_id_trust: Vec<(UserId, GnupgTrust)>,
+    /// The trust packets following subkeys.
+    pub subkey_trust: Vec<(Fingerprint, GnupgTrust)>,
+    /// Metadata of the keybox blob, only set for keys read from a [`Keybox`].
+    pub keybox: Option<KeyboxMetadata>,
+}
+
+/// Metadata GnuPG stores for each key in a keybox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyboxMetadata {
+    /// The blob flags. Bit 1 marks ephemeral keys, which GnuPG does not list.
+    pub flags: u16,
+    pub ownertrust: TrustValue,
+    /// The validity of the key, taking all user IDs into account.
+    pub all_validity: TrustValue,
+    pub user_ids: Vec<KeyboxUserId>,
+    /// Cached signature verification results, one per signature in the keyblock.
+    ///
+    /// `0` means not checked, `1` missing key, `2` bad signature, `0xffffffff` a valid
+    /// signature without expiration, otherwise the expiration time of a valid signature.
+    pub signature_expirations: Vec<u32>,
+    /// When the validity should be recalculated.
+    pub recheck_after: Option<DateTime<Utc>>,
+    /// The newest creation time of a self signature.
+    pub latest_timestamp: Option<DateTime<Utc>>,
+    /// When the key was added to the keybox.
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Per user ID metadata in a keybox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyboxUserId {
+    /// The raw user ID, or the raw user attribute.
+    pub id: Bytes,
+    pub flags: u16,
+    pub validity: TrustValue,
+}
+
+/// Reads keys from a legacy GnuPG keyring, such as `pubring.gpg` or `secring.gpg`.
+///
+/// This is a list of transferable keys, interspersed with [`Trust`](crate::packet::Trust)
+/// packets, which are decoded and returned in the [`KeyringEntry`].
+pub struct Keyring<'a, K> {
+    packets: std::iter::Peekable<Box<dyn Iterator<Item = Result<Packet>> + 'a>>,
+    _key: PhantomData<K>,
+}
+
+impl<'a, K: Deserializable> Keyring<'a, K> {
+    /// Reads a keyring from the binary data in `input`.
+    pub fn from_reader<R: BufRead + 'a>(input: R) -> Self {
+        let packets: Box<dyn Iterator<Item = Result<Packet>> + 'a> =
+            Box::new(PacketParser::new(input).filter_map(|p| match p {
+                Ok(Packet::Trust(_)) => Some(p),
+                p => crate::composed::shared::filter_parsed_packet_results(p),
+            }));
+
+        Keyring {
+            packets: packets.peekable(),
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<K: Deserializable> Keyring<'static, K> {
+    /// Reads a keyring from the file at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self::from_reader(BufReader::new(file)))
+    }
+}
+
+impl<K: Deserializable> Iterator for Keyring<'_, K> {
+    type Item = Result<KeyringEntry<K>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut block = match self.packets.next()? {
+            Ok(packet) => vec![packet],
+            Err(err) => return Some(Err(err)),
+        };
+
+        while let Some(Ok(packet)) = self
+            .packets
+            .next_if(|p| matches!(p, Ok(p) if !is_primary_key(p)))
+        {
+            block.push(packet);
+        }
+
+        Some(parse_keyblock(block))
+    }
+}
+
+/// Reads the keys from a GnuPG keybox, such as `pubring.kbx`.
+///
+/// X.509 certificates and empty blobs are skipped.
+pub struct Keybox<R> {
+    input: R,
+}
+
+impl<R: BufRead> Keybox<R> {
+    /// Reads a keybox from `input`, checking the header blob.
+    pub fn from_reader(mut input: R) -> Result<Self> {
+        let header = read_blob(&mut input)?
+            .ok_or_else(|| format_err!("keybox is missing the header blob"))?;
+        ensure!(header.len() >= 12, "keybox header blob too short");
+        ensure_eq!(
+            header[4],
+            BLOB_TYPE_HEADER,
+            "invalid keybox header blob type"
+        );
+        ensure_eq!(&header[8..12], KEYBOX_MAGIC, "invalid keybox magic");
+
+        Ok(Keybox { input })
+    }
+}
+
+impl Keybox<BufReader<File>> {
+    /// Reads a keybox from the file at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+        Self::from_reader(BufReader::new(file))
+    }
+}
+
+impl<R: BufRead> Iterator for Keybox<R> {
+    type Item = Result<KeyringEntry<SignedPublicKey>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let blob = match read_blob(&mut self.input) {
+                Ok(Some(blob)) => blob,
+                Ok(None) => return None,
+                Err(err) => return Some(Err(err)),
+            };
+
+            match parse_blob(&blob) {
+                Ok(Some(entry)) => return Some(Ok(entry)),
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+fn is_primary_key(packet: &Packet) -> bool {
+    matches!(packet, Packet::PublicKey(_) | Packet::SecretKey(_))
+}
+
+/// Reads the next blob, including its length prefix. Returns `None` at the end of the input.
+fn read_blob<R: BufRead>(input: &mut R) -> Result<Option<Bytes>> {
+    if !input.has_remaining()? {
+        return Ok(None);
+    }
+
+    let raw_len = input.read_be_u32()?;
+    let len = usize::try_from(raw_len)?;
+    ensure!(
+        (6..=MAX_BLOB_LEN).contains(&len),
+        "invalid keybox blob length {}",
+        len
+    );
+
+    let mut blob = Vec::with_capacity(len);
+    blob.extend_from_slice(&raw_len.to_be_bytes());
+    blob.extend_from_slice(&input.take_bytes(len - 4)?);
+
+    Ok(Some(blob.into()))
+}
+
+/// Parses a single blob, returning `None` for blobs that do not contain an OpenPGP key.
+fn parse_blob(blob: &Bytes) -> Result<Option<KeyringEntry<SignedPublicKey>>> {
+    let mut data = &blob[4..];
+    let typ = data.read_u8()?;
+    let version = data.read_u8()?;
+
+    match typ {
+        BLOB_TYPE_OPENPGP => {}
+        BLOB_TYPE_EMPTY | BLOB_TYPE_X509 => return Ok(None),
+        _ => {
+            warn!("skipping unknown keybox blob type {typ}");
+            return Ok(None);
+        }
+    }
+    ensure_eq!(version, 1, "unsupported keybox blob version");
+
+    ensure!(
+        blob.len() > BLOB_CHECKSUM_LEN,
+        "keybox blob too short for checksum"
+    );
+    let (content, checksum) = blob.split_at(blob.len() - BLOB_CHECKSUM_LEN);
+    // Old versions of GnuPG wrote an MD5 checksum, prefixed with 4 zero bytes.
+    if checksum[..4] != [0u8; 4] {
+        ensure!(
+            Sha1::digest(content)[..] == checksum[..],
+            "keybox blob checksum mismatch"
+        );
+    }
+
+    let flags = data.read_be_u16()?;
+    let keyblock_offset = usize::try_from(data.read_be_u32()?)?;
+    let keyblock_len = usize::try_from(data.read_be_u32()?)?;
+
+    // The key infos only hold fingerprints and key IDs, which are taken from the keyblock.
+    let nkeys = usize::from(data.read_be_u16()?);
+    let keyinfo_len = usize::from(data.read_be_u16()?);
+    data.take_bytes(nkeys * keyinfo_len)?;
+
+    let serial_len = usize::from(data.read_be_u16()?);
+    data.take_bytes(serial_len)?;
+
+    let nuids = usize::from(data.read_be_u16()?);
+    let uidinfo_len = usize::from(data.read_be_u16()?);
+    ensure!(uidinfo_len >= 12, "invalid keybox user ID info size");
+    let mut user_ids = Vec::with_capacity(nuids);
+    for _ in 0..nuids {
+        let offset = usize::try_from(data.read_be_u32()?)?;
+        let len = usize::try_from(data.read_be_u32()?)?;
+        let flags = data.read_be_u16()?;
+        let validity = data.read_u8()?.into();
+        data.take_bytes(uidinfo_len - 11)?;
+
+        let id = offset
+            .checked_add(len)
+            .and_then(|end| content.get(offset..end))
+            .ok_or_else(|| format_err!("keybox user ID out of bounds"))?;
+
+        user_ids.push(KeyboxUserId {
+            id: blob.slice_ref(id),
+            flags,
+            validity,
+        });
+    }
+
+    let nsigs = usize::from(data.read_be_u16()?);
+    let siginfo_len = usize::from(data.read_be_u16()?);
+    ensure!(siginfo_len >= 4, "invalid keybox signature info size");
+    let mut signature_expirations = Vec::with_capacity(nsigs);
+    for _ in 0..nsigs {
+        signature_expirations.push(data.read_be_u32()?);
+        data.take_bytes(siginfo_len - 4)?;
+    }
+
+    let ownertrust = data.read_u8()?.into();
+    let all_validity = data.read_u8()?.into();
+    data.read_be_u16()?;
+    let recheck_after = read_timestamp(&mut data)?;
+    let latest_timestamp = read_timestamp(&mut data)?;
+    let created_at = read_timestamp(&mut data)?;
+
+    let keyblock = keyblock_offset
+        .checked_add(keyblock_len)
+        .and_then(|end| content.get(keyblock_offset..end))
+        .ok_or_else(|| format_err!("keybox keyblock out of bounds"))?;
+
+    let packets = Keyring::<SignedPublicKey>::from_reader(keyblock)
+        .packets
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut entry = parse_keyblock(packets)?;
+    entry.keybox = Some(KeyboxMetadata {
+        flags,
+        ownertrust,
+        all_validity,
+        user_ids,
+        signature_expirations,
+        recheck_after,
+        latest_timestamp,
+        created_at,
+    });
+
+    Ok(Some(entry))
+}
+
+fn read_timestamp(data: &mut &[u8]) -> Result<Option<DateTime<Utc>>> {
+    match data.read_be_u32()? {
+        0 => Ok(None),
+        ts => Ok(Utc.timestamp_opt(i64::from(ts), 0).single()),
+    }
+}
+
+/// The last component in a keyblock that a trust packet can refer to.
+enum Component {
+    Key,
+    UserId(UserId),
+    Subkey(Fingerprint),
+    Other,
+}
+
+/// Parses the packets of a single transferable key, collecting the trust packets.
+fn parse_keyblock<K: Deserializable>(packets: Vec<Packet>) -> Result<KeyringEntry<K>> {
+    let mut key_trust = None;
+    let mut user_id_trust = Vec::new();
+    let mut subkey_trust = Vec::new();
+
+    let mut current = Component::Other;
+    let mut key_packets = Vec::with_capacity(packets.len());
+
+    for packet in packets {
+        let Packet::Trust(trust) = packet else {
+            current = match &packet {
+                Packet::PublicKey(_) | Packet::SecretKey(_) => Component::Key,
+                Packet::UserId(id) => Component::UserId(id.clone()),
+                Packet::PublicSubkey(key) => Component::Subkey(key.fingerprint()),
+                Packet::SecretSubkey(key) => Component::Subkey(key.fingerprint()),
+                _ => Component::Other,
+            };
+            key_packets.push(packet);
+            continue;
+        };
+
+        let trust = match trust.to_gnupg() {
+            Ok(trust) => trust,
+            Err(err) => {
+                warn!("skipping invalid trust packet: {err}");
+                continue;
+            }
+        };
+
+        match std::mem::replace(&mut current, Component::Other) {
+            Component::Key => key_trust = Some(trust),
+            Component::UserId(id) => user_id_trust.push((id, trust)),
+            Component::Subkey(fp) => subkey_trust.push((fp, trust)),
+            Component::Other => {}
+        }
+    }
+
+    let key = K::from_packets(key_packets.into_iter().map(Ok).peekable())
+        .next()
+        .ok_or_else(|| crate::errors::NoMatchingPacketSnafu.build())??;
+
+    Ok(KeyringEntry {
+        key,
+        key_trust,
+        user_id_trust,
+        subkey_trust,
+        keybox: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use super::*;
+    use crate::{
+        composed::SignedSecretKey,
+        packet::{KeyOriginKind, PacketHeader, SigCache, TrustLevel},
+        types::{PacketLength, Tag},
+    };
+
+    const ALICE: &str = "78F23A38A9F18F9B1C273E7E935DB45D04592ECB";
+    const BOB: &str = "FF0A90EA1E1B12DD743592F1B4E13C52E47691B9";
+
+    fn fingerprint<K: KeyDetails>(key: &K) -> String {
+        hex::encode_upper(key.fingerprint().as_bytes())
+    }
+
+    /// The offset of the body of the first packet with `tag` in `data`.
+    fn packet_body_offset(data: &[u8], tag: Tag) -> usize {
+        let mut rest = data;
+        loop {
+            let header = PacketHeader::try_from_reader(&mut rest).unwrap();
+            if header.tag() == tag {
+                return data.len() - rest.len();
+            }
+            let PacketLength::Fixed(len) = header.packet_length() else {
+                panic!("unexpected packet length {:?}", header.packet_length());
+            };
+            rest = &rest[usize::try_from(len).unwrap()..];
+        }
+    }
+
+    #[test]
+    fn read_keybox() {
+        let entries: Vec<_> = Keybox::from_file("./tests/gnupg/pubring.kbx")
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let alice = &entries[0];
+        assert_eq!(fingerprint(&alice.key), ALICE);
+        alice.key.verify().unwrap();
+        assert_eq!(alice.key.details.users.len(), 2);
+        assert_eq!(alice.key.public_subkeys.len(), 1);
+
+        let meta = alice.keybox.as_ref().unwrap();
+        assert_eq!(meta.flags, 0);
+        assert_eq!(meta.ownertrust.level, TrustLevel::Unknown);
+        assert_eq!(meta.signature_expirations, vec![0; 3]);
+        assert_eq!(&meta.user_ids[0].id[..], b"Alice <alice@example.org>");
+        assert_eq!(&meta.user_ids[1].id[..], b"Alice <alice@work.example>");
+        assert!(meta.created_at.is_some());
+
+        // the keyblock carries trust packets as well
+        let key_trust = alice.key_trust.as_ref().unwrap();
+        assert_eq!(
+            key_trust.origin.as_ref().unwrap().kind,
+            KeyOriginKind::Unknown
+        );
+        assert_eq!(alice.user_id_trust.len(), 2);
+
+        let bob = &entries[1];
+        assert_eq!(fingerprint(&bob.key), BOB);
+        assert_eq!(bob.key.details.users[0].signatures.len(), 2);
+    }
+
+    #[test]
+    fn keybox_checksum() {
+        let mut data = std::fs::read("./tests/gnupg/pubring.kbx").unwrap();
+        // the keybox starts with the header blob, followed by the blob of Alice
+        let header_len =
+            usize::try_from(u32::from_be_bytes(data[..4].try_into().unwrap())).unwrap();
+
+        // flip a bit in the first user ID of Alice
+        let user_id = b"Alice <alice@example.org>";
+        let offset = data
+            .windows(user_id.len())
+            .position(|w| w == user_id)
+            .unwrap();
+        assert!(offset > header_len);
+        data[offset] ^= 1;
+
+        let mut keybox = Keybox::from_reader(&data[..]).unwrap();
+        let err = keybox.next().unwrap().unwrap_err();
+        assert!(err.to_string().contains("checksum"), "{err}");
+        assert!(keybox.next().unwrap().is_ok());
+
+        assert!(Keybox::from_reader(&data[header_len..]).is_err());
+    }
+
+    #[test]
+    fn read_legacy_keyring() {
+        let mut data = std::fs::read("./tests/gnupg/pubring.gpg").unwrap();
+
+        let entries: Vec<_> = Keyring::<SignedPublicKey>::from_reader(&data[..])
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(fingerprint(&entries[0].key), ALICE);
+        assert_eq!(fingerprint(&entries[1].key), BOB);
+        assert!(entries.iter().all(|e| e.keybox.is_none()));
+
+        let alice = &entries[0];
+        alice.key.verify().unwrap();
+        assert_eq!(alice.key.details.users.len(), 2);
+        assert_eq!(alice.key.public_subkeys.len(), 1);
+        assert_eq!(alice.user_id_trust[1].0.id(), b"Alice <alice@work.example>");
+        assert_eq!(alice.user_id_trust[1].1.sig_cache, None);
+        assert!(alice.subkey_trust.is_empty());
+
+        // without trust packets, the keyring parses the same
+        let plain: Vec<_> = SignedPublicKey::from_bytes_many(&data[..])
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(plain.len(), 2);
+        assert_eq!(plain[0], alice.key);
+
+        // as written by old versions, with the ownertrust in the first octet of the trust
+        // packet of the primary key: fully trusted (5) and disabled (0x80)
+        let offset = packet_body_offset(&data, Tag::Trust);
+        data[offset] = 0x85;
+        let alice = Keyring::<SignedPublicKey>::from_reader(&data[..])
+            .next()
+            .unwrap()
+            .unwrap();
+        let value = alice.key_trust.unwrap().value;
+        assert_eq!(value.level, TrustLevel::Full);
+        assert!(value.disabled);
+
+        // trust packets following signatures carry the verification result
+        let trust = Keyring::<SignedPublicKey>::from_reader(&data[..])
+            .packets
+            .filter_map(|p| match p.unwrap() {
+                Packet::Trust(t) => Some(t.to_gnupg().unwrap()),
+                _ => None,
+            })
+            .nth(2)
+            .unwrap();
+        assert_eq!(
+            trust.sig_cache,
+            Some(SigCache {
+                checked: true,
+                valid: true
+            })
+        );
+
+        // a public keyring does not contain secret keys
+        assert!(Keyring::<SignedSecretKey>::from_reader(&data[..])
+            .next()
+            .unwrap()
+            .is_err());
+    }
+}
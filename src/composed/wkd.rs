@@ -0,0 +1,385 @@
+//! OpenPGP Web Key Directory: lookup paths and generation of the directory tree.
+//!
+//! Fetching keys is left to the caller.
+//!
+//! Ref: <https://datatracker.ietf.org/doc/html/draft-koch-openpgp-webkey-service>
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use sha1::{Digest, Sha1};
+
+use crate::{
+    composed::{store::normalize_email, ExportOptions, SignedPublicKey},
+    errors::{ensure, format_err, Result},
+    ser::Serialize,
+    types::KeyDetails,
+};
+
+/// The alphabet of z-base-32, as used for the hashed local part.
+const ZBASE32_ALPHABET: &[u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+/// The directory below the web root that holds the key directory.
+const WELL_KNOWN: &str = ".well-known/openpgpkey";
+
+/// The subdomain used by the advanced method.
+const ADVANCED_SUBDOMAIN: &str = "openpgpkey";
+
+/// The layout of a Web Key Directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WkdMethod {
+    /// Served from the `openpgpkey` subdomain, with a directory per domain.
+    Advanced,
+    /// Served from the domain itself.
+    Direct,
+}
+
+/// An email address, as used to look up keys in a Web Key Directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WkdAddress {
+    local_part: String,
+    domain: String,
+}
+
+impl WkdAddress {
+    /// Parses an email address.
+    pub fn new(email: &str) -> Result<Self> {
+        let (local_part, domain) = email
+            .trim()
+            .rsplit_once('@')
+            .ok_or_else(|| format_err!("invalid email address {:?}", email))?;
+        ensure!(
+            !local_part.is_empty() && !domain.is_empty(),
+            "invalid email address {:?}",
+            email
+        );
+
+        Ok(WkdAddress {
+            local_part: local_part.to_string(),
+            domain: domain.to_ascii_lowercase(),
+        })
+    }
+
+    /// The local part, as given.
+    pub fn local_part(&self) -> &str {
+        &self.local_part
+    }
+
+    /// The domain, in lower case.
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// The z-base-32 encoded SHA-1 hash of the lower cased local part.
+    pub fn hash(&self) -> String {
+        let digest = Sha1::digest(self.local_part.to_ascii_lowercase().as_bytes());
+        zbase32(&digest)
+    }
+
+    /// The path of the key file, relative to the web root.
+    pub fn path(&self, method: WkdMethod) -> PathBuf {
+        PathBuf::from(self.url_path(method))
+    }
+
+    /// The URL to fetch the key from.
+    pub fn url(&self, method: WkdMethod) -> String {
+        format!(
+            "https://{}/{}?l={}",
+            host(&self.domain, method),
+            self.url_path(method),
+            percent_encode(&self.local_part)
+        )
+    }
+
+    /// The URL of the policy file.
+    pub fn policy_url(&self, method: WkdMethod) -> String {
+        format!(
+            "https://{}/{}/policy",
+            host(&self.domain, method),
+            directory(&self.domain, method)
+        )
+    }
+
+    fn url_path(&self, method: WkdMethod) -> String {
+        format!("{}/hu/{}", directory(&self.domain, method), self.hash())
+    }
+}
+
+impl fmt::Display for WkdAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.local_part, self.domain)
+    }
+}
+
+/// Generates the files of a Web Key Directory for a single domain.
+///
+/// Each key is published for all of its user IDs in the domain, with the other
+/// user IDs removed.
+#[derive(Debug, Clone)]
+pub struct WkdGenerator {
+    domain: String,
+    method: WkdMethod,
+    policy: String,
+    /// Keys to publish, by hashed local part.
+    keys: BTreeMap<String, Vec<SignedPublicKey>>,
+}
+
+impl WkdGenerator {
+    /// Creates an empty directory for `domain`.
+    pub fn new(domain: &str, method: WkdMethod) -> Self {
+        WkdGenerator {
+            domain: domain.to_ascii_lowercase(),
+            method,
+            policy: String::new(),
+            keys: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the contents of the policy file, which is empty by default.
+    pub fn policy(&mut self, policy: impl Into<String>) -> &mut Self {
+        self.policy = policy.into();
+        self
+    }
+
+    /// Adds a key for all of its user IDs in the domain.
+    ///
+    /// Returns the addresses the key was added for.
+    pub fn insert(&mut self, key: &SignedPublicKey) -> Result<Vec<WkdAddress>> {
+        let mut addresses = Vec::new();
+        for user in &key.details.users {
+            let Some(email) = user.id.as_str().and_then(normalize_email) else {
+                continue;
+            };
+            let address = WkdAddress::new(&email)?;
+            if address.domain != self.domain || addresses.contains(&address) {
+                continue;
+            }
+
+            let published = filter_user_ids(key, &email);
+            let keys = self.keys.entry(address.hash()).or_default();
+            match keys
+                .iter_mut()
+                .find(|k| k.fingerprint() == published.fingerprint())
+            {
+                Some(existing) => *existing = existing.clone().merge(published)?,
+                None => keys.push(published),
+            }
+            addresses.push(address);
+        }
+
+        Ok(addresses)
+    }
+
+    /// Returns all files of the directory, with their paths relative to the web root.
+    pub fn files(&self) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+        let dir = directory(&self.domain, self.method);
+
+        let mut files = vec![(
+            PathBuf::from(format!("{dir}/policy")),
+            self.policy.as_bytes().to_vec(),
+        )];
+        for (hash, keys) in &self.keys {
+            let mut data = Vec::new();
+            for key in keys {
+                key.to_writer(&mut data)?;
+            }
+            files.push((PathBuf::from(format!("{dir}/hu/{hash}")), data));
+        }
+
+        Ok(files)
+    }
+
+    /// Writes the directory tree below the web root `root`.
+    pub fn write_to(&self, root: impl AsRef<Path>) -> Result<()> {
+        let root = root.as_ref();
+        for (path, data) in self.files()? {
+            let path = root.join(path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, data)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The directory holding the keys of `domain`, relative to the web root.
+fn directory(domain: &str, method: WkdMethod) -> String {
+    match method {
+        WkdMethod::Advanced => format!("{WELL_KNOWN}/{domain}"),
+        WkdMethod::Direct => WELL_KNOWN.to_string(),
+    }
+}
+
+/// The host serving the directory of `domain`.
+fn host(domain: &str, method: WkdMethod) -> String {
+    match method {
+        WkdMethod::Advanced => format!("{ADVANCED_SUBDOMAIN}.{domain}"),
+        WkdMethod::Direct => domain.to_string(),
+    }
+}
+
+/// Returns a copy of `key` with only the user IDs for `email`.
+fn filter_user_ids(key: &SignedPublicKey, email: &str) -> SignedPublicKey {
+    let options = ExportOptions {
+        drop_user_attributes: true,
+        ..Default::default()
+    };
+    key.export(&options.with_user_ids([email]))
+}
+
+fn zbase32(data: &[u8]) -> String {
+    let mut out = String::with_capacity((data.len() * 8).div_ceil(5));
+    let mut buffer = 0u16;
+    let mut bits = 0;
+
+    for byte in data {
+        buffer = (buffer << 8) | u16::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(char::from(
+                ZBASE32_ALPHABET[usize::from((buffer >> bits) & 0x1f)],
+            ));
+        }
+    }
+    if bits > 0 {
+        out.push(char::from(
+            ZBASE32_ALPHABET[usize::from((buffer << (5 - bits)) & 0x1f)],
+        ));
+    }
+
+    out
+}
+
+/// Percent encodes everything but unreserved characters.
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            out.push(char::from(byte));
+        } else {
+            out.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        composed::{Deserializable, KeyType, SecretKeyParamsBuilder},
+        types::Password,
+    };
+
+    #[test]
+    fn lookup() {
+        // example from the draft
+        let address = WkdAddress::new("Joe.Doe@Example.ORG").unwrap();
+        assert_eq!(address.hash(), "iy9q119eutrkn8s1mk4r39qejnbu3n5q");
+        assert_eq!(
+            address.url(WkdMethod::Advanced),
+            "https://openpgpkey.example.org/.well-known/openpgpkey/example.org/hu/iy9q119eutrkn8s1mk4r39qejnbu3n5q?l=Joe.Doe"
+        );
+        assert_eq!(
+            address.url(WkdMethod::Direct),
+            "https://example.org/.well-known/openpgpkey/hu/iy9q119eutrkn8s1mk4r39qejnbu3n5q?l=Joe.Doe"
+        );
+        assert_eq!(
+            address.policy_url(WkdMethod::Advanced),
+            "https://openpgpkey.example.org/.well-known/openpgpkey/example.org/policy"
+        );
+        assert_eq!(
+            address.path(WkdMethod::Direct),
+            Path::new(".well-known/openpgpkey/hu/iy9q119eutrkn8s1mk4r39qejnbu3n5q")
+        );
+
+        let address = WkdAddress::new("a+b c@example.org").unwrap();
+        assert!(address.url(WkdMethod::Direct).ends_with("?l=a%2Bb%20c"));
+
+        assert!(WkdAddress::new("example.org").is_err());
+        assert!(WkdAddress::new("@example.org").is_err());
+    }
+
+    #[test]
+    fn zbase32_encoding() {
+        assert_eq!(zbase32(&[]), "");
+        assert_eq!(zbase32(&[0x00]), "yy");
+        assert_eq!(zbase32(&[0xf0, 0xbf, 0xc7]), "6n9hq");
+        assert_eq!(zbase32(&[0xd4, 0x7a, 0x04]), "4t7ye");
+    }
+
+    #[test]
+    fn generate() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let key = SecretKeyParamsBuilder::default()
+            .key_type(KeyType::Ed25519Legacy)
+            .can_certify(true)
+            .primary_user_id("Alice <Alice@Example.org>".into())
+            .user_ids(vec![
+                "Alice <alice@other.example>".into(),
+                "alice@example.org".into(),
+                "Team <team@example.org>".into(),
+            ])
+            .build()
+            .unwrap()
+            .generate(&mut rng)
+            .unwrap()
+            .sign(&mut rng, &Password::empty())
+            .unwrap()
+            .signed_public_key();
+
+        let mut wkd = WkdGenerator::new("Example.org", WkdMethod::Advanced);
+        wkd.policy("protocol-version: 18\n");
+        let addresses = wkd.insert(&key).unwrap();
+        assert_eq!(addresses.len(), 2);
+        assert_eq!(addresses[0].to_string(), "alice@example.org");
+        assert_eq!(addresses[1].to_string(), "team@example.org");
+        // inserting again merges
+        wkd.insert(&key).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        wkd.write_to(dir.path()).unwrap();
+
+        let policy = dir.path().join(".well-known/openpgpkey/example.org/policy");
+        assert_eq!(
+            std::fs::read_to_string(policy).unwrap(),
+            "protocol-version: 18\n"
+        );
+
+        let address = WkdAddress::new("ALICE@example.org").unwrap();
+        let data = std::fs::read(dir.path().join(address.path(WkdMethod::Advanced))).unwrap();
+        let published: Vec<_> = SignedPublicKey::from_bytes_many(&data[..])
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].fingerprint(), key.fingerprint());
+        let ids: Vec<_> = published[0]
+            .details
+            .users
+            .iter()
+            .map(|u| u.id.id().to_vec())
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                b"Alice <Alice@Example.org>".to_vec(),
+                b"alice@example.org".to_vec()
+            ]
+        );
+        published[0].verify().unwrap();
+
+        assert_eq!(wkd.files().unwrap().len(), 3);
+    }
+}
@@ -29,6 +29,12 @@ impl From<&SecretKey> for DsaPublicParams {
     }
 }
 
+impl From<SigningKey> for SecretKey {
+    fn from(key: SigningKey) -> Self {
+        Self { key }
+    }
+}
+
 impl Zeroize for SecretKey {
     fn zeroize(&mut self) {
         // TODO: https://github.com/RustCrypto/signatures/issues/883
@@ -134,6 +134,30 @@ impl From<&SecretKey> for EcdhPublicParams {
     }
 }
 
+impl From<StaticSecret> for SecretKey {
+    fn from(key: StaticSecret) -> Self {
+        Self::Curve25519(key.into())
+    }
+}
+
+impl From<p256::SecretKey> for SecretKey {
+    fn from(secret: p256::SecretKey) -> Self {
+        Self::P256 { secret }
+    }
+}
+
+impl From<p384::SecretKey> for SecretKey {
+    fn from(secret: p384::SecretKey) -> Self {
+        Self::P384 { secret }
+    }
+}
+
+impl From<p521::SecretKey> for SecretKey {
+    fn from(secret: p521::SecretKey) -> Self {
+        Self::P521 { secret }
+    }
+}
+
 impl SecretKey {
     /// Generate an ECDH KeyPair.
     pub fn generate<R: Rng + CryptoRng>(mut rng: R, curve: &ECCCurve) -> Result<Self> {
@@ -68,6 +68,30 @@ impl TryFrom<&SecretKey> for EcdsaPublicParams {
     }
 }
 
+impl From<p256::SecretKey> for SecretKey {
+    fn from(key: p256::SecretKey) -> Self {
+        Self::P256(key)
+    }
+}
+
+impl From<p384::SecretKey> for SecretKey {
+    fn from(key: p384::SecretKey) -> Self {
+        Self::P384(key)
+    }
+}
+
+impl From<p521::SecretKey> for SecretKey {
+    fn from(key: p521::SecretKey) -> Self {
+        Self::P521(key)
+    }
+}
+
+impl From<k256::SecretKey> for SecretKey {
+    fn from(key: k256::SecretKey) -> Self {
+        Self::Secp256k1(key)
+    }
+}
+
 impl SecretKey {
     /// Generate an ECDSA `SecretKey`.
     pub fn generate<R: Rng + CryptoRng>(mut rng: R, curve: &ECCCurve) -> Result<Self> {
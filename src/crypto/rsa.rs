@@ -1,3 +1,5 @@
+use std::ops::Deref;
+
 use digest::{const_oid::AssociatedOid, Digest};
 use md5::Md5;
 use num_bigint::ModInverse;
@@ -98,6 +100,14 @@ impl SecretKey {
     }
 }
 
+impl Deref for SecretKey {
+    type Target = RsaPrivateKey;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
 impl From<&SecretKey> for RsaPublicParams {
     fn from(value: &SecretKey) -> Self {
         RsaPublicParams {
@@ -31,6 +31,12 @@ impl From<&SecretKey> for X25519PublicParams {
     }
 }
 
+impl From<StaticSecret> for SecretKey {
+    fn from(secret: StaticSecret) -> Self {
+        Self { secret }
+    }
+}
+
 impl PartialEq for SecretKey {
     fn eq(&self, other: &Self) -> bool {
         self.secret.as_bytes().eq(other.secret.as_bytes())
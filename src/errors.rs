@@ -134,6 +134,10 @@ pub enum Error {
     },
     #[snafu(transparent)]
     SigningError { source: cx448::SigningError },
+    #[snafu(transparent)]
+    Policy {
+        source: crate::policy::PolicyViolation,
+    },
 }
 
 impl From<crate::crypto::hash::Error> for Error {
@@ -29,6 +29,7 @@ pub mod errors;
 pub mod line_writer;
 pub mod normalize_lines;
 pub mod packet;
+pub mod policy;
 pub mod ser;
 pub mod types;
 
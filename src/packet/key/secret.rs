@@ -647,11 +647,81 @@ mod tests {
     use rand_chacha::ChaCha8Rng;
 
     use crate::{
-        crypto::hash::HashAlgorithm,
-        packet::{PubKeyInner, SecretKey},
-        types::{KeyVersion, S2kParams, SecretKeyTrait},
+        crypto::{hash::HashAlgorithm, sym::SymmetricKeyAlgorithm},
+        packet::{Packet, PacketParser, PacketTrait, PubKeyInner, SecretKey},
+        types::{
+            EncryptedSecretParams, KeyVersion, Password, S2kParams, SecretKeyTrait, SecretParams,
+            StringToKey,
+        },
     };
 
+    /// S2K usage 255: CFB encrypted secret key material, followed by a two-octet checksum
+    /// instead of a SHA-1 hash.
+    #[test]
+    fn secret_key_malleable_cfb() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+
+        let key_type = crate::composed::KeyType::Ed25519Legacy;
+        let (public_params, secret_params) = key_type.generate(&mut rng).unwrap();
+        let SecretParams::Plain(ref plain) = secret_params else {
+            panic!("generated keys are not encrypted");
+        };
+
+        let pub_key = PubKeyInner::new(
+            KeyVersion::V4,
+            key_type.to_alg(),
+            Utc::now().trunc_subsecs(0),
+            None,
+            public_params,
+        )
+        .unwrap();
+        let pub_key = crate::packet::PublicKey::from_inner(pub_key).unwrap();
+
+        // rpgp doesn't produce usage 255 itself, so encrypt by hand
+        let sym_alg = SymmetricKeyAlgorithm::AES128;
+        let s2k = StringToKey::new_iterated(&mut rng, HashAlgorithm::Sha256, 96);
+        let iv = vec![0x42; sym_alg.block_size()];
+        let key = s2k.derive_key(b"password", sym_alg.key_size()).unwrap();
+        let mut data = Vec::new();
+        plain.to_writer(&mut data, KeyVersion::V4).unwrap();
+        sym_alg
+            .encrypt_with_iv_regular(&key, &iv, &mut data)
+            .unwrap();
+
+        let params = EncryptedSecretParams::new(
+            data.into(),
+            S2kParams::MalleableCfb {
+                sym_alg,
+                s2k,
+                iv: iv.into(),
+            },
+        );
+        let sec = SecretKey::new(pub_key, SecretParams::Encrypted(params)).unwrap();
+        let mut bytes = Vec::new();
+        sec.to_writer_with_header(&mut bytes).unwrap();
+
+        let mut packets = PacketParser::new(&bytes[..]);
+        let Some(Ok(Packet::SecretKey(parsed))) = packets.next() else {
+            panic!("expected a secret key packet");
+        };
+        assert_eq!(parsed, sec);
+        assert_eq!(parsed.secret_params().string_to_key_id(), 255);
+        let mut reserialized = Vec::new();
+        parsed.to_writer_with_header(&mut reserialized).unwrap();
+        assert_eq!(reserialized, bytes);
+
+        parsed
+            .unlock(&Password::from("password"), |_, unlocked| {
+                assert_eq!(unlocked, plain);
+                Ok(())
+            })
+            .unwrap()
+            .unwrap();
+        assert!(parsed
+            .create_signature(&"wrong".into(), HashAlgorithm::Sha256, &[0x23; 32])
+            .is_err());
+    }
+
     #[test]
     #[ignore] // slow in debug mode (argon2)
     fn secret_key_protection_v4() {
@@ -439,6 +439,11 @@ impl SignatureConfig {
 
     /// Calculate the serialized version of this packet, but only the part relevant for hashing.
     pub fn hash_signature_data(&self, hasher: &mut Box<dyn DynDigest + Send>) -> Result<usize> {
+        self.hash_signature_data_into(&mut **hasher)
+    }
+
+    /// Like [`Self::hash_signature_data`], for any hasher.
+    pub(crate) fn hash_signature_data_into(&self, hasher: &mut dyn DynDigest) -> Result<usize> {
         match self.version() {
             SignatureVersion::V2 | SignatureVersion::V3 => {
                 let created = {
@@ -500,9 +505,13 @@ impl SignatureConfig {
                     // (See https://www.rfc-editor.org/rfc/rfc9580.html#section-5.2.3.35-3)
                     if let SubpacketData::IssuerFingerprint(fp) = &packet.data {
                         match (self.version(), fp.version()) {
-                            (SignatureVersion::V6, Some(KeyVersion::V6)) => {},
-                            (SignatureVersion::V4, Some(KeyVersion::V4)) => {},
-                            _ => bail!("IntendedRecipientFingerprint {:?} doesn't match signature version {:?}", fp, self.version())
+                            (SignatureVersion::V6, Some(KeyVersion::V6)) => {}
+                            (SignatureVersion::V4, Some(KeyVersion::V4)) => {}
+                            _ => bail!(
+                                "IssuerFingerprint {:?} doesn't match signature version {:?}",
+                                fp,
+                                self.version()
+                            ),
                         }
                     }
 
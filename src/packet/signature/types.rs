@@ -356,7 +356,7 @@ impl Signature {
     ///
     /// We also consider `key` a match for `sig` by default, if `sig` contains no issuer-related
     /// subpackets.
-    fn match_identity(sig: &Signature, key: &impl PublicKeyTrait) -> bool {
+    pub(crate) fn match_identity(sig: &Signature, key: &(impl PublicKeyTrait + ?Sized)) -> bool {
         let issuers = sig.issuer();
         let issuer_fps = sig.issuer_fingerprint();
 
@@ -377,7 +377,7 @@ impl Signature {
     /// - only a v6 key may produce a v6 signature
     /// - a v6 key may only produce v6 signatures
     fn check_signature_key_version_alignment(
-        key: &impl PublicKeyTrait,
+        key: &(impl PublicKeyTrait + ?Sized),
         config: &SignatureConfig,
     ) -> Result<()> {
         // Every signature made by a version 6 key MUST be a version 6 signature.
@@ -425,27 +425,32 @@ impl Signature {
     }
 
     /// Verify this signature.
-    pub fn verify<R>(&self, key: &impl PublicKeyTrait, data: R) -> Result<()>
+    pub fn verify<R>(&self, key: &(impl PublicKeyTrait + ?Sized), data: R) -> Result<()>
     where
         R: Read,
     {
-        let InnerSignature::Known {
-            ref config,
-            ref signed_hash_value,
-            ref signature,
-        } = self.inner
-        else {
+        let Some(config) = self.config() else {
             unsupported_err!("signature version {:?}", self.version());
         };
 
-        Self::check_signature_key_version_alignment(&key, config)?;
-        Self::check_signature_hash_strength(config)?;
+        let mut hasher = self.data_hasher()?;
+        if matches!(self.typ(), Some(SignatureType::Text)) {
+            let normalized = NormalizedReader::new(data, LineBreak::Crlf);
 
-        ensure!(
-            Self::match_identity(self, key),
-            "verify: No matching issuer or issuer_fingerprint for Key ID: {:?}",
-            &key.key_id(),
-        );
+            config.hash_data_to_sign(&mut hasher, normalized)?;
+        } else {
+            config.hash_data_to_sign(&mut hasher, data)?;
+        }
+
+        self.verify_hashed(key, hasher)
+    }
+
+    /// Returns a hasher for the data signed by this signature, the salt of v6 signatures is
+    /// already hashed.
+    pub(crate) fn data_hasher(&self) -> Result<Box<dyn DynDigest + Send>> {
+        let Some(config) = self.config() else {
+            unsupported_err!("signature version {:?}", self.version());
+        };
 
         let mut hasher = config.hash_alg.new_hasher()?;
 
@@ -464,14 +469,35 @@ impl Signature {
             hasher.update(salt.as_ref())
         }
 
-        if matches!(self.typ(), Some(SignatureType::Text)) {
-            let normalized = NormalizedReader::new(data, LineBreak::Crlf);
+        Ok(hasher)
+    }
 
-            config.hash_data_to_sign(&mut hasher, normalized)?;
-        } else {
-            config.hash_data_to_sign(&mut hasher, data)?;
-        }
-        let len = config.hash_signature_data(&mut hasher)?;
+    /// Verifies this signature, with `hasher` (see [`Self::data_hasher`]) having already hashed
+    /// the signed data.
+    pub(crate) fn verify_hashed(
+        &self,
+        key: &(impl PublicKeyTrait + ?Sized),
+        mut hasher: Box<dyn DynDigest>,
+    ) -> Result<()> {
+        let InnerSignature::Known {
+            ref config,
+            ref signed_hash_value,
+            ref signature,
+        } = self.inner
+        else {
+            unsupported_err!("signature version {:?}", self.version());
+        };
+
+        Self::check_signature_key_version_alignment(key, config)?;
+        Self::check_signature_hash_strength(config)?;
+
+        ensure!(
+            Self::match_identity(self, key),
+            "verify: No matching issuer or issuer_fingerprint for Key ID: {:?}",
+            &key.key_id(),
+        );
+
+        let len = config.hash_signature_data_into(&mut *hasher)?;
         hasher.update(&config.trailer(len)?);
 
         let hash = &hasher.finalize()[..];
@@ -930,6 +956,23 @@ impl Signature {
             .unwrap_or_default()
     }
 
+    /// The fingerprints of the primary keys of the recipients that the signer intended
+    /// this signature for.
+    ///
+    /// An empty list means the signature makes no statement about its recipients.
+    pub fn intended_recipients(&self) -> Vec<&Fingerprint> {
+        self.config()
+            .map(|c| {
+                c.hashed_subpackets()
+                    .filter_map(|p| match &p.data {
+                        SubpacketData::IntendedRecipientFingerprint(fp) => Some(fp),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    }
+
     pub fn revocation_key(&self) -> Option<&types::RevocationKey> {
         self.config().and_then(|c| {
             c.hashed_subpackets().find_map(|p| match &p.data {
@@ -1,6 +1,10 @@
 use std::io::{self, BufRead};
 
-use log::warn;
+use bytes::Bytes;
+use chrono::{DateTime, TimeZone, Utc};
+use num_enum::{FromPrimitive, IntoPrimitive};
+#[cfg(test)]
+use proptest::prelude::*;
 
 use crate::{
     errors::Result,
@@ -15,29 +19,204 @@ use crate::{
 /// Trust packets SHOULD NOT be emitted to output streams that are
 /// transferred to other users, and they SHOULD be ignored on any input
 /// other than local keyring files.
-#[derive(Debug, PartialEq, Eq, Clone)]
+///
+/// The content is implementation defined, use [`Trust::to_gnupg`] to decode the
+/// format used in GnuPG keyrings.
+#[derive(derive_more::Debug, PartialEq, Eq, Clone)]
 #[cfg_attr(test, derive(proptest_derive::Arbitrary))]
 pub struct Trust {
     packet_header: PacketHeader,
+    #[debug("{}", hex::encode(data))]
+    #[cfg_attr(test, proptest(strategy = "any::<Vec<u8>>().prop_map(Into::into)"))]
+    data: Bytes,
+}
+
+/// Marker used by GnuPG to tag its extended trust packets.
+const GNUPG_MARKER: &[u8; 3] = b"gpg";
+
+/// GnuPG ring trust subtypes.
+const RING_TRUST_KEY: u8 = 1;
+const RING_TRUST_UID: u8 = 2;
+
+/// The content of a trust packet, as written by GnuPG.
+///
+/// GnuPG writes a trust packet after each key, user ID and signature in its keyrings.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GnupgTrust {
+    /// The trust value.
+    ///
+    /// For keys this is the ownertrust, for user IDs the calculated validity.
+    /// Current versions of GnuPG keep these values in the trust database, and always
+    /// write [`TrustLevel::Unknown`] here.
+    pub value: TrustValue,
+    /// The cached result of the signature verification, if this follows a signature.
+    pub sig_cache: Option<SigCache>,
+    /// Where the key or user ID came from, if this follows a key or user ID.
+    pub origin: Option<KeyOrigin>,
+}
+
+/// A GnuPG trust value: a trust level and a set of flags.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TrustValue {
+    pub level: TrustLevel,
+    /// The key or user ID has been revoked.
+    pub revoked: bool,
+    /// A subkey has been revoked.
+    pub sub_revoked: bool,
+    /// The key has been disabled.
+    pub disabled: bool,
+}
+
+/// Trust levels as used by GnuPG for ownertrust and validity.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, FromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum TrustLevel {
+    /// Not yet calculated or assigned.
+    Unknown = 0,
+    /// The key has expired.
+    Expired = 1,
+    /// Not enough information for a calculation.
+    Undefined = 2,
+    /// Never trust this key.
+    Never = 3,
+    Marginal = 4,
+    Full = 5,
+    Ultimate = 6,
+
+    #[num_enum(catch_all)]
+    Other(u8),
+}
+
+/// Cached result of verifying a signature.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SigCache {
+    /// The signature has been checked.
+    pub checked: bool,
+    /// The signature was found to be valid.
+    pub valid: bool,
+}
+
+/// Origin of a key or user ID, as recorded by GnuPG.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct KeyOrigin {
+    pub kind: KeyOriginKind,
+    /// When the key or user ID was last updated from its origin.
+    pub updated_at: Option<DateTime<Utc>>,
+    /// The URL the key or user ID was fetched from.
+    pub url: Option<String>,
+}
+
+/// How GnuPG obtained a key or user ID.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, FromPrimitive, IntoPrimitive)]
+#[repr(u8)]
+pub enum KeyOriginKind {
+    Unknown = 0,
+    Keyserver = 1,
+    Dane = 2,
+    Wkd = 3,
+    Url = 4,
+    File = 5,
+    /// Created locally.
+    SelfCreated = 6,
+
+    #[num_enum(catch_all)]
+    Other(u8),
 }
 
 impl Trust {
     /// Parses a `Trust` packet from the given slice.
-    pub fn try_from_reader<B: BufRead>(packet_header: PacketHeader, mut data: B) -> Result<Self> {
-        warn!("Trust packet detected, ignoring");
-        data.drain()?;
+    pub fn try_from_reader<B: BufRead>(packet_header: PacketHeader, mut input: B) -> Result<Self> {
+        let data = input.rest()?.freeze();
+
+        Ok(Trust {
+            packet_header,
+            data,
+        })
+    }
+
+    /// The raw content of the packet.
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
 
-        Ok(Trust { packet_header })
+    /// Decodes the content of the packet as written by GnuPG.
+    pub fn to_gnupg(&self) -> Result<GnupgTrust> {
+        let mut data = &self.data[..];
+
+        let value = data.read_u8()?;
+        let mut sig_cache = None;
+
+        if data.has_remaining()? {
+            let c = data.read_u8()?;
+            // The signature cache is only present if there is no trust value.
+            if value == 0 && c & 0x80 == 0 {
+                sig_cache = Some(SigCache {
+                    checked: c & 0x01 != 0,
+                    valid: c & 0x02 != 0,
+                });
+            }
+        }
+
+        let mut subtype = None;
+        if data.len() > 3 {
+            let marker = data.read_array::<3>()?;
+            if &marker == GNUPG_MARKER {
+                subtype = Some(data.read_u8()?);
+            }
+        }
+
+        let mut origin = None;
+        if matches!(subtype, Some(RING_TRUST_KEY | RING_TRUST_UID)) {
+            // Older versions did not write the origin.
+            if data.len() >= 6 {
+                let kind = data.read_u8()?.into();
+                let updated_at = match data.read_be_u32()? {
+                    0 => None,
+                    ts => Utc.timestamp_opt(i64::from(ts), 0).single(),
+                };
+                let url_len = data.read_u8()?.into();
+                let url = data
+                    .get(..url_len)
+                    .filter(|url| !url.is_empty())
+                    .map(|url| String::from_utf8_lossy(url).into_owned());
+
+                origin = Some(KeyOrigin {
+                    kind,
+                    updated_at,
+                    url,
+                });
+            }
+        }
+
+        Ok(GnupgTrust {
+            value: value.into(),
+            // Trust packets that follow keys and user IDs never carry a signature cache.
+            sig_cache: sig_cache.filter(|_| subtype.is_none_or(|s| s == 0)),
+            origin,
+        })
+    }
+}
+
+impl From<u8> for TrustValue {
+    fn from(value: u8) -> Self {
+        TrustValue {
+            level: TrustLevel::from(value & 0x0f),
+            revoked: value & 0x20 != 0,
+            sub_revoked: value & 0x40 != 0,
+            disabled: value & 0x80 != 0,
+        }
     }
 }
 
 impl Serialize for Trust {
-    fn to_writer<W: io::Write>(&self, _writer: &mut W) -> Result<()> {
+    fn to_writer<W: io::Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.data)?;
+
         Ok(())
     }
 
     fn write_len(&self) -> usize {
-        0
+        self.data.len()
     }
 }
 
@@ -49,9 +228,71 @@ impl PacketTrait for Trust {
 
 #[cfg(test)]
 mod tests {
-    use proptest::prelude::*;
+    #![allow(clippy::unwrap_used)]
 
     use super::*;
+    use crate::types::{PacketHeaderVersion, PacketLength, Tag};
+
+    fn trust(data: &[u8]) -> Trust {
+        let packet_header = PacketHeader::from_parts(
+            PacketHeaderVersion::Old,
+            Tag::Trust,
+            PacketLength::Fixed(data.len().try_into().unwrap()),
+        )
+        .unwrap();
+        Trust::try_from_reader(packet_header, data).unwrap()
+    }
+
+    #[test]
+    fn gnupg_trust() {
+        // key, as written by GnuPG 2.2
+        let t = trust(&hex::decode("000067706701050000000a00").unwrap());
+        assert_eq!(
+            t.to_gnupg().unwrap(),
+            GnupgTrust {
+                value: TrustValue::from(0),
+                sig_cache: None,
+                origin: Some(KeyOrigin {
+                    kind: KeyOriginKind::File,
+                    updated_at: Utc.timestamp_opt(10, 0).single(),
+                    url: None,
+                }),
+            }
+        );
+
+        // user ID with an origin URL
+        let t =
+            trust(&hex::decode("00006770670203000000000f776b642e6578616d706c652e6f7267").unwrap());
+        let origin = t.to_gnupg().unwrap().origin.unwrap();
+        assert_eq!(origin.kind, KeyOriginKind::Wkd);
+        assert_eq!(origin.updated_at, None);
+        assert_eq!(origin.url.as_deref(), Some("wkd.example.org"));
+
+        // checked and valid signature
+        let t = trust(&hex::decode("000367706700").unwrap());
+        let gnupg = t.to_gnupg().unwrap();
+        assert_eq!(
+            gnupg.sig_cache,
+            Some(SigCache {
+                checked: true,
+                valid: true
+            })
+        );
+        assert_eq!(gnupg.origin, None);
+
+        // legacy keyring with an ownertrust value, of a disabled key
+        let gnupg = trust(&[0x85, 0x00]).to_gnupg().unwrap();
+        assert_eq!(gnupg.value.level, TrustLevel::Full);
+        assert!(gnupg.value.disabled);
+        assert!(!gnupg.value.revoked);
+        assert_eq!(gnupg.sig_cache, None);
+
+        let gnupg = trust(&[0x24]).to_gnupg().unwrap();
+        assert_eq!(gnupg.value.level, TrustLevel::Marginal);
+        assert!(gnupg.value.revoked);
+
+        assert!(trust(&[]).to_gnupg().is_err());
+    }
 
     proptest! {
         #[test]
@@ -68,5 +309,10 @@ mod tests {
             let new_packet = Trust::try_from_reader(packet.packet_header, &mut &buf[..]).unwrap();
             prop_assert_eq!(packet, new_packet);
         }
+
+        #[test]
+        fn gnupg_no_panic(packet: Trust) {
+            let _ = packet.to_gnupg();
+        }
     }
 }
@@ -0,0 +1,504 @@
+//! Cryptographic policy: which algorithms, key sizes and signatures are acceptable.
+//!
+//! A [`Policy`] is consulted when verifying signatures with
+//! [`Message::verify_reports`](crate::composed::Message::verify_reports) and
+//! [`CleartextSignedMessage::verify_reports`](crate::composed::CleartextSignedMessage::verify_reports)
+//! and detached signatures with
+//! [`verify_detached_with_policy`](crate::composed::verify_detached_with_policy),
+//! when evaluating certificates with
+//! [`SignedPublicKey::validity_at_with_policy`](crate::composed::SignedPublicKey::validity_at_with_policy),
+//! when selecting keys with
+//! [`RecipientSelection::select_with_policy`](crate::composed::RecipientSelection::select_with_policy)
+//! and [`SignedSecretKey::signing_key_with_policy`](crate::composed::SignedSecretKey::signing_key_with_policy),
+//! when linting keys with
+//! [`SignedPublicKey::lint_with_policy`](crate::composed::SignedPublicKey::lint_with_policy),
+//! when decrypting legacy SED packets with [`TheRing`](crate::composed::TheRing), and when
+//! building key generation parameters with
+//! [`SecretKeyParamsBuilder::build_with_policy`](crate::composed::SecretKeyParamsBuilder::build_with_policy).
+//!
+//! [`StandardPolicy`] follows the recommendations of RFC 9580, [`NullPolicy`] accepts
+//! everything.
+//!
+//! # Accepting SHA-1 signatures over data made until 2020
+//!
+//! ```rust
+//! use chrono::{TimeZone, Utc};
+//! use pgp::crypto::hash::HashAlgorithm;
+//! use pgp::policy::StandardPolicy;
+//!
+//! let mut policy = StandardPolicy::default();
+//! for cutoff in &mut policy.hash_cutoffs {
+//!     if cutoff.hash_alg == HashAlgorithm::Sha1 {
+//!         cutoff.data = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+//!     }
+//! }
+//! ```
+
+use chrono::{DateTime, TimeZone, Utc};
+use rsa::traits::PublicKeyParts;
+use snafu::Snafu;
+
+use crate::{
+    composed::KeyType,
+    crypto::{hash::HashAlgorithm, public_key::PublicKeyAlgorithm},
+    packet::{Signature, SignatureType, SubpacketData, SubpacketType},
+    types::{PublicKeyTrait, PublicParams},
+};
+
+/// Decides which cryptographic algorithms, key sizes and signatures are acceptable.
+pub trait Policy: std::fmt::Debug {
+    /// Checks the hash algorithm and the hashed subpackets of a signature.
+    ///
+    /// This is called for signatures over data, as well as for self-signatures and
+    /// revocations of certificates, before they are verified.
+    fn signature(&self, signature: &Signature) -> Result<(), PolicyViolation>;
+
+    /// Checks the algorithm and size of a key that signatures are verified with.
+    fn public_key(&self, key: &dyn PublicKeyTrait) -> Result<(), PolicyViolation>;
+
+    /// Checks the type of a key that is about to be generated.
+    fn key_generation(&self, key_type: &KeyType) -> Result<(), PolicyViolation>;
+
+    /// Checks whether data encrypted without integrity protection (SED packets) may be
+    /// decrypted.
+    fn legacy_encryption(&self) -> Result<(), PolicyViolation>;
+}
+
+/// Why a [`Policy`] rejected a signature, key or operation.
+#[derive(Debug, Clone, PartialEq, Eq, Snafu)]
+pub enum PolicyViolation {
+    #[snafu(display(
+        "hash algorithm {:?} is not accepted in signatures made after {}",
+        hash_alg,
+        cutoff
+    ))]
+    WeakHash {
+        hash_alg: HashAlgorithm,
+        cutoff: DateTime<Utc>,
+    },
+    #[snafu(display("unknown critical subpacket {:?}", typ))]
+    UnknownCriticalSubpacket { typ: SubpacketType },
+    #[snafu(display("unknown critical notation {}", name))]
+    UnknownCriticalNotation { name: String },
+    #[snafu(display("{:?} key of {} bits is too small", algorithm, bits))]
+    WeakKey {
+        algorithm: PublicKeyAlgorithm,
+        bits: usize,
+    },
+    #[snafu(display("decryption without integrity protection is not allowed"))]
+    LegacyEncryption,
+    #[snafu(display("{}", message))]
+    Other { message: String },
+}
+
+/// A hash algorithm that is only acceptable in signatures made before a cut-off time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashCutoff {
+    pub hash_alg: HashAlgorithm,
+    /// Signatures over data made at or after this time are rejected.
+    pub data: DateTime<Utc>,
+    /// Self-signatures, certifications and revocations made at or after this time are
+    /// rejected.
+    pub certification: DateTime<Utc>,
+}
+
+/// The default policy, following the recommendations of RFC 9580.
+///
+/// - MD5, SHA-1 and RIPEMD-160 are only accepted in signatures that predate known weaknesses
+///   of the algorithm. Signatures without a creation time are rejected for these.
+///   See <https://www.rfc-editor.org/rfc/rfc9580.html#name-hash-algorithms>
+/// - RSA keys must have at least 2048 bits.
+/// - Data without integrity protection is not decrypted.
+/// - Signatures with critical subpackets or notations that are not understood are rejected.
+///   See <https://www.rfc-editor.org/rfc/rfc9580.html#section-5.2.3.7-6>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StandardPolicy {
+    pub hash_cutoffs: Vec<HashCutoff>,
+    /// The minimum size of RSA keys, for verification as well as generation.
+    ///
+    /// This also applies to the size of the prime `p` of DSA keys.
+    pub min_rsa_bits: usize,
+    /// Allow decrypting data without integrity protection (SED packets).
+    pub allow_legacy_encryption: bool,
+    /// Notation names the application understands, and which are therefore acceptable in
+    /// critical notations.
+    pub known_critical_notations: Vec<String>,
+}
+
+impl Default for StandardPolicy {
+    fn default() -> Self {
+        StandardPolicy {
+            hash_cutoffs: vec![
+                HashCutoff {
+                    hash_alg: HashAlgorithm::Md5,
+                    data: date(1997, 2, 1),
+                    certification: date(2004, 2, 1),
+                },
+                HashCutoff {
+                    hash_alg: HashAlgorithm::Sha1,
+                    data: date(2013, 2, 1),
+                    certification: date(2023, 2, 1),
+                },
+                HashCutoff {
+                    hash_alg: HashAlgorithm::Ripemd160,
+                    data: date(2013, 2, 1),
+                    certification: date(2023, 2, 1),
+                },
+            ],
+            min_rsa_bits: 2048,
+            allow_legacy_encryption: false,
+            known_critical_notations: Vec::new(),
+        }
+    }
+}
+
+fn date(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(year, month, day, 0, 0, 0)
+        .single()
+        .expect("valid date")
+}
+
+impl Policy for StandardPolicy {
+    fn signature(&self, signature: &Signature) -> Result<(), PolicyViolation> {
+        // Signatures we can't parse can't be verified either.
+        let Some(config) = signature.config() else {
+            return Ok(());
+        };
+
+        if let Some(cutoff) = self
+            .hash_cutoffs
+            .iter()
+            .find(|cutoff| cutoff.hash_alg == config.hash_alg)
+        {
+            let cutoff = match config.typ {
+                SignatureType::Binary
+                | SignatureType::Text
+                | SignatureType::Standalone
+                | SignatureType::Timestamp
+                | SignatureType::ThirdParty => cutoff.data,
+                _ => cutoff.certification,
+            };
+            if config.created().is_none_or(|created| created >= &cutoff) {
+                return Err(PolicyViolation::WeakHash {
+                    hash_alg: config.hash_alg,
+                    cutoff,
+                });
+            }
+        }
+
+        for subpacket in config.hashed_subpackets().filter(|p| p.is_critical) {
+            match &subpacket.data {
+                SubpacketData::Notation(notation)
+                    if !self
+                        .known_critical_notations
+                        .iter()
+                        .any(|name| name.as_bytes() == notation.name) =>
+                {
+                    return Err(PolicyViolation::UnknownCriticalNotation {
+                        name: String::from_utf8_lossy(&notation.name).into(),
+                    });
+                }
+                SubpacketData::Experimental(..) | SubpacketData::Other(..) => {
+                    return Err(PolicyViolation::UnknownCriticalSubpacket {
+                        typ: subpacket.typ(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn public_key(&self, key: &dyn PublicKeyTrait) -> Result<(), PolicyViolation> {
+        let bits = match key.public_params() {
+            PublicParams::RSA(params) => params.key.n().bits(),
+            PublicParams::DSA(params) => params.key.components().p().bits(),
+            _ => return Ok(()),
+        };
+        if bits < self.min_rsa_bits {
+            return Err(PolicyViolation::WeakKey {
+                algorithm: key.algorithm(),
+                bits,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn key_generation(&self, key_type: &KeyType) -> Result<(), PolicyViolation> {
+        if let KeyType::Rsa(bits) = key_type {
+            let bits = *bits as usize;
+            if bits < self.min_rsa_bits {
+                return Err(PolicyViolation::WeakKey {
+                    algorithm: PublicKeyAlgorithm::RSA,
+                    bits,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn legacy_encryption(&self) -> Result<(), PolicyViolation> {
+        if self.allow_legacy_encryption {
+            Ok(())
+        } else {
+            Err(PolicyViolation::LegacyEncryption)
+        }
+    }
+}
+
+/// A policy that accepts everything.
+///
+/// Certificates evaluated without a policy, e.g. with
+/// [`SignedPublicKey::validity_at`](crate::composed::SignedPublicKey::validity_at), use this.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NullPolicy;
+
+impl Policy for NullPolicy {
+    fn signature(&self, _signature: &Signature) -> Result<(), PolicyViolation> {
+        Ok(())
+    }
+
+    fn public_key(&self, _key: &dyn PublicKeyTrait) -> Result<(), PolicyViolation> {
+        Ok(())
+    }
+
+    fn key_generation(&self, _key_type: &KeyType) -> Result<(), PolicyViolation> {
+        Ok(())
+    }
+
+    fn legacy_encryption(&self) -> Result<(), PolicyViolation> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+
+    use chrono::SubsecRound;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::{
+        composed::{ComponentStatus, SecretKeyParamsBuilder, SignedSecretKey},
+        packet::{Notation, SignatureConfig, Subpacket},
+        types::{KeyDetails, KeyVersion, Password, Tag},
+        util::test::gen_key,
+    };
+
+    fn sign(
+        rng: &mut ChaCha8Rng,
+        key: &SignedSecretKey,
+        typ: SignatureType,
+        hash_alg: HashAlgorithm,
+        created: DateTime<Utc>,
+        extra: Option<Subpacket>,
+    ) -> Signature {
+        let mut config = SignatureConfig::from_key(&mut *rng, &key.primary_key, typ).unwrap();
+        config.hash_alg = hash_alg;
+        config.hashed_subpackets = vec![
+            Subpacket::regular(SubpacketData::SignatureCreationTime(created)).unwrap(),
+            Subpacket::regular(SubpacketData::IssuerFingerprint(key.fingerprint())).unwrap(),
+        ];
+        config.hashed_subpackets.extend(extra);
+
+        let pw = Password::empty();
+        if typ == SignatureType::Key {
+            config
+                .sign_key(&key.primary_key, &pw, key.primary_key.public_key())
+                .unwrap()
+        } else {
+            config.sign(&key.primary_key, &pw, &b"hello"[..]).unwrap()
+        }
+    }
+
+    #[test]
+    fn standard_policy() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let key = gen_key(&mut rng, KeyVersion::V4, KeyType::Rsa(2048), &[]);
+        let policy = StandardPolicy::default();
+        let now = Utc::now().trunc_subsecs(0);
+        let old = date(2010, 1, 1);
+
+        // SHA-1 data signatures are only accepted before 2013
+        let sig = sign(
+            &mut rng,
+            &key,
+            SignatureType::Binary,
+            HashAlgorithm::Sha1,
+            now,
+            None,
+        );
+        assert_eq!(
+            policy.signature(&sig),
+            Err(PolicyViolation::WeakHash {
+                hash_alg: HashAlgorithm::Sha1,
+                cutoff: date(2013, 2, 1),
+            })
+        );
+        assert!(NullPolicy.signature(&sig).is_ok());
+        let sig = sign(
+            &mut rng,
+            &key,
+            SignatureType::Binary,
+            HashAlgorithm::Sha1,
+            old,
+            None,
+        );
+        assert!(policy.signature(&sig).is_ok());
+
+        // certifications have a later cut-off
+        let created = date(2020, 1, 1);
+        let sig = sign(
+            &mut rng,
+            &key,
+            SignatureType::Binary,
+            HashAlgorithm::Sha1,
+            created,
+            None,
+        );
+        assert!(policy.signature(&sig).is_err());
+        let sig = sign(
+            &mut rng,
+            &key,
+            SignatureType::Key,
+            HashAlgorithm::Sha1,
+            created,
+            None,
+        );
+        assert!(policy.signature(&sig).is_ok());
+
+        let sig = sign(
+            &mut rng,
+            &key,
+            SignatureType::Binary,
+            HashAlgorithm::Sha256,
+            now,
+            None,
+        );
+        assert!(policy.signature(&sig).is_ok());
+
+        // critical notations must be known
+        let notation = Subpacket::critical(SubpacketData::Notation(Notation {
+            readable: true,
+            name: "policy@example.org".into(),
+            value: "strict".into(),
+        }))
+        .unwrap();
+        let sig = sign(
+            &mut rng,
+            &key,
+            SignatureType::Binary,
+            HashAlgorithm::Sha256,
+            now,
+            Some(notation),
+        );
+        assert_eq!(
+            policy.signature(&sig),
+            Err(PolicyViolation::UnknownCriticalNotation {
+                name: "policy@example.org".into()
+            })
+        );
+        let mut known = policy.clone();
+        known
+            .known_critical_notations
+            .push("policy@example.org".into());
+        assert!(known.signature(&sig).is_ok());
+
+        let experimental =
+            Subpacket::critical(SubpacketData::Experimental(101, vec![1].into())).unwrap();
+        let sig = sign(
+            &mut rng,
+            &key,
+            SignatureType::Binary,
+            HashAlgorithm::Sha256,
+            now,
+            Some(experimental),
+        );
+        assert_eq!(
+            policy.signature(&sig),
+            Err(PolicyViolation::UnknownCriticalSubpacket {
+                typ: SubpacketType::Experimental(101)
+            })
+        );
+
+        // keys
+        assert!(policy.public_key(&key.primary_key.public_key()).is_ok());
+        let mut strict = policy.clone();
+        strict.min_rsa_bits = 3072;
+        assert_eq!(
+            strict.public_key(&key.primary_key.public_key()),
+            Err(PolicyViolation::WeakKey {
+                algorithm: PublicKeyAlgorithm::RSA,
+                bits: 2048
+            })
+        );
+        assert!(strict.key_generation(&KeyType::Rsa(2048)).is_err());
+        assert!(strict.key_generation(&KeyType::Ed25519).is_ok());
+
+        assert_eq!(
+            policy.legacy_encryption(),
+            Err(PolicyViolation::LegacyEncryption)
+        );
+    }
+
+    #[test]
+    fn key_generation() {
+        let builder = || {
+            let mut builder = SecretKeyParamsBuilder::default();
+            builder
+                .key_type(KeyType::Rsa(1024))
+                .can_certify(true)
+                .primary_user_id("Alice <alice@example.org>".into());
+            builder
+        };
+
+        let err = builder().build().unwrap_err();
+        assert!(err.to_string().contains("1024 bits is too small"), "{err}");
+        assert!(builder().build_with_policy(&NullPolicy).is_ok());
+    }
+
+    #[test]
+    fn certificate_validity() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let key = gen_key(&mut rng, KeyVersion::V4, KeyType::Rsa(2048), &[]);
+        let public = key.signed_public_key();
+        let now = Utc::now();
+
+        let policy = StandardPolicy::default();
+        assert!(public.validity_at_with_policy(now, &policy).is_valid());
+
+        let mut strict = policy.clone();
+        strict.min_rsa_bits = 3072;
+        let validity = public.validity_at_with_policy(now, &strict);
+        assert_eq!(
+            validity.status(),
+            &ComponentStatus::Rejected(PolicyViolation::WeakKey {
+                algorithm: PublicKeyAlgorithm::RSA,
+                bits: 2048
+            })
+        );
+
+        // a recent SHA-1 self-signature doesn't bind the user ID
+        let mut weak = public.clone();
+        let user = &mut weak.details.users[0];
+        let mut config = user.signatures[0].config().unwrap().clone();
+        config.hash_alg = HashAlgorithm::Sha1;
+        user.signatures = vec![config
+            .sign_certification(
+                &key.primary_key,
+                key.primary_key.public_key(),
+                &Password::empty(),
+                Tag::UserId,
+                &user.id,
+            )
+            .unwrap()];
+        assert!(weak.validity_at_with_policy(now, &NullPolicy).is_valid());
+        let validity = weak.validity_at_with_policy(now, &policy);
+        assert_eq!(validity.users()[0].status, ComponentStatus::Unbound);
+    }
+}
@@ -23,7 +23,7 @@ pub use self::{
     password::Password,
     pkesk::PkeskBytes,
     revocation_key::{RevocationKey, RevocationKeyClass},
-    s2k::{S2kParams, S2kUsage, StringToKey},
+    s2k::{GnuStub, S2kParams, S2kUsage, StringToKey},
     signature::SignatureBytes,
     user::{SignedUser, SignedUserAttribute},
 };
@@ -6,7 +6,7 @@ use digest::Digest;
 use zeroize::ZeroizeOnDrop;
 
 use crate::{
-    crypto::checksum,
+    crypto::{checksum, hash::HashAlgorithm, sym::SymmetricKeyAlgorithm},
     errors::{bail, ensure, unsupported_err, InvalidInputSnafu, Result},
     ser::Serialize,
     types::*,
@@ -42,6 +42,33 @@ impl EncryptedSecretParams {
         &self.s2k_params
     }
 
+    /// Creates the parameters of a secret key packet without secret key material, in the
+    /// format GnuPG uses for `gpg --export-secret-subkeys`.
+    ///
+    /// Version 6 keys use S2K usage 254, as 255 is not allowed for them.
+    pub fn new_gnu_stub(stub: GnuStub, key_version: KeyVersion) -> Self {
+        let sym_alg = SymmetricKeyAlgorithm::Plaintext;
+        let s2k = StringToKey::Gnu {
+            hash_alg: HashAlgorithm::None,
+            stub,
+        };
+        let iv = Bytes::new();
+        let s2k_params = match key_version {
+            KeyVersion::V6 => S2kParams::Cfb { sym_alg, s2k, iv },
+            _ => S2kParams::MalleableCfb { sym_alg, s2k, iv },
+        };
+
+        EncryptedSecretParams::new(Bytes::new(), s2k_params)
+    }
+
+    /// The GnuPG stub, if this secret key packet does not contain the secret key material.
+    pub fn gnu_stub(&self) -> Option<&GnuStub> {
+        match &self.s2k_params {
+            S2kParams::Cfb { s2k, .. } | S2kParams::MalleableCfb { s2k, .. } => s2k.gnu_stub(),
+            _ => None,
+        }
+    }
+
     pub fn checksum(&self) -> Vec<u8> {
         match self.s2k_params {
             S2kParams::Unprotected => unreachable!(),
@@ -49,11 +76,11 @@ impl EncryptedSecretParams {
             | S2kParams::Aead { .. }
             | S2kParams::MalleableCfb { .. } => {
                 // 2 octets
-                self.data[self.data.len() - 2..].to_vec()
+                self.data[self.data.len().saturating_sub(2)..].to_vec()
             }
             S2kParams::Cfb { .. } => {
                 // 20 octets SHA1
-                self.data[self.data.len() - 20..].to_vec()
+                self.data[self.data.len().saturating_sub(20)..].to_vec()
             }
         }
     }
@@ -64,6 +91,10 @@ impl EncryptedSecretParams {
         pub_key: &(impl PublicKeyTrait + Serialize),
         secret_tag: Option<Tag>,
     ) -> Result<PlainSecretParams> {
+        if let Some(stub) = self.gnu_stub() {
+            bail!("secret key material is not available: {}", stub);
+        }
+
         // Argon2 is only used with AEAD (S2K usage octet 253).
         //
         // An implementation MUST NOT create and MUST reject as malformed any secret key packet
@@ -1,5 +1,6 @@
 use std::io::{self, BufRead};
 
+use bytes::Bytes;
 use zeroize::ZeroizeOnDrop;
 
 use crate::{
@@ -52,6 +53,14 @@ impl SecretParams {
         self.string_to_key_id() == 254
     }
 
+    /// The GnuPG stub, if this secret key packet does not contain the secret key material.
+    pub fn gnu_stub(&self) -> Option<&GnuStub> {
+        match self {
+            SecretParams::Plain(_) => None,
+            SecretParams::Encrypted(k) => k.gnu_stub(),
+        }
+    }
+
     pub fn string_to_key_id(&self) -> u8 {
         match self {
             SecretParams::Plain(k) => k.string_to_key_id(),
@@ -182,15 +191,15 @@ fn parse_secret_fields<B: BufRead>(
                 }
             }
 
-            let iv = i.take_bytes(sym_alg.block_size())?.freeze();
+            let iv = read_iv(&mut i, sym_alg, &s2k)?;
             S2kParams::Cfb { sym_alg, s2k, iv }
         }
         S2kUsage::MalleableCfb => {
             let sym_alg = i.read_u8().map(SymmetricKeyAlgorithm::from)?;
             let s2k = StringToKey::try_from_reader(&mut i)?;
-            let iv = i.take_bytes(sym_alg.block_size())?.freeze();
+            let iv = read_iv(&mut i, sym_alg, &s2k)?;
 
-            S2kParams::Cfb { sym_alg, s2k, iv }
+            S2kParams::MalleableCfb { sym_alg, s2k, iv }
         }
     };
 
@@ -205,3 +214,18 @@ fn parse_secret_fields<B: BufRead>(
         }
     }
 }
+
+/// Reads the IV of CFB encrypted secret key material.
+///
+/// GnuPG stubs don't contain any secret key material, and no IV either.
+fn read_iv<B: BufRead>(
+    mut i: B,
+    sym_alg: SymmetricKeyAlgorithm,
+    s2k: &StringToKey,
+) -> Result<Bytes> {
+    if s2k.gnu_stub().is_some() {
+        return Ok(Bytes::new());
+    }
+
+    Ok(i.take_bytes(sym_alg.block_size())?.freeze())
+}
@@ -177,6 +177,12 @@ pub enum StringToKey {
         /// one-octet encoded_m, specifying the exponent of the memory size
         m_enc: u8,
     },
+    /// GnuPG extension (type 101) for secret key packets without secret key material.
+    #[cfg_attr(test, proptest(skip))] // has no IV
+    Gnu {
+        hash_alg: HashAlgorithm,
+        stub: GnuStub,
+    },
     /// Private/Experimental S2K: 100-110
     #[cfg_attr(test, proptest(skip))] // doesn't roundtrip
     Private {
@@ -193,6 +199,43 @@ pub enum StringToKey {
     },
 }
 
+/// Marks a secret key packet that does not contain the secret key material.
+///
+/// This is the "GNU" extension of GnuPG to the S2K specifier, as used in the output of
+/// `gpg --export-secret-subkeys` and for keys stored on a smartcard.
+#[derive(derive_more::Debug, derive_more::Display, PartialEq, Eq, Clone)]
+pub enum GnuStub {
+    /// The secret key material is not available (`gnu-dummy`).
+    #[display("gnu-dummy")]
+    Dummy,
+    /// The secret key material is stored on the smartcard with this serial number
+    /// (`divert-to-card`).
+    #[display("divert-to-card (serial {})", hex::encode(serial))]
+    DivertToCard {
+        #[debug("{}", hex::encode(serial))]
+        serial: Bytes,
+    },
+}
+
+impl GnuStub {
+    const MAGIC: &[u8] = b"GNU";
+    const MODE_DUMMY: u8 = 1;
+    const MODE_DIVERT_TO_CARD: u8 = 2;
+
+    /// Parses the data following the hash algorithm of a type 101 S2K specifier.
+    fn from_slice(data: &[u8]) -> Option<Self> {
+        match data.strip_prefix(Self::MAGIC)? {
+            [Self::MODE_DUMMY] => Some(GnuStub::Dummy),
+            [Self::MODE_DIVERT_TO_CARD, len, serial @ ..] if usize::from(*len) == serial.len() => {
+                Some(GnuStub::DivertToCard {
+                    serial: Bytes::copy_from_slice(serial),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
 impl StringToKey {
     pub fn new_default<R: CryptoRng + Rng>(rng: R) -> Self {
         StringToKey::new_iterated(rng, HashAlgorithm::default(), DEFAULT_ITER_SALTED_COUNT)
@@ -228,11 +271,20 @@ impl StringToKey {
             Self::IteratedAndSalted { .. } => 3,
             Self::Argon2 { .. } => 4,
 
+            Self::Gnu { .. } => 101,
             Self::Private { typ, .. } => *typ,
             Self::Other { typ, .. } => *typ,
         }
     }
 
+    /// The GnuPG stub, if this marks a secret key packet without secret key material.
+    pub fn gnu_stub(&self) -> Option<&GnuStub> {
+        match self {
+            Self::Gnu { stub, .. } => Some(stub),
+            _ => None,
+        }
+    }
+
     /// true, if this StringToKey uses a salt
     pub fn uses_salt(&self) -> bool {
         matches![
@@ -458,6 +510,14 @@ impl StringToKey {
             }
             100..=110 => {
                 let unknown = i.rest()?.freeze();
+                if typ == 101 {
+                    if let Some((hash_alg, data)) = unknown.split_first() {
+                        if let Some(stub) = GnuStub::from_slice(data) {
+                            let hash_alg = HashAlgorithm::from(*hash_alg);
+                            return Ok(StringToKey::Gnu { hash_alg, stub });
+                        }
+                    }
+                }
                 Ok(StringToKey::Private { typ, unknown })
             }
             _ => {
@@ -495,6 +555,19 @@ impl Serialize for StringToKey {
                 writer.write_all(salt)?;
                 writer.write_all(&[*t, *p, *m_enc])?;
             }
+            Self::Gnu { hash_alg, stub } => {
+                writer.write_u8(self.id())?;
+                writer.write_u8((*hash_alg).into())?;
+                writer.write_all(GnuStub::MAGIC)?;
+                match stub {
+                    GnuStub::Dummy => writer.write_u8(GnuStub::MODE_DUMMY)?,
+                    GnuStub::DivertToCard { serial } => {
+                        writer.write_u8(GnuStub::MODE_DIVERT_TO_CARD)?;
+                        writer.write_u8(serial.len().try_into()?)?;
+                        writer.write_all(serial)?;
+                    }
+                }
+            }
 
             Self::Reserved { unknown, .. }
             | Self::Private { unknown, .. }
@@ -527,6 +600,13 @@ impl Serialize for StringToKey {
                 sum += salt.len();
                 sum += 3;
             }
+            Self::Gnu { stub, .. } => {
+                sum += 1 + 1;
+                sum += GnuStub::MAGIC.len() + 1;
+                if let GnuStub::DivertToCard { serial } = stub {
+                    sum += 1 + serial.len();
+                }
+            }
 
             Self::Reserved { unknown, .. }
             | Self::Private { unknown, .. }
@@ -703,6 +783,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn gnu_stub() {
+        let dummy = [101, 0, b'G', b'N', b'U', 1];
+        let s2k = StringToKey::try_from_reader(&dummy[..]).unwrap();
+        assert_eq!(s2k.gnu_stub(), Some(&GnuStub::Dummy));
+        assert_eq!(s2k.to_bytes().unwrap(), dummy);
+        assert_eq!(s2k.write_len(), dummy.len());
+
+        let divert = [101, 0, b'G', b'N', b'U', 2, 3, 0xd2, 0x76, 0x00];
+        let s2k = StringToKey::try_from_reader(&divert[..]).unwrap();
+        let stub = s2k.gnu_stub().unwrap();
+        assert_eq!(
+            stub,
+            &GnuStub::DivertToCard {
+                serial: Bytes::from_static(&[0xd2, 0x76, 0x00])
+            }
+        );
+        assert_eq!(stub.to_string(), "divert-to-card (serial d27600)");
+        assert_eq!(s2k.to_bytes().unwrap(), divert);
+        assert_eq!(s2k.write_len(), divert.len());
+
+        // the serial number is truncated
+        let invalid = [101, 0, b'G', b'N', b'U', 2, 3, 0xd2];
+        let s2k = StringToKey::try_from_reader(&invalid[..]).unwrap();
+        assert!(matches!(s2k, StringToKey::Private { typ: 101, .. }));
+    }
+
     // "These messages are the literal data "Hello, world!" encrypted using AES-128 with various AEADs
     #[test]
     fn test_aead_skesk_msg_gcm() {
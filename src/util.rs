@@ -112,7 +112,14 @@ impl<A: hash::Hasher, B: io::Write> io::Write for TeeWriter<'_, A, B> {
 #[cfg(test)]
 pub(crate) mod test {
     use bytes::{Buf, Bytes};
-    use rand::Rng;
+    use chrono::{Duration, SubsecRound, Utc};
+    use rand::{CryptoRng, Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::{
+        composed::{KeyType, SecretKeyParamsBuilder, SignedSecretKey, SubkeyParamsBuilder},
+        types::{KeyVersion, Password},
+    };
 
     const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
                             abcdefghijklmnopqrstuvwxyz\
@@ -161,6 +168,99 @@ pub(crate) mod test {
             escape_string::escape(b.as_ref())
         );
     }
+
+    /// A subkey of a generated test key.
+    #[derive(Debug, Clone)]
+    pub(crate) enum TestSubkey {
+        Encrypt(KeyType),
+        Sign(KeyType),
+    }
+
+    /// Parameters for a subkey, created a day ago.
+    pub(crate) fn subkey_params(version: KeyVersion, subkey: TestSubkey) -> SubkeyParamsBuilder {
+        let mut params = SubkeyParamsBuilder::default();
+        params
+            .version(version)
+            .created_at(Utc::now().trunc_subsecs(0) - Duration::days(1));
+        match subkey {
+            TestSubkey::Encrypt(key_type) => params.key_type(key_type).can_encrypt(true),
+            TestSubkey::Sign(key_type) => params.key_type(key_type).can_sign(true),
+        };
+        params
+    }
+
+    /// Parameters for a key of "Alice", created a day ago, with a primary key that can certify
+    /// and sign.
+    pub(crate) fn key_params(
+        version: KeyVersion,
+        key_type: KeyType,
+        subkeys: &[TestSubkey],
+    ) -> SecretKeyParamsBuilder {
+        let mut params = SecretKeyParamsBuilder::default();
+        params
+            .version(version)
+            .key_type(key_type)
+            .can_certify(true)
+            .can_sign(true)
+            .created_at(Utc::now().trunc_subsecs(0) - Duration::days(1))
+            .primary_user_id("Alice <alice@example.org>".into());
+        for subkey in subkeys {
+            params.subkey(subkey_params(version, subkey.clone()).build().unwrap());
+        }
+        params
+    }
+
+    /// Generates and self-signs a key from `params`.
+    pub(crate) fn generate<R: Rng + CryptoRng>(
+        rng: &mut R,
+        params: &SecretKeyParamsBuilder,
+        password: &Password,
+    ) -> SignedSecretKey {
+        params
+            .build()
+            .unwrap()
+            .generate(&mut *rng)
+            .unwrap()
+            .sign(&mut *rng, password)
+            .unwrap()
+    }
+
+    /// Generates a key with the default parameters of [`key_params`], without a password.
+    pub(crate) fn gen_key<R: Rng + CryptoRng>(
+        rng: &mut R,
+        version: KeyVersion,
+        key_type: KeyType,
+        subkeys: &[TestSubkey],
+    ) -> SignedSecretKey {
+        generate(
+            rng,
+            &key_params(version, key_type, subkeys),
+            &Password::empty(),
+        )
+    }
+
+    /// Parameters for an Ed25519 key of "Alice".
+    ///
+    /// Besides the defaults of [`key_params`], it has the second user id
+    /// "Alice <alice@work.example>", an X25519 encryption subkey and an Ed25519 signing subkey.
+    pub(crate) fn alice_params(version: KeyVersion) -> SecretKeyParamsBuilder {
+        let mut params = key_params(
+            version,
+            KeyType::Ed25519,
+            &[
+                TestSubkey::Encrypt(KeyType::X25519),
+                TestSubkey::Sign(KeyType::Ed25519),
+            ],
+        );
+        params.user_id("Alice <alice@work.example>");
+        params
+    }
+
+    /// Generates a key from [`alice_params`] and `seed`, without a password.
+    pub(crate) fn alice_key(version: KeyVersion, seed: u64) -> SignedSecretKey {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        generate(&mut rng, &alice_params(version), &Password::empty())
+    }
 }
 
 #[derive(derive_more::Debug)]
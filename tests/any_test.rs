@@ -1,6 +1,14 @@
 use std::fs::File;
 
-use pgp::composed::{Any, Deserializable, SignedPublicKey};
+use pgp::{
+    composed::{
+        export_openssh_public_key, export_openssh_secret_key, export_pkcs8_public_key,
+        export_pkcs8_secret_key, import_openssh_public_key, Any, Deserializable, SignedPublicKey,
+    },
+    crypto::hash::HashAlgorithm,
+    packet::{PublicKey, SecretKey},
+    types::{Password, PublicKeyTrait, SecretKeyTrait},
+};
 
 #[test]
 fn load_csf() {
@@ -49,3 +57,90 @@ fn load_csf_starts_with_newline() {
         _ => panic!("unexpected type"),
     }
 }
+
+fn load_unsigned_secret_key(armor: &str) -> SecretKey {
+    match Any::from_string(armor).expect("from_armor").0 {
+        Any::UnsignedSecretKey(key) => key,
+        _ => panic!("unexpected type"),
+    }
+}
+
+fn load_unsigned_public_key(armor: &str) -> PublicKey {
+    match Any::from_string(armor).expect("from_armor").0 {
+        Any::UnsignedPublicKey(key) => key,
+        _ => panic!("unexpected type"),
+    }
+}
+
+fn assert_key_pair(secret: &SecretKey, public: &PublicKey) {
+    assert_eq!(secret.public_key(), public);
+
+    let digest = [7u8; 64];
+    let signature = secret
+        .create_signature(&Password::empty(), HashAlgorithm::Sha512, &digest)
+        .unwrap();
+    public
+        .verify_signature(HashAlgorithm::Sha512, &digest, &signature)
+        .unwrap();
+}
+
+#[test]
+fn load_pkcs_keys() {
+    for (secret, public) in [
+        ("rsa.pkcs1.pem", "rsa.pkcs1.pub.pem"),
+        ("p256.sec1.pem", ""),
+        ("p384.pkcs8.pem", "p384.pkcs8.pub.pem"),
+        ("ed25519.pkcs8.pem", "ed25519.pkcs8.pub.pem"),
+    ] {
+        let dir = "tests/unit-tests/foreign";
+        let secret = std::fs::read_to_string(format!("{dir}/{secret}")).unwrap();
+        let secret = load_unsigned_secret_key(&secret);
+
+        if !public.is_empty() {
+            let pem = std::fs::read_to_string(format!("{dir}/{public}")).unwrap();
+            let public = load_unsigned_public_key(&pem);
+            assert_key_pair(&secret, &public);
+        }
+
+        // PKCS#8 export
+        let pem = export_pkcs8_public_key(secret.public_key().public_params()).unwrap();
+        assert_eq!(&load_unsigned_public_key(&pem), secret.public_key());
+
+        let pem = secret
+            .unlock(&Password::empty(), |_, secret| {
+                export_pkcs8_secret_key(secret)
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(load_unsigned_secret_key(&pem), secret);
+    }
+}
+
+#[test]
+fn load_openssh_keys() {
+    for name in ["rsa", "p256", "ed25519"] {
+        let dir = "tests/unit-tests/foreign";
+        let secret = std::fs::read_to_string(format!("{dir}/{name}.openssh")).unwrap();
+        let secret = load_unsigned_secret_key(&secret);
+
+        let line = std::fs::read_to_string(format!("{dir}/{name}.openssh.pub")).unwrap();
+        let (public, comment) =
+            import_openssh_public_key(&line, *secret.public_key().created_at()).unwrap();
+        assert_eq!(comment, "alice@example.org");
+        assert_key_pair(&secret, &public);
+
+        // OpenSSH export
+        assert_eq!(
+            export_openssh_public_key(public.public_params(), &comment).unwrap(),
+            line.trim_end()
+        );
+
+        let pem = secret
+            .unlock(&Password::empty(), |_, secret| {
+                export_openssh_secret_key(secret, &comment)
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(load_unsigned_secret_key(&pem), secret);
+    }
+}
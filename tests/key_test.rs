@@ -12,21 +12,24 @@ use chrono::{DateTime, Utc};
 use num_traits::ToPrimitive;
 use pgp::{
     armor,
-    composed::{Deserializable, PublicOrSecret, SignedPublicKey, SignedSecretKey},
+    composed::{
+        Deserializable, InnerRingResult, Message, MessageBuilder, PublicOrSecret, SignedPublicKey,
+        SignedSecretKey, TheRing,
+    },
     crypto::{
         ecdsa::SecretKey as ECDSASecretKey, hash::HashAlgorithm, public_key::PublicKeyAlgorithm,
         sym::SymmetricKeyAlgorithm,
     },
     errors::Error,
     packet::{
-        KeyFlags, KnownFeatures, PacketHeader, Signature, SignatureType, Subpacket, SubpacketData,
-        UserAttribute, UserId,
+        KeyFlags, KnownFeatures, PacketHeader, SecretSubkey, Signature, SignatureType, Subpacket,
+        SubpacketData, UserAttribute, UserId,
     },
     ser::Serialize,
     types::{
-        CompressionAlgorithm, Fingerprint, Imprint, KeyDetails, KeyId, KeyVersion, Mpi,
-        PacketHeaderVersion, PacketLength, PlainSecretParams, PublicKeyTrait, PublicParams,
-        S2kParams, SecretParams, SignedUser, StringToKey, Tag,
+        CompressionAlgorithm, EncryptedSecretParams, Fingerprint, GnuStub, Imprint, KeyDetails,
+        KeyId, KeyVersion, Mpi, PacketHeaderVersion, PacketLength, Password, PlainSecretParams,
+        PublicKeyTrait, PublicParams, S2kParams, SecretParams, SignedUser, StringToKey, Tag,
     },
 };
 use rand::SeedableRng;
@@ -1335,6 +1338,10 @@ fn load_adsk_pub() {
     let key_flags = sig.key_flags();
     println!("key_flags {key_flags:?}");
     assert_eq!(key_flags.to_bytes().unwrap(), vec![0x0, 0x04]);
+
+    let validity = public.validity_at(*sig.created().unwrap());
+    let adsks: Vec<_> = validity.adsks().map(|s| s.key.fingerprint()).collect();
+    assert_eq!(adsks, vec![adsk_subkey.fingerprint()]);
 }
 
 /// Handle a test certificate with key flags that span more than a single `u8`.
@@ -1405,3 +1412,82 @@ fn key_imprint_sha256() {
 
     assert_eq!(sha256, expect);
 }
+
+/// A secret key exported with `gpg --export-secret-subkeys`, the primary key is a
+/// `gnu-dummy` stub.
+#[test]
+fn load_gnu_stub_sec() {
+    let _ = pretty_env_logger::try_init();
+
+    let (key, _) = SignedSecretKey::from_armor_file("tests/gnupg/stub.sec.asc").unwrap();
+    key.verify().unwrap();
+
+    assert_eq!(
+        key.primary_key.secret_params().gnu_stub(),
+        Some(&GnuStub::Dummy)
+    );
+    assert!(key
+        .primary_key
+        .unlock(&Password::empty(), |_, _| Ok(()))
+        .is_err());
+    assert_eq!(key.secret_subkeys.len(), 1);
+    assert!(key.secret_subkeys[0]
+        .key
+        .secret_params()
+        .gnu_stub()
+        .is_none());
+
+    let bytes = key.to_bytes().unwrap();
+    assert_eq!(SignedSecretKey::from_bytes(&bytes[..]).unwrap(), key);
+
+    // the encryption subkey is available
+    let mut rng = ChaChaRng::seed_from_u64(0);
+    let mut builder =
+        MessageBuilder::from_bytes("", "hello\n").seipd_v1(&mut rng, SymmetricKeyAlgorithm::AES128);
+    builder
+        .encrypt_to_key(&mut rng, &key.public_key().public_subkeys[0])
+        .unwrap();
+    let encrypted = builder.to_vec(&mut rng).unwrap();
+
+    let key_pw = Password::empty();
+    let ring = TheRing {
+        secret_keys: vec![&key],
+        key_passwords: vec![&key_pw],
+        ..Default::default()
+    };
+    let (mut msg, result) = Message::from_bytes(&encrypted[..])
+        .unwrap()
+        .decrypt_the_ring(ring, true)
+        .unwrap();
+    assert_eq!(result.secret_keys, vec![InnerRingResult::Ok]);
+    assert_eq!(msg.as_data_string().unwrap(), "hello\n");
+
+    // move the encryption subkey to a smartcard
+    let mut on_card = key.clone();
+    let subkey = &mut on_card.secret_subkeys[0];
+    let stub = GnuStub::DivertToCard {
+        serial: vec![0xd2, 0x76, 0x00, 0x01].into(),
+    };
+    let secret_params = SecretParams::Encrypted(EncryptedSecretParams::new_gnu_stub(
+        stub.clone(),
+        KeyVersion::V4,
+    ));
+    subkey.key = SecretSubkey::new(subkey.key.public_key().clone(), secret_params).unwrap();
+    assert_eq!(subkey.key.secret_params().gnu_stub(), Some(&stub));
+
+    let ring = TheRing {
+        secret_keys: vec![&on_card],
+        key_passwords: vec![&key_pw],
+        ..Default::default()
+    };
+    assert!(Message::from_bytes(&encrypted[..])
+        .unwrap()
+        .decrypt_the_ring(ring, true)
+        .is_err());
+
+    let err = on_card.secret_subkeys[0]
+        .key
+        .unlock(&key_pw, |_, _| Ok(()))
+        .unwrap_err();
+    assert!(err.to_string().contains("divert-to-card (serial d2760001)"));
+}
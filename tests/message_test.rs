@@ -14,9 +14,11 @@ use std::fs::File;
 use pgp::{
     composed::{
         CleartextSignedMessage, Deserializable, Message, PlainSessionKey, SignedPublicKey,
-        SignedSecretKey,
+        SignedSecretKey, TheRing,
     },
     crypto::sym::SymmetricKeyAlgorithm,
+    errors::Error,
+    policy::{PolicyViolation, StandardPolicy},
     types::{KeyDetails, KeyId, Password},
 };
 
@@ -392,6 +394,41 @@ fn pgp6_decrypt() {
     assert_eq!(&decrypted, "hello world\n");
 }
 
+/// The standard policy forbids decrypting SED packets, even if the ring allows it.
+#[test]
+fn pgp6_decrypt_policy() {
+    let (skey, _headers) = SignedSecretKey::from_armor_single(
+        std::fs::File::open("./tests/pgp6/alice.sec.asc").unwrap(),
+    )
+    .unwrap();
+    let pw = Password::empty();
+
+    let mut policy = StandardPolicy::default();
+    let decrypt = |policy: &StandardPolicy| {
+        let (msg, _) = Message::from_armor_file("./tests/pgp6/hello.msg").expect("msg");
+        let ring = TheRing {
+            secret_keys: vec![&skey],
+            key_passwords: vec![&pw],
+            allow_legacy: true,
+            policy: Some(policy),
+            ..Default::default()
+        };
+        msg.decrypt_the_ring(ring, true)
+            .map(|(msg, _)| msg.decompress().unwrap().as_data_string().unwrap())
+    };
+
+    let err = decrypt(&policy).unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Policy {
+            source: PolicyViolation::LegacyEncryption
+        }
+    ));
+
+    policy.allow_legacy_encryption = true;
+    assert_eq!(decrypt(&policy).unwrap(), "hello world\n");
+}
+
 /// Tests that decompressing compression quine does not result in stack overflow.
 /// quine.out comes from <https://mumble.net/~campbell/misc/pgp-quine/>
 /// See <https://mumble.net/~campbell/2013/10/08/compression> for details.